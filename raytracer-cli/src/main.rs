@@ -1,13 +1,25 @@
 mod output;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-use output::save;
+use output::{format_of, save, Format, Layer, OutputOptions};
 
 extern crate clap;
 extern crate indicatif;
 extern crate raytracer_lib;
-use clap::{Parser, ValueEnum};
-use raytracer_lib::{parse_scene, public_consts, render};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use raytracer_lib::{
+    parse_scene, render_pass, render_views, AccumulationBuffer, DisplayTransform, RenderSettings,
+    RenderedImage, Scene,
+};
+
+/// Set by Ctrl-C during a progressive render, which then stops after the current pass
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Passes a progressive render takes before the noise is measured, fewer samples than this
+/// say little about it
+const MIN_NOISE_PASSES: u32 = 4;
 
 #[derive(Debug, Clone, ValueEnum)]
 enum AntialiasMethod {
@@ -16,15 +28,83 @@ enum AntialiasMethod {
     Random,
 }
 
+#[derive(Debug, Clone, ValueEnum)]
+enum ToneMapping {
+    Clamp,
+    Reinhard,
+    Aces,
+    Agx,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum TransferFunction {
+    Linear,
+    Srgb,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum Aov {
+    Depth,
+    Normal,
+    Albedo,
+    ShapeId,
+    ShaderId,
+    Direct,
+    Indirect,
+}
+
 #[derive(Parser, Debug)]
 #[command(author = "Reece Holmdahl", version = None, about="Raytracer CLI", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Orbits the camera around the center of the scene, rendering a numbered image at each
+    /// step
+    Turntable(TurntableArgs),
+}
+
+#[derive(Args, Debug)]
+struct RenderArgs {
+    #[command(flatten)]
+    args: RayTracerArgs,
+    /// Frames of the animation to render as `first..last` or a single frame, each to its own
+    /// numbered file such as `out_0001.png`
+    #[arg(long = "frames", value_parser = parse_frames, default_value = None)]
+    frames: Option<(u32, u32)>,
+}
+
+#[derive(Args, Debug)]
+struct TurntableArgs {
+    #[command(flatten)]
+    args: RayTracerArgs,
+    /// Number of images in one turn
+    #[arg(short = 'n', long = "steps", default_value_t = 36,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    steps: u32,
+    /// Angle of the camera above the center of the scene in degrees
+    #[arg(long = "elevation", default_value_t = 20.0)]
+    elevation: f64,
+    /// Distance of the camera from the center of the scene, where the camera is by default
+    #[arg(long = "distance", default_value = None)]
+    distance: Option<f64>,
+}
+
+#[derive(Args, Debug)]
 struct RayTracerArgs {
     #[arg(short = 'x', long = "width", default_value = None)]
     width: Option<u32>,
     #[arg(short = 'y', long = "height", default_value = None)]
     height: Option<u32>,
-    #[arg(short = 'i', long = "scene-path")]
-    scene_path: String,
+    /// Only optional so the top level arguments can be left out for a subcommand
+    #[arg(short = 'i', long = "scene-path", required = true)]
+    scene_path: Option<String>,
     #[arg(short = 'o', long = "output", default_value = "out.png")]
     output_path: String,
     #[arg(short = 'r', long = "rays-per-pixel", default_value = None)]
@@ -39,50 +119,273 @@ struct RayTracerArgs {
     render_normals: bool,
     #[arg(long = "antialias-method", value_enum, default_value = None)]
     antialias_method: Option<AntialiasMethod>,
+    #[arg(long = "shutter-open", default_value = None)]
+    shutter_open: Option<f64>,
+    #[arg(long = "shutter-close", default_value = None)]
+    shutter_close: Option<f64>,
+    /// Camera to render, can be given more than once to render several
+    #[arg(short = 'c', long = "camera")]
+    cameras: Vec<String>,
+    /// Renders every camera in the scene
+    #[arg(long = "all-cameras", default_value_t = false)]
+    all_cameras: bool,
+    /// Leaves the background out, storing the coverage of each pixel as its alpha
+    #[arg(long = "transparent-background", default_value_t = false)]
+    transparent_background: bool,
+    /// Buffer to render alongside the image, can be given more than once. They are layers of
+    /// EXR output and files of their own otherwise, best in a float format like PFM.
+    #[arg(long = "aov", value_enum)]
+    aovs: Vec<Aov>,
+    /// Format of the output, instead of the one matching the extension of the output path
+    #[arg(long = "format", value_enum, default_value = None)]
+    format: Option<Format>,
+    /// Bits per channel of PNG, TIFF and PPM output
+    #[arg(long = "bit-depth", default_value_t = 8, value_parser = parse_bit_depth)]
+    bit_depth: u8,
+    /// Quality of JPEG output from 1 to 100
+    #[arg(long = "quality", default_value_t = 90,
+          value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+    /// Stores EXR output as 16 bit half floats instead of 32 bit floats
+    #[arg(long = "half-float", default_value_t = false)]
+    half_float: bool,
+    /// Writes all the images of a render into one EXR file, the first as the main image and the
+    /// rest as layers named after their views
+    #[arg(long = "exr-layers", default_value_t = false)]
+    exr_layers: bool,
+    /// Brightens low dynamic range output by this many stops before tone mapping
+    #[arg(long = "exposure", default_value = None, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+    /// Compresses bright values into low dynamic range output instead of clamping them
+    #[arg(long = "tone-mapping", value_enum, default_value = None)]
+    tone_mapping: Option<ToneMapping>,
+    /// Encoding of low dynamic range output, sRGB unless the scene says otherwise
+    #[arg(long = "transfer", value_enum, default_value = None)]
+    transfer: Option<TransferFunction>,
+    /// Dithers low dynamic range output to hide banding
+    #[arg(long = "dither", default_value_t = false)]
+    dither: bool,
+    /// Renders in passes that each add a ray to every pixel, so the render can stop early and
+    /// still write what it has. Ctrl-C stops it after the current pass.
+    #[arg(long = "progressive", default_value_t = false)]
+    progressive: bool,
+    /// Passes of a progressive render, the rays per pixel by default. Rays past those are at
+    /// random positions in the pixel. Implies --progressive.
+    #[arg(long = "passes", default_value = None,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    passes: Option<u32>,
+    /// Seconds a progressive render may take, checked after each pass. Implies --progressive.
+    #[arg(long = "time-limit", default_value = None)]
+    time_limit: Option<f64>,
+    /// Stops a progressive render once the standard error of every pixel is at most this, in
+    /// the linear values of the image. Implies --progressive.
+    #[arg(long = "noise-threshold", default_value = None)]
+    noise_threshold: Option<f32>,
+}
+
+impl RayTracerArgs {
+    fn progressive(&self) -> bool {
+        self.progressive
+            || self.passes.is_some()
+            || self.time_limit.is_some()
+            || self.noise_threshold.is_some()
+    }
+}
+
+fn parse_frames(frames: &str) -> Result<(u32, u32), String> {
+    let parse = |frame: &str| {
+        frame
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| format!("invalid frame {}: {}", frame, e))
+    };
+    let (first, last) = match frames.split_once("..") {
+        Some((first, last)) => (parse(first)?, parse(last)?),
+        None => (parse(frames)?, parse(frames)?),
+    };
+    if first > last {
+        return Err(format!("frame {} comes after frame {}", first, last));
+    }
+    Ok((first, last))
+}
+
+fn parse_bit_depth(bit_depth: &str) -> Result<u8, String> {
+    match bit_depth.trim() {
+        "8" => Ok(8),
+        "16" => Ok(16),
+        _ => Err(format!("bit depth must be 8 or 16, not {}", bit_depth)),
+    }
+}
+
+/// `output_path` with `_suffix` added before its extension for each of `suffixes`
+fn image_path(output_path: &str, suffixes: &[String]) -> String {
+    let path = Path::new(output_path);
+    let mut stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+    for suffix in suffixes {
+        stem = format!("{}_{}", stem, suffix);
+    }
+    let file_name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem,
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = RayTracerArgs::parse();
+    let cli = Cli::parse();
 
     #[cfg(debug_assertions)]
-    println!("{:?}", args);
-
-    // read scene path as string
-    let scene_json = std::fs::read_to_string(&args.scene_path)?;
-    let scene_data_path = Path::new(&args.scene_path)
-        .parent()
-        .unwrap()
-        .to_str()
-        .unwrap();
-
-    let scene = parse_scene(
-        &scene_json,
-        &scene_data_path,
-        args.width,
-        args.height,
-        args.aspect_ratio,
-        args.recursion_depth,
-        args.disable_shadows,
-        args.render_normals,
-    )?;
-
-    // #[cfg(debug_assertions)]
-    // println!("{:#?}", scene);
-
-    let rays_per_pixel = args
-        .rays_per_pixel
-        .unwrap_or(public_consts::DEFAULT_RAYS_PER_PIXEL);
-    let sqrt_rays_per_pixel = (rays_per_pixel as f64).sqrt() as u16;
-
-    // error if rays_per_pixel is not a perfect square
-    if sqrt_rays_per_pixel * sqrt_rays_per_pixel != rays_per_pixel {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "rays_per_pixel must be a perfect square",
-        )));
+    println!("{:?}", cli);
+
+    match cli.command {
+        Some(Command::Turntable(turntable)) => render_turntable(turntable),
+        None => render_scene(cli.render),
+    }
+}
+
+fn render_scene(render: RenderArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut scene = parse(&render.args, render.frames.map(|(first, _)| first as f64))?;
+    let frames: Vec<Option<u32>> = match render.frames {
+        Some((first, last)) => (first..=last).map(Some).collect(),
+        None => vec![None],
+    };
+    render_frames(&mut scene, &frames, &render.args, |scene, frame| {
+        scene.set_frame(frame as f64)
+    })
+}
+
+fn render_turntable(turntable: TurntableArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut scene = parse(&turntable.args, None)?;
+    let center = scene.bvh.get_bbox().centroid;
+    let center = [center.x, center.y, center.z];
+    let position = scene.camera().camera_base().basis.position;
+    let offset = [
+        position.x - center[0],
+        position.y - center[1],
+        position.z - center[2],
+    ];
+    // start from where the camera is, orbiting around the y axis
+    let start = offset[0].atan2(offset[2]);
+    let distance = turntable
+        .distance
+        .unwrap_or_else(|| offset.iter().map(|x| x * x).sum::<f64>().sqrt());
+    if distance <= 0.0 {
+        // the camera would sit on the point it looks at
+        return Err("the turntable distance must be positive".into());
     }
+    let elevation = turntable.elevation.to_radians();
+
+    let frames: Vec<Option<u32>> = (1..=turntable.steps).map(Some).collect();
+    render_frames(&mut scene, &frames, &turntable.args, |scene, frame| {
+        let angle =
+            start + 2.0 * std::f64::consts::PI * (frame - 1) as f64 / turntable.steps as f64;
+        let position = [
+            center[0] + distance * elevation.cos() * angle.sin(),
+            center[1] + distance * elevation.sin(),
+            center[2] + distance * elevation.cos() * angle.cos(),
+        ];
+        scene.set_camera_pose(position, center)
+    })
+}
 
-    let pb = indicatif::ProgressBar::new((scene.image_width * scene.image_height) as u64);
+/// Parses the scene, with the CLI args overriding whatever the scene file specifies
+fn parse(args: &RayTracerArgs, frame: Option<f64>) -> Result<Scene, Box<dyn std::error::Error>> {
+    // read scene path as string, clap makes sure there is one
+    let scene_path = args.scene_path.as_deref().unwrap_or_default();
+    let scene_json = std::fs::read_to_string(scene_path)?;
+    let scene_data_path = Path::new(scene_path).parent().unwrap().to_str().unwrap();
+
+    let settings = RenderSettings {
+        image_width: args.width,
+        image_height: args.height,
+        aspect_ratio: args.aspect_ratio,
+        rays_per_pixel: args.rays_per_pixel,
+        recursion_depth: args.recursion_depth,
+        antialias_method: args.antialias_method.as_ref().map(|method| match method {
+            AntialiasMethod::Normal => raytracer_lib::AntialiasMethod::Normal,
+            AntialiasMethod::Jittered => raytracer_lib::AntialiasMethod::Jittered,
+            AntialiasMethod::Random => raytracer_lib::AntialiasMethod::Random,
+        }),
+        disable_shadows: args.disable_shadows.then_some(true),
+        render_normals: args.render_normals.then_some(true),
+        shutter_open: args.shutter_open,
+        shutter_close: args.shutter_close,
+        cameras: (!args.cameras.is_empty()).then(|| args.cameras.clone()),
+        all_cameras: args.all_cameras.then_some(true),
+        frame,
+        exposure: args.exposure,
+        tone_mapping: args
+            .tone_mapping
+            .as_ref()
+            .map(|tone_mapping| match tone_mapping {
+                ToneMapping::Clamp => raytracer_lib::ToneMapping::Clamp,
+                ToneMapping::Reinhard => raytracer_lib::ToneMapping::Reinhard,
+                ToneMapping::Aces => raytracer_lib::ToneMapping::Aces,
+                ToneMapping::Agx => raytracer_lib::ToneMapping::Agx,
+            }),
+        transfer: args.transfer.as_ref().map(|transfer| match transfer {
+            TransferFunction::Linear => raytracer_lib::TransferFunction::Linear,
+            TransferFunction::Srgb => raytracer_lib::TransferFunction::Srgb,
+        }),
+        dither: args.dither.then_some(true),
+        transparent_background: args.transparent_background.then_some(true),
+        aovs: (!args.aovs.is_empty()).then(|| {
+            args.aovs
+                .iter()
+                .map(|aov| match aov {
+                    Aov::Depth => raytracer_lib::Aov::Depth,
+                    Aov::Normal => raytracer_lib::Aov::Normal,
+                    Aov::Albedo => raytracer_lib::Aov::Albedo,
+                    Aov::ShapeId => raytracer_lib::Aov::ShapeId,
+                    Aov::ShaderId => raytracer_lib::Aov::ShaderId,
+                    Aov::Direct => raytracer_lib::Aov::Direct,
+                    Aov::Indirect => raytracer_lib::Aov::Indirect,
+                })
+                .collect()
+        }),
+    };
+
+    parse_scene(&scene_json, scene_data_path, &settings)
+}
+
+/// Renders each of `frames`, posing the scene with `pose` first, or the scene as it is for
+/// `None`. The scene is parsed once, only its cameras change between frames.
+fn render_frames(
+    scene: &mut Scene,
+    frames: &[Option<u32>],
+    args: &RayTracerArgs,
+    pose: impl Fn(&mut Scene, u32) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let progressive = args.progressive();
+    let passes = args
+        .passes
+        .unwrap_or(scene.settings.rays_per_pixel() as u32);
+    if progressive {
+        if !scene.is_single_image() {
+            return Err("a progressive render can only have one mono camera".into());
+        }
+        if !scene.settings.aovs().is_empty() {
+            return Err("a progressive render can't have AOVs".into());
+        }
+        ctrlc::set_handler(|| {
+            // a second Ctrl-C doesn't wait for the pass to finish
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+        })?;
+    }
+
+    let pixels_per_frame = match progressive {
+        true => scene.pixel_count() * passes as u64,
+        false => scene.pixel_count(),
+    };
+    let pb = indicatif::ProgressBar::new(pixels_per_frame * frames.len() as u64);
 
     pb.set_style(indicatif::ProgressStyle::default_bar().template("{wide_bar} {percent}% ")?);
 
@@ -90,16 +393,173 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         pb.inc(1);
     };
 
-    let aa_method = match args.antialias_method {
-        Some(AntialiasMethod::Normal) => raytracer_lib::AntialiasMethod::Normal,
-        Some(AntialiasMethod::Jittered) => raytracer_lib::AntialiasMethod::Jittered,
-        Some(AntialiasMethod::Random) => raytracer_lib::AntialiasMethod::Random,
-        None => raytracer_lib::AntialiasMethod::Normal,
+    let options = OutputOptions {
+        format: args.format,
+        half_float: args.half_float,
+        bit_depth: args.bit_depth,
+        quality: args.quality,
+        display: scene.settings.display_transform(),
+        alpha: scene.settings.transparent_background(),
     };
-
-    let fb = render(&scene, sqrt_rays_per_pixel, aa_method, Some(&per_pixel_cb));
-    save(args.output_path.as_str(), &fb);
+    let format = format_of(&args.output_path, args.format)?;
+    // AOVs hold values rather than colors, so they are stored as they are
+    let aov_options = OutputOptions {
+        display: DisplayTransform {
+            scale: 1.0,
+            tone_mapping: raytracer_lib::ToneMapping::Clamp,
+            transfer: raytracer_lib::TransferFunction::Linear,
+            dither: false,
+        },
+        alpha: false,
+        ..options.clone()
+    };
+    for &frame in frames {
+        if let Some(frame) = frame {
+            pose(scene, frame)?;
+        }
+        let frame_suffix: Vec<String> = frame.iter().map(|f| format!("{:04}", f)).collect();
+        if progressive {
+            let accumulation = render_progressive(scene, passes, args, &per_pixel_cb, &pb);
+            let samples = accumulation.sample_counts();
+            let variances = accumulation.variances();
+            let layers = match format {
+                Format::Exr => vec![
+                    Layer {
+                        channels: &["count"],
+                        full_float: true,
+                        ..Layer::rgb("samples".to_string(), &samples)
+                    },
+                    Layer::rgb("variance".to_string(), &variances),
+                ],
+                _ => Vec::new(),
+            };
+            save(
+                &image_path(&args.output_path, &frame_suffix),
+                &accumulation.resolve(),
+                &layers,
+                &options,
+            )?;
+            // the frames after an interrupted one aren't rendered at all
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                break;
+            }
+            continue;
+        }
+        let images = render_views(scene, Some(&per_pixel_cb));
+        if args.exr_layers {
+            let (main, rest) = images.split_first().ok_or("nothing to render")?;
+            let mut layers = aov_layers(scene, main, None);
+            for image in rest {
+                layers.push(Layer {
+                    alpha: options.alpha,
+                    ..Layer::rgb(image.name.clone(), &image.framebuffer)
+                });
+                layers.extend(aov_layers(scene, image, Some(&image.name)));
+            }
+            save(
+                &image_path(&args.output_path, &frame_suffix),
+                &main.framebuffer,
+                &layers,
+                &options,
+            )?;
+            continue;
+        }
+        // a single image goes straight to the output path, several get their names appended
+        let single = images.len() == 1;
+        for image in &images {
+            let view_suffix = (!single).then(|| image.name.clone());
+            let path = |aov: Option<&str>| {
+                let suffixes: Vec<String> = view_suffix
+                    .iter()
+                    .cloned()
+                    .chain(aov.map(str::to_string))
+                    .chain(frame_suffix.iter().cloned())
+                    .collect();
+                image_path(&args.output_path, &suffixes)
+            };
+            if format == Format::Exr {
+                let layers = aov_layers(scene, image, None);
+                save(&path(None), &image.framebuffer, &layers, &options)?;
+                continue;
+            }
+            save(&path(None), &image.framebuffer, &[], &options)?;
+            for (aov, fb) in &image.aovs {
+                save(&path(Some(aov.name())), fb, &[], &aov_options)?;
+            }
+        }
+    }
     pb.finish_with_message("Render complete");
 
     Ok(())
 }
+
+/// Refines the image of the main camera one pass at a time until it has `passes` rays per
+/// pixel, stopping early at the time limit, under the noise threshold or on Ctrl-C
+fn render_progressive(
+    scene: &Scene,
+    passes: u32,
+    args: &RayTracerArgs,
+    per_pixel_cb: &dyn Fn(),
+    pb: &indicatif::ProgressBar,
+) -> AccumulationBuffer {
+    let start = Instant::now();
+    let mut accumulation =
+        AccumulationBuffer::new(scene.settings.image_width(), scene.settings.image_height());
+    for pass in 1..=passes {
+        render_pass(&mut accumulation, scene, Some(per_pixel_cb));
+        let reason = if INTERRUPTED.load(Ordering::SeqCst) {
+            "interrupted"
+        } else if args
+            .time_limit
+            .is_some_and(|limit| start.elapsed().as_secs_f64() >= limit)
+        {
+            "out of time"
+        } else if args
+            .noise_threshold
+            .is_some_and(|threshold| pass >= MIN_NOISE_PASSES && noise(&accumulation) <= threshold)
+        {
+            "noise under the threshold"
+        } else {
+            continue;
+        };
+        if pass < passes {
+            pb.suspend(|| eprintln!("Stopped after {} of {} passes, {}", pass, passes, reason));
+        }
+        break;
+    }
+    accumulation
+}
+
+/// Largest standard error of any channel of any pixel
+fn noise(accumulation: &AccumulationBuffer) -> f32 {
+    let mut noise: f32 = 0.0;
+    for i in 0..accumulation.width {
+        for j in 0..accumulation.height {
+            noise = noise.max(accumulation.standard_error(i, j).max());
+        }
+    }
+    noise
+}
+
+/// EXR layers for the AOVs of `image`, named after the AOV and prefixed with `view` if given
+fn aov_layers<'a>(scene: &Scene, image: &'a RenderedImage, view: Option<&str>) -> Vec<Layer<'a>> {
+    image
+        .aovs
+        .iter()
+        .map(|(aov, fb)| Layer {
+            name: match view {
+                Some(view) => format!("{}.{}", view, aov.name()),
+                None => aov.name().to_string(),
+            },
+            fb,
+            channels: aov.channels(),
+            alpha: false,
+            full_float: aov.is_id(),
+            names: match aov {
+                raytracer_lib::Aov::ShapeId => scene.shape_names(),
+                raytracer_lib::Aov::ShaderId => scene.shader_names(),
+                _ => Vec::new(),
+            },
+        })
+        .collect()
+}
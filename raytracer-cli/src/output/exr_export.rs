@@ -0,0 +1,132 @@
+use exr::prelude::*;
+use raytracer_lib::Framebuffer;
+
+use super::Layer as OutputLayer;
+
+/// Linear OpenEXR with the main image in the `R`, `G`, `B` and optionally `A` channels and
+/// each of `layers` in channels prefixed with its name, such as `depth.Z`
+pub(crate) fn save_to_exr(
+    output_path: &str,
+    fb: &Framebuffer,
+    layers: &[OutputLayer],
+    half_float: bool,
+    alpha: bool,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let main = OutputLayer {
+        alpha,
+        ..OutputLayer::rgb(String::new(), fb)
+    };
+    let mut channels = Vec::new();
+    let mut attributes = LayerAttributes::default();
+    for layer in std::iter::once(&main).chain(layers) {
+        let prefix = if layer.name.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", layer.name)
+        };
+        let layer_fb = layer.fb;
+        if (layer_fb.width, layer_fb.height) != (fb.width, fb.height) {
+            return Err(format!(
+                "layer {} is {}x{} but the image is {}x{}",
+                layer.name, layer_fb.width, layer_fb.height, fb.width, fb.height
+            )
+            .into());
+        }
+        let alpha_channel = layer.alpha.then_some("A");
+        for (c, channel) in layer.channels.iter().chain(&alpha_channel).enumerate() {
+            // rows top to bottom, the alpha after the channels of the framebuffer
+            let color_channels = layer.channels.len();
+            let values = (0..layer_fb.height).rev().flat_map(move |y| {
+                (0..layer_fb.width).map(move |x| {
+                    if c < color_channels {
+                        layer_fb.get_pixel(x, y)[c]
+                    } else {
+                        layer_fb.get_alpha(x, y)
+                    }
+                })
+            });
+            let samples = if half_float && !layer.full_float {
+                FlatSamples::F16(values.map(f16::from_f32).collect())
+            } else {
+                FlatSamples::F32(values.collect())
+            };
+            channels.push(AnyChannel::new(
+                format!("{}{}", prefix, channel).as_str(),
+                samples,
+            ));
+        }
+        if !layer.names.is_empty() {
+            let names = layer.names.iter().map(|name| Text::from(name.as_str()));
+            attributes.other.insert(
+                Text::from(format!("{}names", prefix).as_str()),
+                AttributeValue::TextVector(names.collect()),
+            );
+        }
+    }
+
+    let layer = Layer::new(
+        (fb.width as usize, fb.height as usize),
+        attributes,
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(SmallVec::from_vec(channels)),
+    );
+    Image::from_layer(layer).write().to_file(output_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x2 image whose pixel `(i, j)` is `(i, j, 10 * i + j)` with alpha 0.25 in the bottom
+    /// row and 0.75 in the top row
+    fn gradient() -> Framebuffer {
+        let mut fb = Framebuffer::new(2, 2);
+        fb.pixels = vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 10.0],
+            [0.0, 1.0, 1.0],
+            [1.0, 1.0, 11.0],
+        ];
+        fb.alpha = vec![0.25, 0.25, 0.75, 0.75];
+        fb
+    }
+
+    #[test]
+    fn test_exr_channels_and_layers() {
+        let fb = gradient();
+        let depth = OutputLayer {
+            channels: &["Z"],
+            alpha: true,
+            ..OutputLayer::rgb("depth".to_string(), &fb)
+        };
+        let albedo = OutputLayer::rgb("albedo".to_string(), &fb);
+        let path = std::env::temp_dir().join("raytracer_cli_layers.exr");
+        let path = path.to_string_lossy();
+        save_to_exr(&path, &fb, &[depth, albedo], false, true).unwrap();
+
+        let image = read_all_flat_layers_from_file(path.as_ref()).unwrap();
+        std::fs::remove_file(path.as_ref()).unwrap();
+        let channels = &image.layer_data[0].channel_data.list;
+        let channel = |name: &str| -> Vec<f32> {
+            let channel = channels
+                .iter()
+                .find(|channel| channel.name == *name)
+                .unwrap_or_else(|| panic!("no channel {}", name));
+            channel.sample_data.values_as_f32().collect()
+        };
+        let names: Vec<String> = channels.iter().map(|c| c.name.to_string()).collect();
+        assert_eq!(
+            names,
+            ["A", "B", "G", "R", "albedo.B", "albedo.G", "albedo.R", "depth.A", "depth.Z"]
+        );
+        // rows top to bottom
+        assert_eq!(channel("R"), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(channel("G"), [1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(channel("albedo.B"), [1.0, 11.0, 0.0, 10.0]);
+        assert_eq!(channel("A"), [0.75, 0.75, 0.25, 0.25]);
+        // a layer with one channel has its alpha after it
+        assert_eq!(channel("depth.Z"), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(channel("depth.A"), [0.75, 0.75, 0.25, 0.25]);
+    }
+}
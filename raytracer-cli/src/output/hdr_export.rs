@@ -0,0 +1,28 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use image::codecs::hdr::HdrEncoder;
+use image::Rgb;
+
+/// Radiance RGBE, which keeps the range but not the sign, so negative values become zero
+pub(crate) fn save_to_hdr(
+    output_path: &str,
+    fb: &raytracer_lib::Framebuffer,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut pixels = Vec::with_capacity(fb.pixels.len());
+    // rows top to bottom
+    for y in (0..fb.height).rev() {
+        for x in 0..fb.width {
+            let color = fb.get_pixel(x, y);
+            pixels.push(Rgb([
+                color[0].max(0.0),
+                color[1].max(0.0),
+                color[2].max(0.0),
+            ]));
+        }
+    }
+
+    let writer = BufWriter::new(File::create(output_path)?);
+    HdrEncoder::new(writer).encode(&pixels, fb.width as usize, fb.height as usize)?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat};
+use raytracer_lib::{DisplayTransform, Framebuffer};
+
+/// Tone maps `fb` into integer channels of `bit_depth` bits and encodes it as `format`, with
+/// straight alpha if `alpha` is set
+pub(crate) fn save_ldr(
+    output_path: &str,
+    fb: &Framebuffer,
+    display: &DisplayTransform,
+    bit_depth: u8,
+    alpha: bool,
+    format: ImageOutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let max = if bit_depth == 16 {
+        u16::MAX as u32
+    } else {
+        u8::MAX as u32
+    };
+    // rows top to bottom
+    let rgba = |x: u32, y: u32| {
+        let j = fb.height - y - 1;
+        display.quantize_rgba(fb.get_pixel(x, j), fb.get_alpha(x, j), x, j, max)
+    };
+    let rgb = |x: u32, y: u32| {
+        let j = fb.height - y - 1;
+        display.quantize(fb.get_pixel(x, j), x, j, max)
+    };
+    let img = match (bit_depth == 16, alpha) {
+        (true, true) => {
+            DynamicImage::ImageRgba16(ImageBuffer::from_fn(fb.width, fb.height, |x, y| {
+                image::Rgba(rgba(x, y).map(|c| c as u16))
+            }))
+        }
+        (true, false) => {
+            DynamicImage::ImageRgb16(ImageBuffer::from_fn(fb.width, fb.height, |x, y| {
+                image::Rgb(rgb(x, y).map(|c| c as u16))
+            }))
+        }
+        (false, true) => {
+            DynamicImage::ImageRgba8(ImageBuffer::from_fn(fb.width, fb.height, |x, y| {
+                image::Rgba(rgba(x, y).map(|c| c as u8))
+            }))
+        }
+        (false, false) => {
+            DynamicImage::ImageRgb8(ImageBuffer::from_fn(fb.width, fb.height, |x, y| {
+                image::Rgb(rgb(x, y).map(|c| c as u8))
+            }))
+        }
+    };
+
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    img.write_to(&mut writer, format)?;
+    Ok(())
+}
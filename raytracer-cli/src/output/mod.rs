@@ -1,17 +1,208 @@
-mod png_export;
-
-use self::png_export::save_to_png;
-use raytracer_lib::Framebuffer;
+mod exr_export;
+mod hdr_export;
+mod ldr_export;
+mod pnm_export;
+use self::exr_export::save_to_exr;
+use self::hdr_export::save_to_hdr;
+use self::ldr_export::save_ldr;
+use self::pnm_export::{save_to_pfm, save_to_ppm};
+use clap::ValueEnum;
+use image::ImageOutputFormat;
+use raytracer_lib::{DisplayTransform, Framebuffer};
 use std::path::Path;
 
-pub(crate) fn save(output_path: &str, fb: &Framebuffer) {
-    if let Some(ext) = Path::new(output_path).extension() {
-        if let Some(ext) = ext.to_str() {
-            if ext == "png" {
-                save_to_png(output_path, fb);
-            } else {
-                unimplemented!("The format '{}' is not supported", ext)
-            }
+/// Image file formats, picked from the extension of the output path unless given explicitly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    Png,
+    Jpeg,
+    /// Lossless WebP
+    Webp,
+    Tiff,
+    Ppm,
+    /// Portable float map, linear like EXR
+    Pfm,
+    Exr,
+    /// Radiance RGBE
+    Hdr,
+}
+
+impl Format {
+    fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "webp" => Some(Format::Webp),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "ppm" => Some(Format::Ppm),
+            "pfm" => Some(Format::Pfm),
+            "exr" => Some(Format::Exr),
+            "hdr" => Some(Format::Hdr),
+            _ => None,
         }
     }
+
+    /// Whether the format has 16 bit integer channels
+    fn has_16_bit(self) -> bool {
+        matches!(self, Format::Png | Format::Tiff | Format::Ppm)
+    }
+
+    fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            Format::Png | Format::Webp | Format::Tiff | Format::Exr
+        )
+    }
+}
+
+impl std::fmt::Display for Format {
+    /// The name `--format` takes
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no format is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Named image stored alongside the main one
+pub(crate) struct Layer<'a> {
+    pub name: String,
+    pub fb: &'a Framebuffer,
+    /// Names of the channels, holding the first channels of `fb`
+    pub channels: &'static [&'static str],
+    /// Stores the alpha of `fb` in an `A` channel
+    pub alpha: bool,
+    /// Keeps 32 bit floats for half float output, for integers too large for half floats
+    pub full_float: bool,
+    /// What the values of an ID layer stand for, the name of ID `i` is at `i - 1`
+    pub names: Vec<String>,
+}
+
+impl<'a> Layer<'a> {
+    pub fn rgb(name: String, fb: &'a Framebuffer) -> Self {
+        Self {
+            name,
+            fb,
+            channels: &["R", "G", "B"],
+            alpha: false,
+            full_float: false,
+            names: Vec::new(),
+        }
+    }
+}
+
+/// Settings for the formats that have them
+#[derive(Debug, Clone)]
+pub(crate) struct OutputOptions {
+    /// Overrides the format picked from the extension
+    pub format: Option<Format>,
+    /// Stores EXR channels as 16 bit half floats instead of 32 bit floats
+    pub half_float: bool,
+    /// Bits per channel of PNG, TIFF and PPM, either 8 or 16
+    pub bit_depth: u8,
+    /// JPEG quality from 1 to 100
+    pub quality: u8,
+    /// Applied to low dynamic range formats, high dynamic range ones keep the linear values
+    pub display: DisplayTransform,
+    /// Stores the alpha of the image, premultiplied in EXR and straight in the other formats as
+    /// they expect
+    pub alpha: bool,
+}
+
+/// `format` if there is one, otherwise the format matching the extension of `output_path`
+pub(crate) fn format_of(
+    output_path: &str,
+    format: Option<Format>,
+) -> Result<Format, Box<dyn std::error::Error>> {
+    match format {
+        Some(format) => Ok(format),
+        None => {
+            let Some(ext) = Path::new(output_path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+            else {
+                return Err(format!(
+                    "{} has no file extension to pick a format from, use --format",
+                    output_path
+                )
+                .into());
+            };
+            Format::from_extension(ext)
+                .ok_or_else(|| format!("the format '{}' is not supported", ext).into())
+        }
+    }
+}
+
+/// Saves `fb` in the format of `options` or the one matching the extension of `output_path`.
+/// `layers` are images of the same size stored alongside it, which only EXR can hold.
+pub(crate) fn save(
+    output_path: &str,
+    fb: &Framebuffer,
+    layers: &[Layer],
+    options: &OutputOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = format_of(output_path, options.format)?;
+    if !layers.is_empty() && format != Format::Exr {
+        return Err(format!("the format '{}' can't store layers", format).into());
+    }
+    if options.alpha && !format.has_alpha() {
+        return Err(format!("the format '{}' has no alpha channel", format).into());
+    }
+    if options.bit_depth == 16 && !format.has_16_bit() {
+        return Err(format!("the format '{}' can't store 16 bit channels", format).into());
+    }
+
+    let display = &options.display;
+    let bit_depth = options.bit_depth;
+    match format {
+        Format::Png => save_ldr(
+            output_path,
+            fb,
+            display,
+            bit_depth,
+            options.alpha,
+            ImageOutputFormat::Png,
+        ),
+        Format::Jpeg => save_ldr(
+            output_path,
+            fb,
+            display,
+            bit_depth,
+            options.alpha,
+            ImageOutputFormat::Jpeg(options.quality),
+        ),
+        Format::Webp => save_ldr(
+            output_path,
+            fb,
+            display,
+            bit_depth,
+            options.alpha,
+            ImageOutputFormat::WebP,
+        ),
+        Format::Tiff => save_ldr(
+            output_path,
+            fb,
+            display,
+            bit_depth,
+            options.alpha,
+            ImageOutputFormat::Tiff,
+        ),
+        Format::Ppm => save_to_ppm(output_path, fb, display, bit_depth),
+        Format::Pfm => save_to_pfm(output_path, fb),
+        Format::Exr => save_to_exr(output_path, fb, layers, options.half_float, options.alpha),
+        Format::Hdr => save_to_hdr(output_path, fb),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_are_named_like_their_flag() {
+        let error = format_of("render.bmp", None).unwrap_err();
+        assert_eq!(error.to_string(), "the format 'bmp' is not supported");
+        assert_eq!(format_of("render.JPG", None).unwrap(), Format::Jpeg);
+        assert_eq!(Format::Jpeg.to_string(), "jpeg");
+        assert_eq!(Format::Pfm.to_string(), "pfm");
+    }
 }
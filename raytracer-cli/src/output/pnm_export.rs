@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use raytracer_lib::{DisplayTransform, Framebuffer};
+
+/// Binary PPM, the simplest format most tools can still open. 16 bit channels are big endian.
+pub(crate) fn save_to_ppm(
+    output_path: &str,
+    fb: &Framebuffer,
+    display: &DisplayTransform,
+    bit_depth: u8,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let max = if bit_depth == 16 {
+        u16::MAX
+    } else {
+        u8::MAX as u16
+    };
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    write!(writer, "P6\n{} {}\n{}\n", fb.width, fb.height, max)?;
+    // rows top to bottom
+    for j in (0..fb.height).rev() {
+        for i in 0..fb.width {
+            for c in display.quantize(fb.get_pixel(i, j), i, j, max as u32) {
+                if bit_depth == 16 {
+                    writer.write_all(&(c as u16).to_be_bytes())?;
+                } else {
+                    writer.write_all(&[c as u8])?;
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Portable float map, the linear framebuffer as it is
+pub(crate) fn save_to_pfm(
+    output_path: &str,
+    fb: &Framebuffer,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    // a negative scale marks little endian, rows go bottom to top like the framebuffer
+    write!(writer, "PF\n{} {}\n-1.0\n", fb.width, fb.height)?;
+    for pixel in &fb.pixels {
+        for c in pixel {
+            writer.write_all(&c.to_le_bytes())?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pfm_rows_go_bottom_to_top() {
+        let mut fb = Framebuffer::new(2, 2);
+        // bottom left and top right, the framebuffer's rows go bottom to top
+        fb.pixels[0] = [1.0, 2.0, 3.0];
+        fb.pixels[3] = [4.0, 5.0, 6.0];
+        let path = std::env::temp_dir().join("raytracer_cli_rows.pfm");
+        save_to_pfm(&path.to_string_lossy(), &fb).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let values: Vec<f32> = bytes[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        // the bottom left pixel comes first and the top right one last
+        assert_eq!(
+            values,
+            [1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0, 5.0, 6.0]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{prelude::*, Framebuffer};
+
+/// Running average of the samples added to each pixel over any number of passes, which can be
+/// turned into an image at any moment. Pixels refine independently, so they may have different
+/// numbers of samples.
+#[derive(Clone)]
+pub struct AccumulationBuffer {
+    pub width: u32,
+    pub height: u32,
+    mean: Vec<[f32; 3]>,
+    /// Sum of squared differences from the mean, for the variance
+    m2: Vec<[f32; 3]>,
+    samples: Vec<u32>,
+    /// Samples that hit something, for the alpha
+    hits: Vec<u32>,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            mean: vec![[0.0; 3]; size],
+            m2: vec![[0.0; 3]; size],
+            samples: vec![0; size],
+            hits: vec![0; size],
+        }
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        (i + j * self.width) as usize
+    }
+
+    /// Adds the color of one ray through pixel `(i, j)`, `hit` if it hit something
+    pub fn add_sample(&mut self, i: u32, j: u32, color: Color, hit: bool) {
+        let idx = self.index(i, j);
+        self.samples[idx] += 1;
+        self.hits[idx] += hit as u32;
+        // Welford's update, which stays accurate over many samples
+        let n = self.samples[idx] as f32;
+        for c in 0..3 {
+            let delta = color[c] - self.mean[idx][c];
+            self.mean[idx][c] += delta / n;
+            self.m2[idx][c] += delta * (color[c] - self.mean[idx][c]);
+        }
+    }
+
+    pub fn sample_count(&self, i: u32, j: u32) -> u32 {
+        self.samples[self.index(i, j)]
+    }
+
+    /// Fewest samples any pixel has
+    pub fn min_sample_count(&self) -> u32 {
+        self.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    pub fn mean(&self, i: u32, j: u32) -> Color {
+        let mean = self.mean[self.index(i, j)];
+        Color::new(mean[0], mean[1], mean[2])
+    }
+
+    /// Sample variance of the rays through the pixel, zero until it has two samples
+    pub fn variance(&self, i: u32, j: u32) -> Color {
+        let idx = self.index(i, j);
+        let n = self.samples[idx];
+        if n < 2 {
+            return Color::zeros();
+        }
+        let m2 = self.m2[idx];
+        Color::new(m2[0], m2[1], m2[2]) / (n - 1) as f32
+    }
+
+    /// How far the pixel's color is likely off from what infinitely many samples would give
+    pub fn standard_error(&self, i: u32, j: u32) -> Color {
+        let n = self.sample_count(i, j).max(1) as f32;
+        (self.variance(i, j) / n).map(f32::sqrt)
+    }
+
+    /// The image as it is now, pixels without samples are black and transparent
+    pub fn resolve(&self) -> Framebuffer {
+        let mut fb = Framebuffer::new(self.width, self.height);
+        for (idx, mean) in self.mean.iter().enumerate() {
+            fb.pixels[idx] = *mean;
+            fb.alpha[idx] = match self.samples[idx] {
+                0 => 0.0,
+                n => self.hits[idx] as f32 / n as f32,
+            };
+        }
+        fb
+    }
+
+    /// Number of samples of each pixel in all three channels
+    pub fn sample_counts(&self) -> Framebuffer {
+        let mut fb = Framebuffer::new(self.width, self.height);
+        for (pixel, n) in fb.pixels.iter_mut().zip(&self.samples) {
+            *pixel = [*n as f32; 3];
+        }
+        fb
+    }
+
+    /// Variance of each pixel, see [`AccumulationBuffer::variance`]
+    pub fn variances(&self) -> Framebuffer {
+        let mut fb = Framebuffer::new(self.width, self.height);
+        for i in 0..self.width {
+            for j in 0..self.height {
+                fb.set_pixel(i, j, self.variance(i, j));
+            }
+        }
+        fb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    #[test]
+    fn test_accumulation_mean_and_variance() {
+        let mut accumulation = AccumulationBuffer::new(2, 1);
+        for value in [1.0, 2.0, 3.0, 6.0] {
+            accumulation.add_sample(0, 0, color!(value, 2.0 * value, 0.0), value > 1.0);
+        }
+        assert_eq!(accumulation.sample_count(0, 0), 4);
+        assert_eq!(accumulation.min_sample_count(), 0);
+        assert!((accumulation.mean(0, 0) - color!(3.0, 6.0, 0.0)).norm() < 1e-6);
+        // squared differences 4 + 1 + 0 + 9 over 3
+        assert!((accumulation.variance(0, 0) - color!(14.0 / 3.0, 56.0 / 3.0, 0.0)).norm() < 1e-5);
+
+        let fb = accumulation.resolve();
+        assert_eq!(fb.get_alpha(0, 0), 0.75);
+        assert_eq!(fb.get_alpha(1, 0), 0.0);
+    }
+}
@@ -1,11 +1,16 @@
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum AntialiasMethod {
+    #[serde(alias = "Normal")]
     Normal,
+    #[serde(alias = "Jittered")]
     Jittered,
+    #[serde(alias = "Random")]
     Random,
 }
 
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Framebuffer;
+
+/// Arbitrary output variable, a buffer rendered in the same pass as the image for compositing.
+/// The values are raw, not colors meant to be looked at, so they are best stored as floats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Aov {
+    /// Distance along the ray to the nearest surface seen in the pixel, infinite for the
+    /// background
+    Depth,
+    /// World space normal averaged over the pixel
+    Normal,
+    /// Surface color without lighting
+    Albedo,
+    /// Shape covering most of the pixel, see [`Scene::shape_names`](crate::Scene::shape_names)
+    #[serde(alias = "shapeID")]
+    ShapeId,
+    /// Shader covering most of the pixel, see
+    /// [`Scene::shader_names`](crate::Scene::shader_names)
+    #[serde(alias = "shaderID")]
+    ShaderId,
+    /// Light reaching the surfaces seen straight from the lights
+    Direct,
+    /// Light arriving at the surfaces seen through reflections
+    Indirect,
+}
+
+impl Aov {
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ShapeId => "shape_id",
+            Aov::ShaderId => "shader_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+
+    /// Names of the channels the AOV uses, which are the first channels of its framebuffer
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::ShapeId | Aov::ShaderId => &["id"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect => &["R", "G", "B"],
+        }
+    }
+
+    /// Whether the values are IDs, which can't be blended or stored in less than 32 bits
+    pub fn is_id(self) -> bool {
+        matches!(self, Aov::ShapeId | Aov::ShaderId)
+    }
+}
+
+/// One image of a render with the AOVs rendered alongside it
+pub struct RenderedImage {
+    pub name: String,
+    pub framebuffer: Framebuffer,
+    /// In the order the AOVs were asked for in the settings
+    pub aovs: Vec<(Aov, Framebuffer)>,
+}
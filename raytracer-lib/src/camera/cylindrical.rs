@@ -0,0 +1,71 @@
+use super::*;
+use crate::math::Ray;
+
+/// Panorama projected onto a cylinder around the up axis, so vertical lines stay straight
+#[derive(Debug)]
+pub struct CylindricalCamera {
+    base: CameraBase,
+    /// Angle around the cylinder covered by the image, up to 2π
+    horizontal_fov: Real,
+    /// Height of the image on a cylinder of radius 1
+    height: Real,
+}
+
+impl CylindricalCamera {
+    /// Both fields of view are in radians, the vertical one less than π
+    pub fn new(
+        basis: CoordinateSystem,
+        aspect_ratio: Real,
+        horizontal_fov: Real,
+        vertical_fov: Real,
+    ) -> Self {
+        Self {
+            base: CameraBase::new(basis, None, aspect_ratio),
+            horizontal_fov,
+            height: 2.0 * (vertical_fov / 2.0).tan(),
+        }
+    }
+}
+
+impl Camera for CylindricalCamera {
+    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real, time: Real) -> Option<Ray> {
+        let (x, y) = self.base.get_image_position(i, j, di, dj);
+        let angle = (x - 0.5) * self.horizontal_fov;
+        let basis = self.base.basis_at(time);
+        Some(Ray {
+            origin: basis.position,
+            direction: basis.u * angle.sin() - basis.w * angle.cos()
+                + basis.v * (y - 0.5) * self.height,
+            time,
+        })
+    }
+
+    fn camera_base(&self) -> &CameraBase {
+        &self.base
+    }
+    fn camera_base_mut(&mut self) -> &mut CameraBase {
+        &mut self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cylindrical_edges_are_half_the_fov() {
+        let basis = CoordinateSystem::new(P3::origin(), &-V3::z());
+        let mut camera = CylindricalCamera::new(basis, 2.0, PI, PI / 2.0);
+        camera.set_image_pixels(100, 50);
+        let direction = |i, j| {
+            let ray = camera.generate_ray(i, j, 0.0, 0.0, 0.0).unwrap();
+            ray.direction.normalize()
+        };
+        assert!((direction(50, 25) + V3::z()).norm() < 1e-4);
+        assert!((direction(100, 25) - V3::x()).norm() < 1e-4);
+        assert!((direction(0, 25) + V3::x()).norm() < 1e-4);
+        // 45° up at the top, where vertical lines stay vertical
+        assert!((direction(50, 50) - V3::new(0.0, 1.0, -1.0).normalize()).norm() < 1e-4);
+        assert!((direction(100, 50) - V3::new(1.0, 1.0, 0.0).normalize()).norm() < 1e-4);
+    }
+}
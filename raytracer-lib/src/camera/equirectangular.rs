@@ -0,0 +1,63 @@
+use super::*;
+use crate::math::Ray;
+
+/// Full 360° panorama, with longitude across the image and latitude up it, as used for VR.
+/// The center of the image looks down the view direction.
+#[derive(Debug)]
+pub struct EquirectangularCamera {
+    base: CameraBase,
+}
+
+impl EquirectangularCamera {
+    pub fn new(basis: CoordinateSystem, aspect_ratio: Real) -> Self {
+        Self {
+            base: CameraBase::new(basis, None, aspect_ratio),
+        }
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real, time: Real) -> Option<Ray> {
+        let (x, y) = self.base.get_image_position(i, j, di, dj);
+        let longitude = (x - 0.5) * 2.0 * PI;
+        let latitude = (y - 0.5) * PI;
+        let basis = self.base.basis_at(time);
+        Some(Ray {
+            origin: basis.position,
+            direction: (basis.u * longitude.sin() - basis.w * longitude.cos()) * latitude.cos()
+                + basis.v * latitude.sin(),
+            time,
+        })
+    }
+
+    fn camera_base(&self) -> &CameraBase {
+        &self.base
+    }
+    fn camera_base_mut(&mut self) -> &mut CameraBase {
+        &mut self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equirectangular_covers_the_sphere() {
+        let basis = CoordinateSystem::new(P3::origin(), &-V3::z());
+        let mut camera = EquirectangularCamera::new(basis, 2.0);
+        camera.set_image_pixels(200, 100);
+        let direction = |i, j| {
+            let ray = camera.generate_ray(i, j, 0.0, 0.0, 0.0).unwrap();
+            ray.direction.normalize()
+        };
+        assert!((direction(100, 50) + V3::z()).norm() < 1e-4);
+        assert!((direction(150, 50) - V3::x()).norm() < 1e-4);
+        assert!((direction(50, 50) + V3::x()).norm() < 1e-4);
+        // the left and right edges meet behind the camera
+        assert!((direction(0, 50) - V3::z()).norm() < 1e-4);
+        assert!((direction(200, 50) - V3::z()).norm() < 1e-4);
+        assert!((direction(100, 100) - V3::y()).norm() < 1e-4);
+        assert!((direction(100, 0) + V3::y()).norm() < 1e-4);
+    }
+}
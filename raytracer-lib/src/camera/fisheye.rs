@@ -0,0 +1,90 @@
+use super::*;
+use crate::math::Ray;
+
+/// How the angle from the view direction maps to the distance from the image center
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FisheyeProjection {
+    /// Distance proportional to the angle, as used for dome projection
+    Equidistant,
+    /// Equal areas of the image cover equal solid angles
+    Equisolid,
+}
+
+/// Fisheye lens whose circular image fits the shorter side of the image. Pixels outside of
+/// the circle don't see anything.
+#[derive(Debug)]
+pub struct FisheyeCamera {
+    base: CameraBase,
+    /// Angle across the image circle, up to 2π
+    fov: Real,
+    projection: FisheyeProjection,
+}
+
+impl FisheyeCamera {
+    /// `fov` is in radians
+    pub fn new(
+        basis: CoordinateSystem,
+        aspect_ratio: Real,
+        fov: Real,
+        projection: FisheyeProjection,
+    ) -> Self {
+        Self {
+            base: CameraBase::new(basis, None, aspect_ratio),
+            fov,
+            projection,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real, time: Real) -> Option<Ray> {
+        let (width, height) = (self.base.pixels_x as Real, self.base.pixels_y as Real);
+        let radius = width.min(height) / 2.0;
+        let x = (i as Real + di - width / 2.0) / radius;
+        let y = (j as Real + dj - height / 2.0) / radius;
+        // distance from the center, 1 on the edge of the image circle
+        let r = x.hypot(y);
+        if r > 1.0 {
+            return None;
+        }
+
+        let max_angle = self.fov / 2.0;
+        let angle = match self.projection {
+            FisheyeProjection::Equidistant => r * max_angle,
+            FisheyeProjection::Equisolid => 2.0 * (r * (max_angle / 2.0).sin()).asin(),
+        };
+        let (cos, sin) = if r > 0.0 { (x / r, y / r) } else { (1.0, 0.0) };
+        let basis = self.base.basis_at(time);
+        Some(Ray {
+            origin: basis.position,
+            direction: (basis.u * cos + basis.v * sin) * angle.sin() - basis.w * angle.cos(),
+            time,
+        })
+    }
+
+    fn camera_base(&self) -> &CameraBase {
+        &self.base
+    }
+    fn camera_base_mut(&mut self) -> &mut CameraBase {
+        &mut self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fisheye_edge_is_half_the_fov() {
+        let basis = CoordinateSystem::new(P3::origin(), &-V3::z());
+        for projection in [FisheyeProjection::Equidistant, FisheyeProjection::Equisolid] {
+            let mut camera = FisheyeCamera::new(basis.clone(), 1.0, PI, projection);
+            camera.set_image_pixels(100, 100);
+            let center = camera.generate_ray(50, 50, 0.0, 0.0, 0.0).unwrap();
+            assert!((center.direction.normalize() + V3::z()).norm() < 1e-4);
+            let edge = camera.generate_ray(100, 50, 0.0, 0.0, 0.0).unwrap();
+            assert!((edge.direction.normalize() - V3::x()).norm() < 1e-4);
+            assert!(camera.generate_ray(99, 99, 0.0, 0.0, 0.0).is_none());
+        }
+    }
+}
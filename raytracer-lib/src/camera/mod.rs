@@ -1,15 +1,30 @@
+use std::borrow::Cow;
+
 use crate::math::{CoordinateSystem, Ray};
 use crate::prelude::*;
-use Real;
 
+mod cylindrical;
+mod equirectangular;
+mod fisheye;
 mod orthographic;
 mod perspective;
+mod realistic;
+mod thin_lens;
+mod view;
 
+pub use self::cylindrical::CylindricalCamera;
+pub use self::equirectangular::EquirectangularCamera;
+pub use self::fisheye::{FisheyeCamera, FisheyeProjection};
 pub use self::orthographic::OrthographicCamera;
 pub use self::perspective::PerspectiveCamera;
+pub use self::realistic::{LensElement, RealisticCamera};
+pub use self::thin_lens::ThinLens;
+pub use self::view::{Eye, StereoLayout, StereoRig, View};
 
 pub trait Camera: std::fmt::Debug {
-    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real) -> Ray;
+    /// Ray through `(di, dj)` within pixel `(i, j)`, traced at `time`. `None` for pixels the
+    /// camera doesn't see anything through, such as those outside of a fisheye's image circle.
+    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real, time: Real) -> Option<Ray>;
 
     // Default implementation that all cameras can use
     fn set_image_pixels(&mut self, pixels_x: u32, pixels_y: u32) {
@@ -25,6 +40,9 @@ pub trait Camera: std::fmt::Debug {
 #[derive(Debug)]
 pub struct CameraBase {
     pub basis: CoordinateSystem,
+    /// Where the camera has moved to at time 1, it moves in a straight line from `basis` at
+    /// time 0
+    pub end_basis: Option<CoordinateSystem>,
     pub pixels_x: u32,
     pub pixels_y: u32,
     left: Real,
@@ -34,12 +52,19 @@ pub struct CameraBase {
 }
 
 impl CameraBase {
-    pub fn new(position: P3, view_direction: &V3, aspect_ratio: Real) -> Self {
-        let image_plane_width = DEFAULT_IMAGE_PLANE_WIDTH;
+    /// `image_plane_width` defaults to `DEFAULT_IMAGE_PLANE_WIDTH`, the height follows from
+    /// `aspect_ratio`
+    pub fn new(
+        basis: CoordinateSystem,
+        image_plane_width: Option<Real>,
+        aspect_ratio: Real,
+    ) -> Self {
+        let image_plane_width = image_plane_width.unwrap_or(DEFAULT_IMAGE_PLANE_WIDTH);
         let image_plane_height = image_plane_width / aspect_ratio;
 
         Self {
-            basis: CoordinateSystem::new(position, view_direction),
+            basis,
+            end_basis: None,
             pixels_x: 0,
             pixels_y: 0,
             left: -image_plane_width / 2.0,
@@ -49,6 +74,33 @@ impl CameraBase {
         }
     }
 
+    /// Makes the camera move to `basis` by time 1
+    pub fn set_end_pose(&mut self, basis: CoordinateSystem) {
+        self.end_basis = Some(basis);
+    }
+
+    /// Where the camera is and where it looks at `time`
+    pub fn basis_at(&self, time: Real) -> Cow<'_, CoordinateSystem> {
+        match &self.end_basis {
+            Some(end_basis) => Cow::Owned(self.basis.interpolate(end_basis, time.clamp(0.0, 1.0))),
+            None => Cow::Borrowed(&self.basis),
+        }
+    }
+
+    /// Position within the image from `(0, 0)` at the bottom left to `(1, 1)` at the top right
+    pub fn get_image_position(&self, i: u32, j: u32, di: Real, dj: Real) -> (Real, Real) {
+        (
+            (i as Real + di) / self.pixels_x as Real,
+            (j as Real + dj) / self.pixels_y as Real,
+        )
+    }
+
+    /// Moves the image window sideways by `offset` on the image plane, for off-axis views
+    pub fn shift_window(&mut self, offset: Real) {
+        self.left += offset;
+        self.right += offset;
+    }
+
     pub fn get_uv(&self, i: u32, j: u32, di: Real, dj: Real) -> (Real, Real) {
         let u = self.left + (self.right - self.left) * (i as Real + di) / self.pixels_x as Real;
         let v = self.bottom + (self.top - self.bottom) * (j as Real + dj) / self.pixels_y as Real;
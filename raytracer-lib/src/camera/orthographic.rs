@@ -1,6 +1,5 @@
 use super::*;
 use crate::math::Ray;
-use crate::V3;
 
 #[derive(Debug)]
 pub struct OrthographicCamera {
@@ -8,21 +7,27 @@ pub struct OrthographicCamera {
 }
 
 impl OrthographicCamera {
-    pub fn new(position: P3, view_direction: &V3, aspect_ratio: Real) -> Self {
+    /// The image plane width is the width of the view in world units
+    pub fn new(
+        basis: CoordinateSystem,
+        image_plane_width: Option<Real>,
+        aspect_ratio: Real,
+    ) -> Self {
         Self {
-            base: CameraBase::new(position, view_direction, aspect_ratio),
+            base: CameraBase::new(basis, image_plane_width, aspect_ratio),
         }
     }
 }
 
 impl Camera for OrthographicCamera {
-    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real) -> Ray {
+    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real, time: Real) -> Option<Ray> {
         let (u, v) = self.base.get_uv(i, j, di, dj);
-        let origin = self.base.basis.position + V3::new(u, v, 0.0);
-        Ray {
-            origin,
-            direction: V3::new(0.0, 0.0, -1.0),
-        }
+        let basis = self.base.basis_at(time);
+        Some(Ray {
+            origin: basis.position + basis.u * u + basis.v * v,
+            direction: -basis.w,
+            time,
+        })
     }
 
     fn camera_base(&self) -> &CameraBase {
@@ -5,26 +5,52 @@ use crate::math::Ray;
 pub struct PerspectiveCamera {
     base: CameraBase,
     focal_length: Real,
+    /// Pinhole camera without one, so everything is in focus
+    lens: Option<ThinLens>,
 }
 
 impl PerspectiveCamera {
-    pub fn new(position: P3, view_direction: &V3, aspect_ratio: Real, focal_length: Real) -> Self {
+    pub fn new(
+        basis: CoordinateSystem,
+        image_plane_width: Option<Real>,
+        aspect_ratio: Real,
+        focal_length: Real,
+    ) -> Self {
         Self {
-            base: CameraBase::new(position, view_direction, aspect_ratio),
+            base: CameraBase::new(basis, image_plane_width, aspect_ratio),
             focal_length,
+            lens: None,
         }
     }
+
+    pub fn set_lens(&mut self, lens: ThinLens) {
+        self.lens = Some(lens);
+    }
 }
 
 impl Camera for PerspectiveCamera {
-    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real) -> Ray {
+    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real, time: Real) -> Option<Ray> {
         let (u, v) = self.base.get_uv(i, j, di, dj);
-        let direction =
-            self.base.basis.u * u + self.base.basis.v * v - self.base.basis.w * self.focal_length;
-        Ray {
-            origin: self.base.basis.position,
-            direction,
-        }
+        let basis = self.base.basis_at(time);
+        let direction = basis.u * u + basis.v * v - basis.w * self.focal_length;
+        let Some(lens) = &self.lens else {
+            return Some(Ray {
+                origin: basis.position,
+                direction,
+                time,
+            });
+        };
+
+        // every ray through the lens meets the pinhole ray on the plane in focus
+        let focus = direction * (lens.focus_distance / self.focal_length);
+        let (x, y) = lens.sample_aperture();
+        let offset = basis.u * x + basis.v * y;
+        Some(Ray {
+            origin: basis.position + offset,
+            // still reaching the image plane at t = 1
+            direction: (focus - offset) * (self.focal_length / lens.focus_distance),
+            time,
+        })
     }
 
     fn camera_base(&self) -> &CameraBase {
@@ -0,0 +1,363 @@
+use rand::Rng;
+
+use super::*;
+use crate::{math::Ray, V3};
+
+/// Spherical surface of a lens, or its aperture stop when the curvature radius is 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensElement {
+    /// Positive when the center of curvature is on the film side of the surface
+    pub curvature_radius: Real,
+    /// Distance along the axis to the next element, or to the film for the last one
+    pub thickness: Real,
+    /// Index of refraction between this element and the next, 1 for air
+    pub ior: Real,
+    pub aperture_radius: Real,
+}
+
+impl LensElement {
+    /// Reads a lens prescription with one element per line, from the front of the lens to the
+    /// back: its curvature radius, thickness, index of refraction and aperture diameter, all in
+    /// millimeters. An index of refraction of 0 is air, as is usual for the aperture stop.
+    /// Empty lines and lines starting with `#` are ignored. `scale` converts millimeters to
+    /// scene units.
+    pub fn read_prescription(
+        reader: impl std::io::BufRead,
+        scale: Real,
+    ) -> std::io::Result<Vec<LensElement>> {
+        let invalid = |line: usize, message: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line {}: {}", line + 1, message),
+            )
+        };
+
+        let mut elements = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let numbers = line
+                .split_whitespace()
+                .map(|word| word.parse::<Real>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid(i, &e.to_string()))?;
+            let [curvature_radius, thickness, ior, aperture] = numbers[..] else {
+                return Err(invalid(
+                    i,
+                    "expected a radius, a thickness, an index of refraction and an aperture",
+                ));
+            };
+            if thickness < 0.0 || ior < 0.0 || aperture <= 0.0 {
+                return Err(invalid(i, "lens element has a negative size"));
+            }
+            elements.push(LensElement {
+                curvature_radius: curvature_radius * scale,
+                thickness: thickness * scale,
+                ior: if ior == 0.0 { 1.0 } else { ior },
+                aperture_radius: aperture * scale / 2.0,
+            });
+        }
+        Ok(elements)
+    }
+
+    fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+}
+
+/// Camera tracing rays from its film through each element of a real lens, so the lens'
+/// vignetting, distortion and depth of field come out as they would in the real thing.
+///
+/// In lens space the film is at `z = 0` and the lens sits in front of it along `+z`, towards
+/// the scene. The camera's position is the center of the film.
+#[derive(Debug)]
+pub struct RealisticCamera {
+    base: CameraBase,
+    elements: Vec<LensElement>,
+    /// Distance of each element's vertex from the film
+    vertices: Vec<Real>,
+    /// Half of the film's diagonal
+    film_radius: Real,
+    /// Smallest and largest coordinates on the plane of the rear element that light from a
+    /// point of the film on the `x` axis can pass through the lens at. The same bounds are
+    /// used all over the film, so the share of rays that make it through falls off smoothly
+    /// towards the corners, like the light does.
+    exit_pupil: ([Real; 2], [Real; 2]),
+}
+
+impl RealisticCamera {
+    /// `film_diagonal` is in scene units. The last element is moved so that points
+    /// `focus_distance` in front of the film are sharp.
+    pub fn new(
+        basis: CoordinateSystem,
+        elements: Vec<LensElement>,
+        film_diagonal: Real,
+        aspect_ratio: Real,
+        focus_distance: Real,
+    ) -> Result<Self, String> {
+        if elements.is_empty() {
+            return Err("lens has no elements".to_string());
+        }
+        let film_width = film_diagonal * aspect_ratio / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let mut camera = Self {
+            base: CameraBase::new(basis, Some(film_width), aspect_ratio),
+            vertices: vertices(&elements),
+            elements,
+            film_radius: film_diagonal / 2.0,
+            exit_pupil: ([0.0; 2], [0.0; 2]),
+        };
+        camera.focus(focus_distance, film_diagonal)?;
+        camera.exit_pupil = camera
+            .bound_exit_pupil()
+            .ok_or("no light makes it through the lens to the film")?;
+        Ok(camera)
+    }
+
+    /// Finds the exit pupil by tracing a grid of rays from points along the film's radius to
+    /// the plane of the rear element
+    fn bound_exit_pupil(&self) -> Option<([Real; 2], [Real; 2])> {
+        const FILM_SAMPLES: usize = 64;
+        const GRID: usize = 32;
+        let rear = self.elements.len() - 1;
+        let extent = 1.5 * self.elements[rear].aperture_radius;
+        let spacing = 2.0 * extent / GRID as Real;
+
+        let mut bounds: Option<([Real; 2], [Real; 2])> = None;
+        for sample in 0..FILM_SAMPLES {
+            let x = self.film_radius * sample as Real / (FILM_SAMPLES - 1) as Real;
+            let film = P3::new(x, 0.0, 0.0);
+            for a in 0..GRID {
+                for b in 0..GRID {
+                    let target = P3::new(
+                        -extent + (a as Real + 0.5) * spacing,
+                        -extent + (b as Real + 0.5) * spacing,
+                        self.vertices[rear],
+                    );
+                    if self.trace(film, target - film, true).is_none() {
+                        continue;
+                    }
+                    let (min, max) =
+                        bounds.get_or_insert(([target.x, target.y], [target.x, target.y]));
+                    *min = [min[0].min(target.x), min[1].min(target.y)];
+                    *max = [max[0].max(target.x), max[1].max(target.y)];
+                }
+            }
+        }
+        // the grid can miss light just outside of the cells it found some in
+        bounds.map(|(min, max)| {
+            (
+                [min[0] - spacing, min[1] - spacing],
+                [max[0] + spacing, max[1] + spacing],
+            )
+        })
+    }
+
+    /// Moves the lens so that it focuses at `focus_distance`, using the cardinal points of its
+    /// thick lens approximation
+    fn focus(&mut self, focus_distance: Real, film_diagonal: Real) -> Result<(), String> {
+        let height = 0.001 * film_diagonal;
+        let front = self.vertices[0];
+        let rear = self.vertices[self.vertices.len() - 1];
+        let cardinal_points = |start: P3, direction: V3, from_film: bool| {
+            let (origin, direction) = self
+                .trace(start, direction, from_film)
+                .ok_or("a ray parallel to the lens axis doesn't make it through the lens")?;
+            // measured towards the film, as in the thick lens equation
+            let focal_point = -(origin.z - origin.x / direction.x * direction.z);
+            let principal_plane = -(origin.z + (height - origin.x) / direction.x * direction.z);
+            Ok::<_, String>((principal_plane, focal_point))
+        };
+        let (film_principal, film_focal) =
+            cardinal_points(P3::new(height, 0.0, front + 1.0), -V3::z(), false)?;
+        let (scene_principal, _) =
+            cardinal_points(P3::new(height, 0.0, rear - 1.0), V3::z(), true)?;
+
+        let focal_length = film_focal - film_principal;
+        let z = -focus_distance;
+        let c = (scene_principal - z - film_principal)
+            * (scene_principal - z - 4.0 * focal_length - film_principal);
+        if c.is_nan() || c <= 0.0 {
+            return Err(format!("the lens can't focus at {}", focus_distance));
+        }
+        let delta = 0.5 * (scene_principal - z + film_principal - c.sqrt());
+        let last = self.elements.len() - 1;
+        self.elements[last].thickness += delta;
+        if self.elements[last].thickness <= 0.0 {
+            return Err(format!("the lens can't focus at {}", focus_distance));
+        }
+        self.vertices = vertices(&self.elements);
+        Ok(())
+    }
+
+    /// Refracts a ray in lens space through every element, starting from the film or from the
+    /// scene. Returns where it leaves the lens and its direction, or `None` if it's blocked.
+    fn trace(&self, mut origin: P3, direction: V3, from_film: bool) -> Option<(P3, V3)> {
+        let mut direction = direction.normalize();
+        let count = self.elements.len();
+        for k in 0..count {
+            let i = if from_film { count - 1 - k } else { k };
+            let element = &self.elements[i];
+            let vertex = self.vertices[i];
+            // medium on the scene side of the element
+            let outer_ior = if i == 0 {
+                1.0
+            } else {
+                self.elements[i - 1].ior
+            };
+
+            if element.is_stop() {
+                let t = (vertex - origin.z) / direction.z;
+                if t < 0.0 {
+                    return None;
+                }
+                origin += direction * t;
+                if origin.x * origin.x + origin.y * origin.y > element.aperture_radius.powi(2) {
+                    return None;
+                }
+                continue;
+            }
+
+            let radius = element.curvature_radius;
+            let center = P3::new(0.0, 0.0, vertex - radius);
+            let to_origin = origin - center;
+            let half_b = to_origin.dot(&direction);
+            let discriminant = half_b * half_b - (to_origin.norm_squared() - radius * radius);
+            if discriminant < 0.0 {
+                return None;
+            }
+            // the hit on the cap of the sphere around the vertex
+            let root = discriminant.sqrt();
+            let t = [-half_b - root, -half_b + root]
+                .into_iter()
+                .find(|&t| t > 0.0 && (origin.z + t * direction.z - center.z) * radius > 0.0)?;
+            origin += direction * t;
+            if origin.x * origin.x + origin.y * origin.y > element.aperture_radius.powi(2) {
+                return None;
+            }
+
+            let mut normal = (origin - center) / radius.abs();
+            if normal.dot(&direction) > 0.0 {
+                normal = -normal;
+            }
+            let eta = if from_film {
+                element.ior / outer_ior
+            } else {
+                outer_ior / element.ior
+            };
+            direction = refract(&direction, &normal, eta)?;
+        }
+        Some((origin, direction))
+    }
+}
+
+/// Distance of each element's vertex from the film
+fn vertices(elements: &[LensElement]) -> Vec<Real> {
+    let mut vertices: Vec<Real> = elements
+        .iter()
+        .rev()
+        .scan(0.0, |z, element| {
+            *z += element.thickness;
+            Some(*z)
+        })
+        .collect();
+    vertices.reverse();
+    vertices
+}
+
+/// `direction` bent through a surface facing it with `normal`, where `eta` is the ratio of the
+/// indices of refraction before and after the surface. `None` for total internal reflection.
+fn refract(direction: &V3, normal: &V3, eta: Real) -> Option<V3> {
+    let cos_i = -normal.dot(direction);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(direction * eta + normal * (eta * cos_i - cos_t))
+}
+
+impl Camera for RealisticCamera {
+    fn generate_ray(&self, i: u32, j: u32, di: Real, dj: Real, time: Real) -> Option<Ray> {
+        // the lens flips the image on the film
+        let (u, v) = self.base.get_uv(i, j, di, dj);
+        let film = P3::new(-u, -v, 0.0);
+
+        // aim at a random point of the exit pupil, turned around the axis to the film point
+        let distance = film.coords.xy().norm();
+        let (min, max) = self.exit_pupil;
+        let mut rng = rand::thread_rng();
+        let (x, y) = (
+            min[0] + (max[0] - min[0]) * rng.gen::<Real>(),
+            min[1] + (max[1] - min[1]) * rng.gen::<Real>(),
+        );
+        let (sin, cos) = if distance > 0.0 {
+            (film.y / distance, film.x / distance)
+        } else {
+            (0.0, 1.0)
+        };
+        let target = P3::new(
+            x * cos - y * sin,
+            x * sin + y * cos,
+            self.vertices[self.elements.len() - 1],
+        );
+        let (origin, direction) = self.trace(film, target - film, true)?;
+
+        let basis = self.base.basis_at(time);
+        Some(Ray {
+            origin: basis.position + basis.u * origin.x + basis.v * origin.y - basis.w * origin.z,
+            direction: basis.u * direction.x + basis.v * direction.y - basis.w * direction.z,
+            time,
+        })
+    }
+
+    fn camera_base(&self) -> &CameraBase {
+        &self.base
+    }
+    fn camera_base_mut(&mut self) -> &mut CameraBase {
+        &mut self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_lens_focuses_at_its_focal_length() {
+        // biconvex lens with a focal length of about R / 2(n - 1) = 100mm
+        let prescription = "# radius thickness ior aperture
+100 5 1.5 20
+-100 50 0 20";
+        let elements = LensElement::read_prescription(prescription.as_bytes(), 0.001).unwrap();
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[1].ior, 1.0);
+        assert!((elements[1].aperture_radius - 0.01).abs() < 1e-9);
+
+        let basis = CoordinateSystem::new(P3::origin(), &-V3::z());
+        let camera = RealisticCamera::new(basis, elements, 0.035, 1.0, 1e6).unwrap();
+        // a ray from the center of the film leaves the lens parallel to the axis
+        let (_, direction) = camera
+            .trace(P3::origin(), V3::new(0.001, 0.0, 0.1), true)
+            .unwrap();
+        assert!(direction.x.abs() < 1e-3 * direction.z);
+    }
+
+    #[test]
+    fn test_lens_without_an_exit_pupil() {
+        // the stop in front lets through the rays used to focus, which run close to the axis,
+        // but none of the rays from the film
+        let prescription = "0 1 0 0.2
+100 5 1.5 20
+-100 50 0 20";
+        let elements = LensElement::read_prescription(prescription.as_bytes(), 0.001).unwrap();
+        let basis = CoordinateSystem::new(P3::origin(), &-V3::z());
+        let camera = RealisticCamera::new(basis, elements, 0.035, 1.0, 1e6);
+        assert_eq!(
+            camera.unwrap_err(),
+            "no light makes it through the lens to the film"
+        );
+    }
+}
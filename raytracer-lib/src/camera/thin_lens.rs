@@ -0,0 +1,44 @@
+use rand::Rng;
+
+use crate::prelude::*;
+
+/// Lens of a camera with depth of field. Points at `focus_distance` in front of the camera are
+/// sharp, everything else is blurred more the wider the aperture is.
+#[derive(Debug, Clone)]
+pub struct ThinLens {
+    /// Radius of the aperture, measured to the corners of a polygonal one
+    pub aperture_radius: Real,
+    pub focus_distance: Real,
+    /// Number of straight sides of the aperture, which shows in the shape of out of focus
+    /// highlights. Round if less than 3.
+    pub blades: u32,
+    /// Rotation of a polygonal aperture in radians
+    pub rotation: Real,
+}
+
+impl ThinLens {
+    /// Random point on the aperture, relative to its center
+    pub fn sample_aperture(&self) -> (Real, Real) {
+        let mut rng = rand::thread_rng();
+        if self.blades < 3 {
+            // uniform over the disk
+            let radius = self.aperture_radius * rng.gen::<Real>().sqrt();
+            let angle = 2.0 * PI * rng.gen::<Real>();
+            return (radius * angle.cos(), radius * angle.sin());
+        }
+
+        // uniform over the triangle between the center and a random blade
+        let blade = rng.gen_range(0..self.blades) as Real;
+        let step = 2.0 * PI / self.blades as Real;
+        let corner = |angle: Real| {
+            let angle = angle + self.rotation;
+            (
+                self.aperture_radius * angle.cos(),
+                self.aperture_radius * angle.sin(),
+            )
+        };
+        let (a, b) = (corner(blade * step), corner((blade + 1.0) * step));
+        let (s, t) = (rng.gen::<Real>().sqrt(), rng.gen::<Real>());
+        (s * (a.0 + (b.0 - a.0) * t), s * (a.1 + (b.1 - a.1) * t))
+    }
+}
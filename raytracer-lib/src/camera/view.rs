@@ -0,0 +1,127 @@
+use super::*;
+
+/// How the two eyes of a stereo pair end up in the rendered images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// An image of its own for each eye
+    Separate,
+    /// One image twice as wide, with the left eye on the left
+    SideBySide,
+    /// One red/cyan image, red from the left eye and green and blue from the right
+    Anaglyph,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// Placement of the eyes of a stereo camera around its position
+#[derive(Debug, Clone, Copy)]
+pub struct StereoRig {
+    pub interocular_distance: Real,
+    /// Distance at which the eyes' views line up, so things there appear on the screen. The
+    /// views stay parallel without one, putting everything in front of the screen.
+    pub convergence_distance: Option<Real>,
+}
+
+impl StereoRig {
+    /// `basis` moved sideways to `eye`, still looking the same way
+    pub fn eye_basis(&self, basis: &CoordinateSystem, eye: Eye) -> CoordinateSystem {
+        CoordinateSystem {
+            position: basis.position + basis.u * (self.side(eye) * self.interocular_distance / 2.0),
+            ..basis.clone()
+        }
+    }
+
+    /// How far the image window of `eye` shifts sideways to converge, for a camera with
+    /// `focal_length`. Shifting keeps the eyes parallel, which unlike turning them inwards
+    /// doesn't add vertical parallax.
+    pub fn window_shift(&self, eye: Eye, focal_length: Real) -> Real {
+        match self.convergence_distance {
+            Some(distance) => {
+                -self.side(eye) * self.interocular_distance / 2.0 * focal_length / distance
+            }
+            None => 0.0,
+        }
+    }
+
+    fn side(&self, eye: Eye) -> Real {
+        match eye {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+/// Something to render from a scene, each camera of a scene becomes one
+#[derive(Debug)]
+pub enum View {
+    Mono {
+        name: String,
+        camera: Box<dyn Camera>,
+    },
+    Stereo {
+        name: String,
+        left: Box<dyn Camera>,
+        right: Box<dyn Camera>,
+        layout: StereoLayout,
+    },
+}
+
+impl View {
+    pub fn name(&self) -> &str {
+        match self {
+            View::Mono { name, .. } | View::Stereo { name, .. } => name,
+        }
+    }
+
+    /// The only camera of a mono view, the left eye of a stereo one
+    pub fn main_camera(&self) -> &dyn Camera {
+        match self {
+            View::Mono { camera, .. } => camera.as_ref(),
+            View::Stereo { left, .. } => left.as_ref(),
+        }
+    }
+
+    /// Names of the images the view renders to, with the size of each. Stereo views with
+    /// separate images add the eye to their name.
+    pub fn images(&self, width: u32, height: u32) -> Vec<(String, u32, u32)> {
+        match self {
+            View::Mono { name, .. } => vec![(name.clone(), width, height)],
+            View::Stereo { name, layout, .. } => match layout {
+                StereoLayout::Separate => vec![
+                    (format!("{}_left", name), width, height),
+                    (format!("{}_right", name), width, height),
+                ],
+                StereoLayout::SideBySide => vec![(name.clone(), 2 * width, height)],
+                StereoLayout::Anaglyph => vec![(name.clone(), width, height)],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::V3;
+
+    #[test]
+    fn test_stereo_eyes_converge() {
+        let rig = StereoRig {
+            interocular_distance: 0.1,
+            convergence_distance: Some(5.0),
+        };
+        let center = CoordinateSystem::new(P3::origin(), &-V3::z());
+        for eye in [Eye::Left, Eye::Right] {
+            let basis = rig.eye_basis(&center, eye);
+            // the center of the shifted window lines up with the convergence point
+            let shift = rig.window_shift(eye, 1.0);
+            let through_window = basis.position + basis.u * shift - basis.w;
+            let direction = through_window - basis.position;
+            let at_convergence = basis.position + direction * 5.0;
+            assert!((at_convergence - P3::new(0.0, 0.0, -5.0)).norm() < 1e-6);
+        }
+    }
+}
@@ -5,6 +5,9 @@ pub struct Framebuffer {
     pub width: u32,
     pub height: u32,
     pub pixels: Vec<[f32; 3]>,
+    /// Coverage of each pixel, the share of its rays that hit something. Colors are
+    /// premultiplied by it when the background is transparent.
+    pub alpha: Vec<f32>,
 }
 
 impl Framebuffer {
@@ -14,6 +17,7 @@ impl Framebuffer {
             width,
             height,
             pixels: vec![[0.0, 0.0, 0.0]; (width * height) as usize],
+            alpha: vec![1.0; (width * height) as usize],
         }
     }
 
@@ -40,6 +44,15 @@ impl Framebuffer {
         )
     }
 
+    pub fn set_alpha(&mut self, i: u32, j: u32, alpha: f32) {
+        let idx = self.index(i, j);
+        self.alpha[idx] = alpha;
+    }
+
+    pub fn get_alpha(&self, i: u32, j: u32) -> f32 {
+        self.alpha[self.index(i, j)]
+    }
+
     // clear color
     pub fn clear_color(&mut self, color: Color) {
         for pixel in self.pixels.iter_mut() {
@@ -20,6 +20,14 @@ impl BBox {
         }
     }
 
+    /// Unbounded shapes like infinite planes have infinite boxes
+    pub fn is_finite(&self) -> bool {
+        self.min
+            .iter()
+            .chain(self.max.iter())
+            .all(|x| x.is_finite())
+    }
+
     pub fn combine(b1: &BBox, b2: &BBox) -> BBox {
         BBox::new(
             P3::new(
@@ -73,6 +81,16 @@ impl BBox {
         BBox::new(min, max)
     }
 
+    /// Parameters where `ray`'s line enters and leaves the box, which may be behind the
+    /// ray's origin
+    pub fn hit_interval(&self, ray: &Ray) -> Option<(Real, Real)> {
+        let t0 = (self.min - ray.origin).component_div(&ray.direction);
+        let t1 = (self.max - ray.origin).component_div(&ray.direction);
+        let near = t0.inf(&t1).max();
+        let far = t0.sup(&t1).min();
+        (near <= far).then_some((near, far))
+    }
+
     pub fn hit(&self, ray: &Ray, mut tmin: Real, mut tmax: Real) -> Option<Real> {
         let r_to_min = self.min - ray.origin;
         let r_to_max = self.max - ray.origin;
@@ -86,7 +104,7 @@ impl BBox {
         }
 
         // Early exit if no x-axis overlap
-        if tmin_x >= tmax_x || tmin_x >= tmax || tmax_x <= tmin {
+        if tmin_x > tmax_x || tmin_x >= tmax || tmax_x <= tmin {
             return None;
         }
 
@@ -101,7 +119,7 @@ impl BBox {
         }
 
         // Early exit if no y-axis overlap
-        if tmin_y >= tmax_y || tmin_y >= tmax || tmax_y <= tmin {
+        if tmin_y > tmax_y || tmin_y >= tmax || tmax_y <= tmin {
             return None;
         }
 
@@ -116,7 +134,7 @@ impl BBox {
         }
 
         // Final check for z-axis overlap
-        if tmin_z >= tmax_z || tmin_z >= tmax || tmax_z <= tmin {
+        if tmin_z > tmax_z || tmin_z >= tmax || tmax_z <= tmin {
             return None;
         }
 
@@ -138,21 +156,25 @@ mod tests {
         let r1 = Ray {
             origin: P3::new(0.0, 0.0, 0.0),
             direction: V3::new(0.0, 0.0, -1.0),
+            ..Default::default()
         };
 
         let r2 = Ray {
             origin: P3::new(0.0, 0.0, 0.0),
             direction: V3::new(0.0, 0.0, 1.0),
+            ..Default::default()
         };
 
         let r3 = Ray {
             origin: P3::new(1.25, 1.25, 0.25),
             direction: V3::new(-1.0, -1.0, -2.0),
+            ..Default::default()
         };
 
         let r4 = Ray {
             origin: P3::new(0.0, 0.0, 0.0),
             direction: V3::new(-2.0, -2.0, -1.0),
+            ..Default::default()
         };
 
         // Create larger bounding box for r5 test
@@ -161,6 +183,7 @@ mod tests {
         let r5 = Ray {
             origin: P3::new(80.0, -100.0, 300.0),
             direction: V3::new(0.1871, 0.6359, -0.7488),
+            ..Default::default()
         };
 
         // Test ray intersections
@@ -170,4 +193,15 @@ mod tests {
         assert!(b1.hit(&r4, 1.0, f64::INFINITY).is_none());
         assert!(b2.hit(&r5, 1.0, f64::INFINITY).is_some());
     }
+
+    #[test]
+    fn test_flat_bbox_intersection() {
+        // axis aligned disks and quads have boxes without thickness, which rays enter and
+        // leave at the same t
+        let flat = BBox::new(P3::new(-1.0, -1.0, -2.0), P3::new(1.0, 1.0, -2.0));
+        let r1 = Ray::atob(P3::origin(), P3::new(0.0, 0.0, -1.0));
+        let r2 = Ray::atob(P3::origin(), P3::new(0.0, 2.0, -1.0));
+        assert_eq!(flat.hit(&r1, 1.0, f64::INFINITY), Some(2.0));
+        assert!(flat.hit(&r2, 1.0, f64::INFINITY).is_none());
+    }
 }
@@ -1,4 +1,4 @@
-use crate::geometry::Shape;
+use crate::{geometry::Shape, prelude::*};
 use std::sync::Arc;
 
 use super::BBox;
@@ -101,7 +101,7 @@ impl BVHNode {
         // If this is a leaf node, check all shapes
         if !self.shapes.is_empty() {
             for shape in &self.shapes {
-                if shape.closest_hit(hit) {
+                if hit_shape(shape.as_ref(), hit) {
                     hit_anything = true;
                 }
             }
@@ -125,24 +125,65 @@ impl BVHNode {
     }
 }
 
+/// Tests `shape` against `hit`, dropping the color of the hit it replaces when it is closer so
+/// only shapes with colors have to set one
+fn hit_shape<'hit>(shape: &'hit dyn Shape, hit: &mut crate::shader::Hit<'hit>) -> bool {
+    let color = hit.color.take();
+    if shape.closest_hit(hit) {
+        return true;
+    }
+    hit.color = color;
+    false
+}
+
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub struct BVH {
-    root: BVHNode,
+    root: Option<BVHNode>,
+    /// Shapes without a finite bounding box, which are tested against every ray
+    unbounded: Vec<Arc<dyn Shape>>,
+    bbox: BBox,
 }
 
 impl BVH {
     pub fn new(shapes: Vec<Arc<dyn Shape>>) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = shapes
+            .into_iter()
+            .partition(|shape| shape.get_bbox().is_finite());
+
+        let root = (!bounded.is_empty()).then(|| BVHNode::new(bounded, Axis::X));
+        let bbox = match &root {
+            Some(root) => root.bbox.clone(),
+            None => BBox::new(P3::origin(), P3::origin()),
+        };
+
         Self {
-            root: BVHNode::new(shapes.clone(), Axis::X),
+            root,
+            unbounded,
+            bbox,
         }
     }
 
     pub fn closest_hit<'hit>(&'hit self, hit: &mut crate::shader::Hit<'hit>) -> bool {
-        self.root.closest_hit(hit)
+        let mut hit_anything = false;
+        for shape in &self.unbounded {
+            if hit_shape(shape.as_ref(), hit) {
+                hit_anything = true;
+            }
+        }
+
+        if let Some(root) = &self.root {
+            if root.closest_hit(hit) {
+                hit_anything = true;
+            }
+        }
+
+        hit_anything
     }
 
+    /// Bounds of the shapes with finite bounding boxes
     pub fn get_bbox(&self) -> &BBox {
-        &self.root.bbox
+        &self.bbox
     }
 }
 
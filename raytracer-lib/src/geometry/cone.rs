@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::disk::{azimuth, disk_bbox, hit_disk};
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    math::{solve_quadratic, CoordinateSystem},
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+#[derive(Debug)]
+pub struct Cone {
+    frame: CoordinateSystem,
+    radius: Real,
+    height: Real,
+    capped: bool,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Cone {
+    /// Cone with a base of `radius` at `base` and its apex `height` along `axis`, the base is
+    /// closed with a disk when `capped`
+    pub fn new(
+        base: P3,
+        axis: V3,
+        radius: Real,
+        height: Real,
+        capped: bool,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let frame = CoordinateSystem::from_axis(base, &axis);
+        let apex = base + frame.w * height;
+        let bbox = BBox::combine(&disk_bbox(&base, &frame.w, radius), &BBox::new(apex, apex));
+        Self {
+            frame,
+            radius,
+            height,
+            capped,
+            bbox,
+            shader,
+            name,
+        }
+    }
+}
+
+impl Shape for Cone {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Cone
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let o = self.frame.to_local(hit.ray.origin);
+        let d = self.frame.vector_to_local(&hit.ray.direction);
+
+        // (t, local normal, uv) of the closest surface so far
+        let mut closest: Option<(Real, V3, (Real, Real))> = None;
+
+        // x^2 + y^2 = k^2 (h - z)^2, only the nappe between the base and the apex is part
+        // of the cone
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.z;
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k2 * h * d.z);
+        let c = o.x * o.x + o.y * o.y - k2 * h * h;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            let side = [t0, t1].into_iter().find(|t| {
+                let z = o.z + d.z * t;
+                (hit.t_min..hit.t).contains(t) && (0.0..=self.height).contains(&z)
+            });
+            if let Some(t) = side {
+                let p = o + d * t;
+                let normal = V3::new(p.x, p.y, k2 * (self.height - p.z));
+                let uv = (azimuth(p.x, p.y), p.z / self.height);
+                closest = Some((t, normal, uv));
+            }
+        }
+
+        if self.capped {
+            let t_max = closest.map_or(hit.t, |(t, _, _)| t);
+            if let Some((t, p)) = hit_disk(&o, &d, 0.0, 0.0, self.radius, hit.t_min..t_max) {
+                let r = (p.x * p.x + p.y * p.y).sqrt();
+                closest = Some((t, -V3::z(), (azimuth(p.x, p.y), r / self.radius)));
+            }
+        }
+
+        let Some((t, normal, uv)) = closest else {
+            return false;
+        };
+        hit.t = t;
+        hit.normal = Unit::new_normalize(self.frame.vector_to_global(&normal));
+        hit.uv = uv;
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+
+    #[test]
+    fn test_cone_intersection() {
+        let (right, up) = (V3::new(1.0, 0.0, 0.0), V3::new(0.0, 0.0, 1.0));
+        let new = |capped| Cone::new(P3::origin(), up, 1.0, 2.0, capped, shader(), "cone");
+        let capped = new(true);
+        // half way up the radius is 0.5, the side leans in by a slope of 1 / 2
+        assert_hit(
+            trace(&capped, P3::new(-3.0, 0.0, 1.0), right),
+            2.5,
+            V3::new(-2.0, 0.0, 1.0),
+        );
+        assert_hit(trace(&capped, P3::new(0.2, 0.0, -3.0), up), 3.0, -up);
+        assert!(trace(&capped, P3::new(-3.0, 0.0, 2.5), right).is_none());
+        assert_hit(
+            trace(&capped, P3::new(0.0, 0.0, 0.5), right),
+            0.75,
+            V3::new(2.0, 0.0, 1.0),
+        );
+
+        // without a cap the ray goes in through the base and hits the side from inside
+        assert_hit(
+            trace(&new(false), P3::new(0.2, 0.0, -3.0), up),
+            4.6,
+            V3::new(2.0, 0.0, 1.0),
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    prelude::*,
+    shader::{Hit, Shader},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// `a` with `b` cut out of it
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, inside_a: bool, inside_b: bool) -> bool {
+        match self {
+            CsgOperation::Union => inside_a || inside_b,
+            CsgOperation::Intersection => inside_a && inside_b,
+            CsgOperation::Difference => inside_a && !inside_b,
+        }
+    }
+}
+
+/// Boolean combination of two closed shapes. Children must have outward facing normals, which
+/// is how crossings along a ray are classified as entering or leaving them.
+#[derive(Debug)]
+pub struct Csg {
+    operation: CsgOperation,
+    a: Arc<dyn Shape>,
+    b: Arc<dyn Shape>,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Csg {
+    /// `shader` is only reported by `get_shader`, hits use the shader of the child surface
+    /// that was hit
+    pub fn new(
+        operation: CsgOperation,
+        a: Arc<dyn Shape>,
+        b: Arc<dyn Shape>,
+        shader: Option<Arc<dyn Shader>>,
+        name: &'static str,
+    ) -> Self {
+        let (a_bbox, b_bbox) = (a.get_bbox(), b.get_bbox());
+        let bbox = match operation {
+            CsgOperation::Union => BBox::combine(a_bbox, b_bbox),
+            CsgOperation::Intersection => {
+                let min = a_bbox.min.sup(&b_bbox.min);
+                // disjoint children leave an empty box, nothing can be hit anyway
+                let max = a_bbox.max.inf(&b_bbox.max).sup(&min);
+                BBox::new(min, max)
+            }
+            CsgOperation::Difference => a_bbox.clone(),
+        };
+        let shader = shader.unwrap_or_else(|| a.get_shader());
+
+        Self {
+            operation,
+            a,
+            b,
+            bbox,
+            shader,
+            name,
+        }
+    }
+}
+
+/// Next surface of `shape` along `hit`'s ray after `t_min`, along with whether the ray is
+/// entering the shape there
+fn next_crossing<'hit>(
+    shape: &'hit dyn Shape,
+    hit: &Hit<'hit>,
+    t_min: Real,
+) -> Option<(Hit<'hit>, bool)> {
+    let mut crossing = Hit::new(hit.ray, hit.scene);
+    crossing.t_min = t_min;
+    crossing.depth = hit.depth;
+    if !shape.closest_hit(&mut crossing) {
+        return None;
+    }
+
+    let entering = crossing.normal.dot(&crossing.ray.direction) < 0.0;
+    Some((crossing, entering))
+}
+
+impl Shape for Csg {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Csg
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        self.shader.clone()
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let mut next_a = next_crossing(self.a.as_ref(), hit, hit.t_min);
+        let mut next_b = next_crossing(self.b.as_ref(), hit, hit.t_min);
+
+        // leaving a child first means the ray started inside it
+        let mut inside_a = next_a.as_ref().is_some_and(|(_, entering)| !entering);
+        let mut inside_b = next_b.as_ref().is_some_and(|(_, entering)| !entering);
+
+        // walk the crossings of both children in order until the combined shape's boundary
+        loop {
+            let from_a = match (&next_a, &next_b) {
+                (Some((a, _)), Some((b, _))) => a.t <= b.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return false,
+            };
+            let next = if from_a { &mut next_a } else { &mut next_b };
+            let Some((crossing, entering)) = next.take() else {
+                return false;
+            };
+            if crossing.t >= hit.t {
+                return false;
+            }
+
+            let was_inside = self.operation.contains(inside_a, inside_b);
+            if from_a {
+                inside_a = entering;
+                next_a = next_crossing(self.a.as_ref(), hit, crossing.t + VERY_SMALL_NUMBER);
+            } else {
+                inside_b = entering;
+                next_b = next_crossing(self.b.as_ref(), hit, crossing.t + VERY_SMALL_NUMBER);
+            }
+
+            if was_inside != self.operation.contains(inside_a, inside_b) {
+                // b's surface faces into the result when it is cut out of a
+                let flip = !from_a && self.operation == CsgOperation::Difference;
+                hit.t = crossing.t;
+                hit.normal = if flip {
+                    Unit::new_unchecked(-crossing.normal.into_inner())
+                } else {
+                    crossing.normal
+                };
+                hit.uv = crossing.uv;
+                hit.shape = Some(self);
+                hit.shader = crossing.shader;
+                hit.color = crossing.color;
+                return true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+    use crate::geometry::Sphere;
+    use crate::V3;
+
+    /// Unit spheres at x = -0.5 and 0.5, overlapping between -0.5 and 0.5
+    fn csg(operation: CsgOperation) -> Csg {
+        let a = Arc::new(Sphere::new(P3::new(-0.5, 0.0, 0.0), 1.0, shader(), "a"));
+        let b = Arc::new(Sphere::new(P3::new(0.5, 0.0, 0.0), 1.0, shader(), "b"));
+        Csg::new(operation, a, b, None, "csg")
+    }
+
+    #[test]
+    fn test_csg_operations() {
+        let (left, right) = (V3::new(-1.0, 0.0, 0.0), V3::new(1.0, 0.0, 0.0));
+        let from_left = |csg: &Csg| trace(csg, P3::new(-5.0, 0.0, 0.0), right);
+        let from_right = |csg: &Csg| trace(csg, P3::new(5.0, 0.0, 0.0), left);
+
+        let union = csg(CsgOperation::Union);
+        assert_hit(from_left(&union), 3.5, left);
+        assert_hit(from_right(&union), 3.5, right);
+        assert!(trace(&union, P3::new(-5.0, 2.0, 0.0), right).is_none());
+
+        let intersection = csg(CsgOperation::Intersection);
+        assert_hit(from_left(&intersection), 4.5, left);
+        assert_hit(from_right(&intersection), 4.5, right);
+
+        // the cut out surface of b faces out of the result
+        let difference = csg(CsgOperation::Difference);
+        assert_hit(from_left(&difference), 3.5, left);
+        assert_hit(from_right(&difference), 5.5, right);
+    }
+
+    #[test]
+    fn test_csg_rays_starting_inside() {
+        let right = V3::new(1.0, 0.0, 0.0);
+        let origin = P3::origin();
+
+        // inside both spheres
+        assert_hit(trace(&csg(CsgOperation::Union), origin, right), 1.5, right);
+        assert_hit(
+            trace(&csg(CsgOperation::Intersection), origin, right),
+            0.5,
+            right,
+        );
+        assert!(trace(&csg(CsgOperation::Difference), origin, right).is_none());
+
+        // inside a only, leaving the difference where b is cut out
+        let inside_a = P3::new(-1.0, 0.0, 0.0);
+        assert_hit(
+            trace(&csg(CsgOperation::Difference), inside_a, right),
+            0.5,
+            right,
+        );
+    }
+}
@@ -61,14 +61,24 @@ impl Shape for Cuboid {
     }
 
     fn closest_hit<'hit>(&'hit self, hit: &mut crate::shader::Hit<'hit>) -> bool {
-        if let Some(t) = self.bbox.hit(&hit.ray, hit.t_min, hit.t) {
-            hit.t = t;
-            hit.normal = Unit::new_normalize(self.normal(&hit.hit_point()));
-            hit.shape = Some(self);
+        let Some((near, far)) = self.bbox.hit_interval(&hit.ray) else {
+            return false;
+        };
 
-            return true;
-        }
+        // rays starting inside the box hit it on the way out
+        let valid_t_range = hit.t_min..hit.t;
+        let t = if valid_t_range.contains(&near) {
+            near
+        } else if valid_t_range.contains(&far) {
+            far
+        } else {
+            return false;
+        };
 
-        false
+        hit.t = t;
+        hit.normal = Unit::new_normalize(self.normal(&hit.hit_point()));
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
     }
 }
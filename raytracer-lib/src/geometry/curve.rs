@@ -0,0 +1,482 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::{BBox, Shape, ShapeType, BVH};
+use crate::{
+    math::CoordinateSystem,
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+/// How the width of a curve is shaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveMode {
+    /// Flat strip facing the ray, with the same normal across its width
+    Ribbon,
+    /// Round tube, the normal turns around the curve across its width
+    Tube,
+}
+
+/// Hair, fur or grass strand made of cubic Bézier segments sharing their end points, so it
+/// has `3n + 1` control points for `n` segments
+#[derive(Debug, Clone)]
+pub struct Strand {
+    pub points: Vec<P3>,
+    /// Radius at the root and at the tip, in between it changes linearly along the strand
+    pub radii: (Real, Real),
+}
+
+impl Strand {
+    pub fn is_valid(&self) -> bool {
+        self.points.len() >= 4 && (self.points.len() - 1).is_multiple_of(3)
+    }
+
+    /// Reads a strand file, which has one strand per line: its root radius, its tip radius and
+    /// then the x, y and z of each of its control points, all separated by whitespace. Empty
+    /// lines and lines starting with `#` are ignored.
+    pub fn read_file(path: &str) -> std::io::Result<Vec<Strand>> {
+        let invalid = |line: usize, message: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path, line + 1, message),
+            )
+        };
+
+        let mut strands = Vec::new();
+        for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let numbers = line
+                .split_whitespace()
+                .map(|n| n.parse::<Real>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid(i, &e.to_string()))?;
+            if numbers.len() < 2 || !(numbers.len() - 2).is_multiple_of(3) {
+                return Err(invalid(i, "expected two radii followed by x y z triples"));
+            }
+
+            let strand = Strand {
+                points: numbers[2..]
+                    .chunks(3)
+                    .map(|p| P3::new(p[0], p[1], p[2]))
+                    .collect(),
+                radii: (numbers[0], numbers[1]),
+            };
+            if !strand.is_valid() {
+                return Err(invalid(i, "strands need 3n + 1 control points"));
+            }
+            strands.push(strand);
+        }
+        Ok(strands)
+    }
+}
+
+/// Collection of strands, intersected directly instead of being turned into triangles. The
+/// Bézier segments of all strands are kept in their own BVH.
+#[derive(Debug)]
+pub struct Curves {
+    bvh: BVH,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Curves {
+    pub fn new(
+        strands: &[Strand],
+        mode: CurveMode,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Result<Self, String> {
+        let mut segments: Vec<Arc<dyn Shape>> = Vec::new();
+        for strand in strands {
+            if !strand.is_valid() {
+                return Err(format!(
+                    "{} has a strand without 3n + 1 control points",
+                    name
+                ));
+            }
+            let count = (strand.points.len() - 1) / 3;
+            let radius = |u: Real| strand.radii.0 + (strand.radii.1 - strand.radii.0) * u;
+            for (i, points) in strand.points.windows(4).step_by(3).enumerate() {
+                let u_range = (i as Real / count as Real, (i + 1) as Real / count as Real);
+                segments.push(Arc::new(CurveSegment::new(
+                    [points[0], points[1], points[2], points[3]],
+                    (radius(u_range.0), radius(u_range.1)),
+                    u_range,
+                    mode,
+                    Arc::clone(&shader),
+                    name,
+                )));
+            }
+        }
+
+        let bvh = BVH::new(segments);
+        let bbox = bvh.get_bbox().clone();
+        Ok(Self {
+            bvh,
+            bbox,
+            shader,
+            name,
+        })
+    }
+}
+
+impl Shape for Curves {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Curves
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        self.bvh.closest_hit(hit)
+    }
+}
+
+/// A single cubic Bézier segment of a strand
+#[derive(Debug)]
+struct CurveSegment {
+    points: [P3; 4],
+    /// Radius at the start and the end of the segment
+    radii: (Real, Real),
+    /// Part of the strand covered by the segment, reported as the u texture coordinate
+    u_range: (Real, Real),
+    /// How often the segment is halved before it is treated as a straight line
+    max_depth: u32,
+    mode: CurveMode,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl CurveSegment {
+    fn new(
+        points: [P3; 4],
+        radii: (Real, Real),
+        u_range: (Real, Real),
+        mode: CurveMode,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        // the curve lies inside the hull of its control points
+        let max_radius = V3::repeat(radii.0.max(radii.1));
+        let bbox = points[1..].iter().fold(
+            BBox::new(points[0] - max_radius, points[0] + max_radius),
+            |bbox, p| BBox::combine(&bbox, &BBox::new(p - max_radius, p + max_radius)),
+        );
+
+        // enough halvings for the segments to deviate from a straight line by at most a
+        // tenth of the radius
+        let deviation = (0..2)
+            .map(|i| {
+                (points[i].coords - points[i + 1].coords * 2.0 + points[i + 2].coords)
+                    .abs()
+                    .max()
+            })
+            .fold(0.0, Real::max);
+        let tolerance = 0.1 * radii.0.max(radii.1);
+        let max_depth = if deviation > 0.0 && tolerance > 0.0 {
+            ((Real::sqrt(2.0) * 6.0 * deviation / (8.0 * tolerance)).log2() / 2.0).clamp(0.0, 10.0)
+                as u32
+        } else {
+            0
+        };
+
+        Self {
+            points,
+            radii,
+            u_range,
+            max_depth,
+            mode,
+            bbox,
+            shader,
+            name,
+        }
+    }
+
+    fn radius(&self, u: Real) -> Real {
+        self.radii.0 + (self.radii.1 - self.radii.0) * u
+    }
+
+    /// Closest crossing of the ray, which runs along z from the origin, with the part
+    /// `u_range` of the segment whose control points are `points`. Keeps the distance along
+    /// the ray to the front of the curve and the curve parameter of the closest one in
+    /// `closest`.
+    fn intersect(
+        &self,
+        points: &[P3; 4],
+        u_range: (Real, Real),
+        depth: u32,
+        z_range: (Real, Real),
+        closest: &mut Option<(Real, Real)>,
+    ) {
+        let z_max = closest.map_or(z_range.1, |(z, _)| z);
+        let radius = self.radius(u_range.0).max(self.radius(u_range.1));
+        let (min, max) = points[1..]
+            .iter()
+            .fold((points[0], points[0]), |(min, max), p| {
+                (min.inf(p), max.sup(p))
+            });
+        if min.x - radius > 0.0
+            || max.x + radius < 0.0
+            || min.y - radius > 0.0
+            || max.y + radius < 0.0
+            || min.z - radius > z_max
+            || max.z + radius < z_range.0
+        {
+            return;
+        }
+
+        if depth > 0 {
+            let (first, second) = split(points);
+            let middle = (u_range.0 + u_range.1) / 2.0;
+            self.intersect(&first, (u_range.0, middle), depth - 1, z_range, closest);
+            self.intersect(&second, (middle, u_range.1), depth - 1, z_range, closest);
+            return;
+        }
+
+        // the ray has to pass between the planes through both ends that are perpendicular
+        // to the curve
+        let [p0, p1, p2, p3] = points;
+        if (p1.x - p0.x) * -p0.x + (p1.y - p0.y) * -p0.y < 0.0
+            || (p2.x - p3.x) * -p3.x + (p2.y - p3.y) * -p3.y < 0.0
+        {
+            return;
+        }
+
+        // the segment is close to a straight line, find where it passes closest to the ray
+        let chord = (p3 - p0).xy();
+        let length = chord.norm_squared();
+        let w = if length > 0.0 {
+            (-p0.coords.xy().dot(&chord) / length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let point = bezier(points, w);
+        let u = u_range.0 + (u_range.1 - u_range.0) * w;
+        let radius = self.radius(u);
+        let distance = point.coords.xy().norm_squared();
+        if distance > radius * radius {
+            return;
+        }
+
+        // where the ray enters the tube around the curve, so rays leaving its surface don't
+        // hit it again
+        let z = point.z - (radius * radius - distance).sqrt();
+        if z > z_range.0 && z < z_max {
+            *closest = Some((z, u));
+        }
+    }
+}
+
+impl Shape for CurveSegment {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Curves
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        // work in a frame where the ray starts at the origin and runs along z
+        let speed = hit.ray.direction.norm();
+        if speed == 0.0 {
+            return false;
+        }
+        let frame = CoordinateSystem::from_axis(hit.ray.origin, &hit.ray.direction);
+        let points = self.points.map(|p| frame.to_local(p));
+
+        let mut closest = None;
+        let z_range = (hit.t_min * speed, hit.t * speed);
+        self.intersect(&points, (0.0, 1.0), self.max_depth, z_range, &mut closest);
+        let Some((z, u)) = closest else {
+            return false;
+        };
+
+        let t = z / speed;
+        let direction = frame.w;
+        let tangent = bezier_tangent(&self.points, u);
+        // towards the ray, perpendicular to the curve
+        let facing = Unit::try_new(tangent * tangent.dot(&direction) - direction, 1e-9)
+            .unwrap_or_else(|| Unit::new_unchecked(frame.u));
+        let side = tangent.cross(&facing);
+        let offset = (hit.ray.point_at(t) - bezier(&self.points, u)).dot(&side);
+        let v = (offset / self.radius(u)).clamp(-1.0, 1.0);
+
+        hit.t = t;
+        hit.normal = match self.mode {
+            CurveMode::Ribbon => facing,
+            CurveMode::Tube => Unit::new_normalize(facing.scale((1.0 - v * v).sqrt()) + side * v),
+        };
+        hit.uv = (
+            self.u_range.0 + (self.u_range.1 - self.u_range.0) * u,
+            (v + 1.0) / 2.0,
+        );
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+fn bezier(points: &[P3; 4], u: Real) -> P3 {
+    let [p0, p1, p2, p3] = points.map(|p| p.coords);
+    let s = 1.0 - u;
+    P3::from(p0 * (s * s * s) + p1 * (3.0 * s * s * u) + p2 * (3.0 * s * u * u) + p3 * (u * u * u))
+}
+
+/// Direction of the curve at `u`, falling back to its chord where the derivative vanishes
+fn bezier_tangent(points: &[P3; 4], u: Real) -> V3 {
+    let [p0, p1, p2, p3] = points;
+    let s = 1.0 - u;
+    let derivative = (p1 - p0) * (s * s) + (p2 - p1) * (2.0 * s * u) + (p3 - p2) * (u * u);
+    derivative
+        .try_normalize(1e-12)
+        .or_else(|| (p3 - p0).try_normalize(1e-12))
+        .unwrap_or_else(V3::y)
+}
+
+/// de Casteljau split of the curve into its two halves
+fn split(points: &[P3; 4]) -> ([P3; 4], [P3; 4]) {
+    let [p0, p1, p2, p3] = *points;
+    let a = na::center(&p0, &p1);
+    let b = na::center(&p1, &p2);
+    let c = na::center(&p2, &p3);
+    let d = na::center(&a, &b);
+    let e = na::center(&b, &c);
+    let middle = na::center(&d, &e);
+    ([p0, a, d, middle], [middle, e, c, p3])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+
+    /// Straight strand from x = -1 to x = 1 with radius `radii.0` at the root and `radii.1` at
+    /// the tip
+    fn straight(radii: (Real, Real), mode: CurveMode) -> Curves {
+        let points = [-1.0, -1.0 / 3.0, 1.0 / 3.0, 1.0].map(|x| P3::new(x, 0.0, 0.0));
+        let strand = Strand {
+            points: points.to_vec(),
+            radii,
+        };
+        Curves::new(&[strand], mode, shader(), "hair").unwrap()
+    }
+
+    #[test]
+    fn test_curve_intersection() {
+        let (y, z) = (V3::y(), V3::z());
+        let tube = straight((0.1, 0.1), CurveMode::Tube);
+        let ribbon = straight((0.1, 0.1), CurveMode::Ribbon);
+        assert_hit(trace(&tube, P3::new(0.0, 0.0, 5.0), -z), 4.9, z);
+        assert!(trace(&tube, P3::new(1.5, 0.0, 5.0), -z).is_none());
+        assert!(trace(&tube, P3::new(0.0, 0.15, 5.0), -z).is_none());
+
+        // off center the normal of a tube turns around it, a ribbon's keeps facing the ray
+        let depth = 5.0 - Real::sqrt(0.0075);
+        let origin = P3::new(0.0, 0.05, 5.0);
+        assert_hit(
+            trace(&tube, origin, -z),
+            depth,
+            y * 0.5 + z * Real::sqrt(0.75),
+        );
+        assert_hit(trace(&ribbon, origin, -z), depth, z);
+
+        // 0.15 halfway between the root and the middle, 0.1 in the middle
+        let tapered = straight((0.2, 0.0), CurveMode::Tube);
+        assert_hit(trace(&tapered, P3::new(-0.5, 0.0, 5.0), -z), 4.85, z);
+        assert_hit(trace(&tapered, P3::new(0.0, 0.0, 5.0), -z), 4.9, z);
+        assert!(trace(&tapered, P3::new(0.0, 0.12, 5.0), -z).is_none());
+    }
+
+    #[test]
+    fn test_strands_need_3n_plus_1_points() {
+        let strand = Strand {
+            points: vec![P3::origin(); 5],
+            radii: (0.1, 0.1),
+        };
+        let curves = Curves::new(&[strand], CurveMode::Tube, shader(), "hair");
+        assert_eq!(
+            curves.unwrap_err(),
+            "hair has a strand without 3n + 1 control points"
+        );
+    }
+
+    #[test]
+    fn test_read_strand_file() {
+        let path = std::env::temp_dir().join("raytracer_strands.txt");
+        let read = |contents: &str| {
+            std::fs::write(&path, contents).unwrap();
+            Strand::read_file(&path.to_string_lossy())
+        };
+
+        let strands = read(
+            "# root radius, tip radius, control points\n\
+             0.2 0.1  0 0 0  0 1 0  0 2 0  0 3 0\n\
+             \n\
+             0.1 0  1 0 0  1 1 0  1 2 0  1 3 0  1 4 0  1 5 0  1 6 0\n",
+        )
+        .unwrap();
+        assert_eq!(strands.len(), 2);
+        assert_eq!(strands[0].radii, (0.2, 0.1));
+        assert_eq!(strands[0].points[3], P3::new(0.0, 3.0, 0.0));
+        assert_eq!(strands[1].points.len(), 7);
+
+        // a point without its z, on the second line
+        let error = read("# strands\n0.1 0.1  0 0 0  0 1 0  0 2 0  0 3\n").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains(":2: expected two radii"));
+        let error = read("0.1 0.1  0 0 0  0 1 0\n").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains(":1: strands need 3n + 1 control points"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_split_keeps_the_curve() {
+        let points = [
+            P3::new(0.0, 0.0, 0.0),
+            P3::new(1.0, 2.0, 0.0),
+            P3::new(2.0, -1.0, 1.0),
+            P3::new(3.0, 0.0, 0.0),
+        ];
+        let (first, second) = split(&points);
+        for u in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert!((bezier(&first, u) - bezier(&points, u / 2.0)).norm() < 1e-6);
+            assert!((bezier(&second, u) - bezier(&points, 0.5 + u / 2.0)).norm() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::disk::{azimuth, disk_bbox, hit_disk};
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    math::{solve_quadratic, CoordinateSystem},
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+#[derive(Debug)]
+pub struct Cylinder {
+    frame: CoordinateSystem,
+    radius: Real,
+    height: Real,
+    capped: bool,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Cylinder {
+    /// Cylinder of `height` going from `base` along `axis`, closed with disks when `capped`
+    pub fn new(
+        base: P3,
+        axis: V3,
+        radius: Real,
+        height: Real,
+        capped: bool,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let frame = CoordinateSystem::from_axis(base, &axis);
+        let top = base + frame.w * height;
+        let bbox = BBox::combine(
+            &disk_bbox(&base, &frame.w, radius),
+            &disk_bbox(&top, &frame.w, radius),
+        );
+        Self {
+            frame,
+            radius,
+            height,
+            capped,
+            bbox,
+            shader,
+            name,
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Cylinder
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let o = self.frame.to_local(hit.ray.origin);
+        let d = self.frame.vector_to_local(&hit.ray.direction);
+
+        // (t, local normal, uv) of the closest surface so far
+        let mut closest: Option<(Real, V3, (Real, Real))> = None;
+
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        // rays parallel to the axis can only hit the caps
+        if a > Real::EPSILON {
+            if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+                let side = [t0, t1].into_iter().find(|t| {
+                    let z = o.z + d.z * t;
+                    (hit.t_min..hit.t).contains(t) && (0.0..=self.height).contains(&z)
+                });
+                if let Some(t) = side {
+                    let p = o + d * t;
+                    let uv = (azimuth(p.x, p.y), p.z / self.height);
+                    closest = Some((t, V3::new(p.x, p.y, 0.0), uv));
+                }
+            }
+        }
+
+        if self.capped {
+            for (z, normal) in [(0.0, -V3::z()), (self.height, V3::z())] {
+                let t_max = closest.map_or(hit.t, |(t, _, _)| t);
+                if let Some((t, p)) = hit_disk(&o, &d, z, 0.0, self.radius, hit.t_min..t_max) {
+                    let r = (p.x * p.x + p.y * p.y).sqrt();
+                    closest = Some((t, normal, (azimuth(p.x, p.y), r / self.radius)));
+                }
+            }
+        }
+
+        let Some((t, normal, uv)) = closest else {
+            return false;
+        };
+        hit.t = t;
+        hit.normal = Unit::new_normalize(self.frame.vector_to_global(&normal));
+        hit.uv = uv;
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+
+    #[test]
+    fn test_cylinder_intersection() {
+        let (right, up) = (V3::new(1.0, 0.0, 0.0), V3::new(0.0, 0.0, 1.0));
+        let new = |capped| Cylinder::new(P3::origin(), up, 1.0, 2.0, capped, shader(), "can");
+        let capped = new(true);
+        assert_hit(trace(&capped, P3::new(-3.0, 0.0, 1.0), right), 2.0, -right);
+        assert_hit(trace(&capped, P3::new(0.5, 0.0, 5.0), -up), 3.0, up);
+        assert_hit(trace(&capped, P3::new(0.5, 0.0, -3.0), up), 3.0, -up);
+        assert!(trace(&capped, P3::new(-3.0, 0.0, 3.0), right).is_none());
+        // from inside the side is hit from behind, with the normal still facing out
+        assert_hit(trace(&capped, P3::new(0.0, 0.0, 1.0), right), 1.0, right);
+
+        // without caps a ray along the axis goes straight through
+        assert!(trace(&new(false), P3::new(0.5, 0.0, 5.0), -up).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    math::CoordinateSystem,
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+#[derive(Debug)]
+pub struct Disk {
+    frame: CoordinateSystem,
+    radius: Real,
+    inner_radius: Real,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Disk {
+    /// Disk facing `normal`, with a hole of `inner_radius` when it is non-zero
+    pub fn new(
+        center: P3,
+        normal: V3,
+        radius: Real,
+        inner_radius: Real,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let frame = CoordinateSystem::from_axis(center, &normal);
+        let bbox = disk_bbox(&center, &frame.w, radius);
+        Self {
+            frame,
+            radius,
+            inner_radius,
+            bbox,
+            shader,
+            name,
+        }
+    }
+}
+
+/// Intersection of a ray in local space with the ring at height `z` around the local `w` axis,
+/// returning `t` and the local hit point
+pub(super) fn hit_disk(
+    origin: &P3,
+    direction: &V3,
+    z: Real,
+    inner_radius: Real,
+    radius: Real,
+    t_range: std::ops::Range<Real>,
+) -> Option<(Real, P3)> {
+    if direction.z.abs() < Real::EPSILON {
+        return None;
+    }
+
+    let t = (z - origin.z) / direction.z;
+    if !t_range.contains(&t) {
+        return None;
+    }
+
+    let point = origin + direction * t;
+    let r2 = point.x * point.x + point.y * point.y;
+    if r2 > radius * radius || r2 < inner_radius * inner_radius {
+        return None;
+    }
+    Some((t, point))
+}
+
+/// Tight bounds of a disk, the extent along each axis shrinks as the normal approaches it
+pub(super) fn disk_bbox(center: &P3, normal: &V3, radius: Real) -> BBox {
+    let extent = normal.map(|n| radius * (1.0 - n * n).max(0.0).sqrt());
+    BBox::new(center - extent, center + extent)
+}
+
+/// Angle of `(x, y)` around the local `w` axis, mapped to `[0, 1)`
+pub(super) fn azimuth(x: Real, y: Real) -> Real {
+    (y.atan2(x) / (2.0 * PI)).rem_euclid(1.0)
+}
+
+impl Shape for Disk {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Disk
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.frame.position
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let origin = self.frame.to_local(hit.ray.origin);
+        let direction = self.frame.vector_to_local(&hit.ray.direction);
+
+        let Some((t, point)) = hit_disk(
+            &origin,
+            &direction,
+            0.0,
+            self.inner_radius,
+            self.radius,
+            hit.t_min..hit.t,
+        ) else {
+            return false;
+        };
+
+        let r = (point.x * point.x + point.y * point.y).sqrt();
+        hit.t = t;
+        hit.normal = Unit::new_unchecked(self.frame.w);
+        hit.uv = (
+            azimuth(point.x, point.y),
+            (r - self.inner_radius) / (self.radius - self.inner_radius),
+        );
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+
+    #[test]
+    fn test_disk_intersection() {
+        let down = V3::new(0.0, 0.0, -1.0);
+        let up = V3::new(0.0, 0.0, 1.0);
+        let ring = Disk::new(P3::origin(), up, 1.0, 0.5, shader(), "ring");
+        assert_hit(trace(&ring, P3::new(0.75, 0.0, 2.0), down), 2.0, up);
+        // through the hole and past the edge
+        assert!(trace(&ring, P3::new(0.2, 0.0, 2.0), down).is_none());
+        assert!(trace(&ring, P3::new(1.5, 0.0, 2.0), down).is_none());
+    }
+}
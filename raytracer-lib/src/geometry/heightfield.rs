@@ -0,0 +1,363 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    prelude::*,
+    shader::{Hit, Shader},
+    texture::GrayscaleTexture,
+    V3,
+};
+
+/// Terrain made of a grid of heights, two triangles per grid cell. Rays walk the cells they
+/// cross in order and only test the triangles of cells whose height range they pass through.
+#[derive(Debug)]
+pub struct Heightfield {
+    /// Samples along x
+    columns: usize,
+    /// Samples along z
+    rows: usize,
+    /// World space heights, row by row
+    heights: Vec<Real>,
+    normals: Vec<V3>,
+    /// Lowest and highest height of each cell, row by row
+    cell_ranges: Vec<(Real, Real)>,
+    /// Size of a cell along x and z
+    cell_size: (Real, Real),
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Heightfield {
+    /// `samples` holds `columns` by `rows` heights between 0 and 1, row by row. They are
+    /// spread over `size.x` by `size.z` centered on `center` and scaled to `size.y`.
+    pub fn new(
+        samples: &[Real],
+        columns: usize,
+        rows: usize,
+        center: P3,
+        size: V3,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        assert!(
+            columns >= 2 && rows >= 2,
+            "heightfield needs at least 2x2 samples"
+        );
+        assert_eq!(samples.len(), columns * rows);
+
+        let min = center - V3::new(size.x / 2.0, 0.0, size.z / 2.0);
+        let heights: Vec<Real> = samples.iter().map(|h| min.y + h * size.y).collect();
+        let cell_size = (size.x / (columns - 1) as Real, size.z / (rows - 1) as Real);
+
+        let height = |i: usize, j: usize| heights[j * columns + i];
+        let normals = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                // central differences, one sided at the edges
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(columns - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(rows - 1));
+                let dx = (height(i1, j) - height(i0, j)) / ((i1 - i0) as Real * cell_size.0);
+                let dz = (height(i, j1) - height(i, j0)) / ((j1 - j0) as Real * cell_size.1);
+                V3::new(-dx, 1.0, -dz).normalize()
+            })
+            .collect();
+
+        let cell_ranges = (0..rows - 1)
+            .flat_map(|j| (0..columns - 1).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let corners = [
+                    height(i, j),
+                    height(i + 1, j),
+                    height(i, j + 1),
+                    height(i + 1, j + 1),
+                ];
+                let low = corners.iter().copied().fold(INFINITY, Real::min);
+                let high = corners.iter().copied().fold(-INFINITY, Real::max);
+                (low, high)
+            })
+            .collect::<Vec<_>>();
+
+        let low = cell_ranges.iter().map(|r| r.0).fold(INFINITY, Real::min);
+        let high = cell_ranges.iter().map(|r| r.1).fold(-INFINITY, Real::max);
+        let bbox = BBox::new(
+            P3::new(min.x, low, min.z),
+            P3::new(min.x + size.x, high, min.z + size.z),
+        );
+
+        Self {
+            columns,
+            rows,
+            heights,
+            normals,
+            cell_ranges,
+            cell_size,
+            bbox,
+            shader,
+            name,
+        }
+    }
+
+    /// Heights from the brightness of a grayscale image, its width along x and its height
+    /// along z
+    pub fn from_image(
+        image_path: &str,
+        center: P3,
+        size: V3,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let texture = GrayscaleTexture::open(image_path)?;
+        if texture.width() < 2 || texture.height() < 2 {
+            return Err(format!(
+                "heightfield needs at least 2x2 samples, the image is {}x{}",
+                texture.width(),
+                texture.height()
+            )
+            .into());
+        }
+        Ok(Self::new(
+            texture.values(),
+            texture.width(),
+            texture.height(),
+            center,
+            size,
+            shader,
+            name,
+        ))
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> P3 {
+        P3::new(
+            self.bbox.min.x + i as Real * self.cell_size.0,
+            self.heights[j * self.columns + i],
+            self.bbox.min.z + j as Real * self.cell_size.1,
+        )
+    }
+
+    /// Closest hit on the two triangles of cell `(i, j)` in `t_range`, with its smooth normal
+    fn hit_cell(
+        &self,
+        hit: &Hit,
+        i: usize,
+        j: usize,
+        t_range: std::ops::Range<Real>,
+    ) -> Option<(Real, V3)> {
+        let corners = [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)];
+        let mut closest: Option<(Real, V3)> = None;
+        for triangle in [[0, 1, 2], [1, 3, 2]] {
+            let [a, b, c] = triangle.map(|k| corners[k]);
+            let (pa, pb, pc) = (
+                self.vertex(a.0, a.1),
+                self.vertex(b.0, b.1),
+                self.vertex(c.0, c.1),
+            );
+
+            // Möller-Trumbore
+            let ab = pb - pa;
+            let ac = pc - pa;
+            let p = hit.ray.direction.cross(&ac);
+            let det = ab.dot(&p);
+            if det.abs() < Real::EPSILON {
+                continue;
+            }
+            let ao = hit.ray.origin - pa;
+            let beta = ao.dot(&p) / det;
+            let q = ao.cross(&ab);
+            let gamma = hit.ray.direction.dot(&q) / det;
+            // rays that land on an edge between cells may only visit the cell on one side
+            // of it, so the edges are a little wider
+            let edge = -VERY_SMALL_NUMBER;
+            if beta < edge || gamma < edge || beta + gamma > 1.0 - edge {
+                continue;
+            }
+
+            let t = ac.dot(&q) / det;
+            let t_max = closest.map_or(t_range.end, |(t, _)| t);
+            if (t_range.start..t_max).contains(&t) {
+                let normal = self.normals[a.1 * self.columns + a.0] * (1.0 - beta - gamma)
+                    + self.normals[b.1 * self.columns + b.0] * beta
+                    + self.normals[c.1 * self.columns + c.0] * gamma;
+                closest = Some((t, normal));
+            }
+        }
+        closest
+    }
+}
+
+impl Shape for Heightfield {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Heightfield
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let Some((near, far)) = self.bbox.hit_interval(&hit.ray) else {
+            return false;
+        };
+        let t_start = near.max(hit.t_min);
+        let t_end = far.min(hit.t);
+        if t_start > t_end {
+            return false;
+        }
+
+        let o = hit.ray.origin;
+        let d = hit.ray.direction;
+        let start = hit.ray.point_at(t_start);
+        let cells = (self.columns - 1, self.rows - 1);
+        let cell_of = |x: Real, min: Real, size: Real, count: usize| {
+            (((x - min) / size).floor().max(0.0) as usize).min(count - 1)
+        };
+        let mut i = cell_of(start.x, self.bbox.min.x, self.cell_size.0, cells.0);
+        let mut j = cell_of(start.z, self.bbox.min.z, self.cell_size.1, cells.1);
+
+        // 2D DDA over the cells, t_next is where the ray crosses into the next column or row
+        let step = |direction: Real| if direction >= 0.0 { 1 } else { -1 };
+        let (step_i, step_j) = (step(d.x), step(d.z));
+        let t_delta = (
+            (self.cell_size.0 / d.x).abs(),
+            (self.cell_size.1 / d.z).abs(),
+        );
+        let boundary = |cell: usize, step: i32, min: Real, size: Real| {
+            min + (cell as Real + if step > 0 { 1.0 } else { 0.0 }) * size
+        };
+        let mut t_next = (
+            if d.x == 0.0 {
+                INFINITY
+            } else {
+                (boundary(i, step_i, self.bbox.min.x, self.cell_size.0) - o.x) / d.x
+            },
+            if d.z == 0.0 {
+                INFINITY
+            } else {
+                (boundary(j, step_j, self.bbox.min.z, self.cell_size.1) - o.z) / d.z
+            },
+        );
+
+        let mut t_cell = t_start;
+        loop {
+            let t_exit = t_next.0.min(t_next.1).min(t_end);
+
+            // skip cells whose heights the ray passes above or below
+            let (y0, y1) = (o.y + d.y * t_cell, o.y + d.y * t_exit);
+            let (low, high) = self.cell_ranges[j * cells.0 + i];
+            if y0.min(y1) <= high && y0.max(y1) >= low {
+                // a little slack so hits on the cell's edges aren't missed
+                let slack = VERY_SMALL_NUMBER * (t_exit - t_cell).abs().max(1.0);
+                let t_range = hit.t_min.max(t_cell - slack)..hit.t.min(t_exit + slack);
+                if let Some((t, normal)) = self.hit_cell(hit, i, j, t_range) {
+                    let p = hit.ray.point_at(t);
+                    hit.t = t;
+                    hit.normal = Unit::new_normalize(normal);
+                    hit.uv = (
+                        (p.x - self.bbox.min.x) / self.bbox.extent.x,
+                        (p.z - self.bbox.min.z) / self.bbox.extent.z,
+                    );
+                    hit.shape = Some(self);
+                    hit.shader = Some(self.shader.as_ref());
+                    return true;
+                }
+            }
+
+            if t_exit >= t_end {
+                return false;
+            }
+            if t_next.0 < t_next.1 {
+                if (step_i < 0 && i == 0) || (step_i > 0 && i + 1 == cells.0) {
+                    return false;
+                }
+                i = (i as i32 + step_i) as usize;
+                t_cell = t_next.0;
+                t_next.0 += t_delta.0;
+            } else {
+                if (step_j < 0 && j == 0) || (step_j > 0 && j + 1 == cells.1) {
+                    return false;
+                }
+                j = (j as i32 + step_j) as usize;
+                t_cell = t_next.1;
+                t_next.1 += t_delta.1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+    use crate::shader::NormalShader;
+
+    /// 5x5 samples over 4 by 4 around the origin, from height 0 at x = -2 up to 1 at x = 2
+    fn ramp() -> Heightfield {
+        let samples: Vec<Real> = (0..25).map(|k| (k % 5) as Real / 4.0).collect();
+        let size = V3::new(4.0, 1.0, 4.0);
+        Heightfield::new(&samples, 5, 5, P3::origin(), size, shader(), "ramp")
+    }
+
+    #[test]
+    fn test_heightfield_intersection() {
+        let ramp = ramp();
+        let (x, y, z) = (V3::x(), V3::y(), V3::z());
+        let normal = V3::new(-0.25, 1.0, 0.0);
+        // diagonally down across a few cells onto y = (x + 2) / 4
+        let direction = V3::new(1.0, -0.2, 0.3);
+        let t = 35.0 / 9.0;
+        assert_hit(trace(&ramp, P3::new(-3.0, 1.5, -1.7), direction), t, normal);
+        assert_hit(trace(&ramp, P3::new(0.3, 5.0, 0.7), -y), 4.425, normal);
+        // parallel to the grid, along x into the slope and along z above it
+        assert_hit(trace(&ramp, P3::new(-3.0, 0.5, 0.3), x), 3.0, normal);
+        assert!(trace(&ramp, P3::new(1.0, 0.9, -3.0), z).is_none());
+        // leaving the field before reaching its height
+        assert!(trace(&ramp, P3::new(-3.0, 0.5, 0.3), -x).is_none());
+    }
+
+    #[test]
+    fn test_flat_heightfield_hits_on_cell_edges() {
+        // the box of a flat field has no thickness, so the ray enters and leaves it in the
+        // cell on one side of the edge it lands on
+        let size = V3::new(4.0, 1.0, 4.0);
+        let center = P3::new(0.0, -1.0, 0.0);
+        let flat = Heightfield::new(&[1.0; 25], 5, 5, center, size, shader(), "flat");
+        // the center ray of a camera at (0.01, 5, 0.01) looking at (0.01, 0, 0)
+        let direction = V3::new(0.0, -0.9330108358724137, -0.0018660216717448278);
+        let t = 5.0 / direction.y.abs();
+        assert_hit(
+            trace(&flat, P3::new(0.01, 5.0, 0.01), direction),
+            t,
+            V3::y(),
+        );
+        assert_hit(trace(&flat, P3::new(1.0, 5.0, 1.0), -V3::y()), 5.0, V3::y());
+    }
+
+    #[test]
+    fn test_heightfield_needs_a_grid() {
+        let path = std::env::temp_dir().join("raytracer_heightfield_1x1.png");
+        image::GrayImage::new(1, 1).save(&path).unwrap();
+        let heightfield = Heightfield::from_image(
+            &path.to_string_lossy(),
+            P3::origin(),
+            V3::new(1.0, 1.0, 1.0),
+            Arc::new(NormalShader),
+            "terrain",
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(heightfield.is_err());
+    }
+}
@@ -1,49 +1,61 @@
 use crate::prelude::*;
 use std::sync::Arc;
 
-use na::{Matrix4, Rotation3, Scale3, Translation3, Unit};
+use na::Unit;
 
+use crate::math::{AnimatedTransform, Transform};
 use crate::shader::Shader;
 
-use super::{bbox::BBox, Real, Shape, ShapeType};
+use super::{bbox::BBox, Shape, ShapeType};
 
 #[derive(Debug)]
 pub struct Instance {
     shape: Arc<dyn Shape>,
-    inv_transform: Matrix4<Real>,
-    normal_matrix: Matrix4<Real>,
+    transform: AnimatedTransform,
+    /// Covers the shape wherever it moves
     bbox: BBox,
-    shader: Arc<dyn Shader>,
-    name: &'static str,
+    shader: Option<Arc<dyn Shader>>,
+    /// `name/prototype name`, so hits on instances can be told apart from the prototype
+    name: String,
 }
 
 impl Instance {
+    /// Poses between two keyframes the bounding box is made to cover
+    const MOTION_STEPS: usize = 64;
+
+    /// `shape` may itself be an instance, its transform is applied before this one's. Hits
+    /// use the prototype's shaders unless `shader` overrides them.
     pub fn new(
         shape: Arc<dyn Shape>,
-        translation: Translation3<Real>,
-        rotation: Rotation3<Real>,
-        scale: Scale3<Real>,
-        shader: Arc<dyn Shader>,
+        transform: Transform,
+        shader: Option<Arc<dyn Shader>>,
+        name: &'static str,
+    ) -> Self {
+        Self::animated(shape, AnimatedTransform::fixed(transform), shader, name)
+    }
+
+    /// Instance that moves, rays see it with the transform at their time
+    pub fn animated(
+        shape: Arc<dyn Shape>,
+        transform: AnimatedTransform,
+        shader: Option<Arc<dyn Shader>>,
         name: &'static str,
     ) -> Self {
-        let transform =
-            translation.to_homogeneous() * rotation.to_homogeneous() * scale.to_homogeneous();
-        let inv_rotate = rotation.inverse().to_homogeneous();
-        let inv_scale = scale
-            .try_inverse()
-            .expect(&format!(
-                "The scaling applied to {} is not invertible",
-                name
-            ))
-            .to_homogeneous();
-        let inv_transform = inv_scale * inv_rotate * translation.inverse().to_homogeneous();
-        let normal_matrix = (inv_scale * inv_rotate).transpose();
-
-        let bbox = shape.get_bbox().transform(&transform);
+        let times: Vec<Real> = transform.keyframe_times().collect();
+        let mut bbox = shape.get_bbox().transform(&transform.at(times[0]).matrix);
+        for pair in times.windows(2) {
+            // rotations sweep the corners along arcs, follow them in small steps
+            for step in 1..=Self::MOTION_STEPS {
+                let time =
+                    pair[0] + (pair[1] - pair[0]) * step as Real / Self::MOTION_STEPS as Real;
+                let moved = shape.get_bbox().transform(&transform.at(time).matrix);
+                bbox = BBox::combine(&bbox, &moved);
+            }
+        }
+        let name = format!("{}/{}", name, shape.get_name());
         Self {
             shape,
-            inv_transform,
-            normal_matrix,
+            transform,
             bbox,
             shader,
             name,
@@ -57,7 +69,7 @@ impl Shape for Instance {
     }
 
     fn get_name(&self) -> &str {
-        self.name
+        &self.name
     }
 
     fn get_bbox(&self) -> &BBox {
@@ -69,14 +81,19 @@ impl Shape for Instance {
     }
 
     fn get_shader(&self) -> Arc<dyn Shader> {
-        self.shader.clone()
+        match &self.shader {
+            Some(shader) => shader.clone(),
+            None => self.shape.get_shader(),
+        }
     }
 
     fn closest_hit<'hit>(&'hit self, hit: &mut crate::shader::Hit<'hit>) -> bool {
         let og_ray = hit.ray;
+        let transform = self.transform.at(og_ray.time);
         let transformed_ray = crate::math::Ray {
-            origin: self.inv_transform.transform_point(&og_ray.origin),
-            direction: self.inv_transform.transform_vector(&og_ray.direction),
+            origin: transform.inverse.transform_point(&og_ray.origin),
+            direction: transform.inverse.transform_vector(&og_ray.direction),
+            time: og_ray.time,
         };
         hit.ray = transformed_ray;
 
@@ -86,10 +103,62 @@ impl Shape for Instance {
             return false;
         }
 
-        let normal = self.normal_matrix.transform_vector(&hit.normal);
+        let normal = transform.normal_matrix * hit.normal.into_inner();
         hit.normal = Unit::new_normalize(normal);
         hit.shape = Some(self);
+        if let Some(shader) = &self.shader {
+            hit.shader = Some(shader.as_ref());
+        }
 
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use na::Matrix4;
+
+    use super::*;
+    use crate::color;
+    use crate::geometry::{testing, Sphere};
+    use crate::shader::LambertianShader;
+
+    #[test]
+    fn test_instance_shaders() {
+        let [red, green, blue]: [Arc<dyn Shader>; 3] = [
+            color!(1.0, 0.0, 0.0),
+            color!(0.0, 1.0, 0.0),
+            color!(0.0, 0.0, 1.0),
+        ]
+        .map(|diffuse| Arc::new(LambertianShader::new(diffuse)) as Arc<dyn Shader>);
+        let proto: Arc<dyn Shape> = Arc::new(Sphere::new(P3::origin(), 1.0, red.clone(), "proto"));
+        let moved = || Transform::new(Matrix4::new_translation(&V3::new(2.0, 0.0, 0.0))).unwrap();
+        let instance = |shape: Arc<dyn Shape>, shader: Option<&Arc<dyn Shader>>, name| {
+            Arc::new(Instance::new(shape, moved(), shader.cloned(), name)) as Arc<dyn Shape>
+        };
+
+        let plain = instance(proto.clone(), None, "plain");
+        let painted = instance(proto.clone(), Some(&green), "painted");
+        let nested = instance(painted.clone(), Some(&blue), "outer");
+        let unset = instance(painted.clone(), None, "unset");
+
+        let scene = testing::scene();
+        let shader_of = |shape: &dyn Shape, x: Real| {
+            let mut hit = testing::hit(&scene, P3::new(x, 0.0, 5.0), -V3::z());
+            assert!(shape.closest_hit(&mut hit));
+            assert!((hit.t - 4.0).abs() < 1e-9);
+            hit.shader.unwrap() as *const dyn Shader as *const ()
+        };
+        let address = |shader: &Arc<dyn Shader>| Arc::as_ptr(shader) as *const ();
+        assert_eq!(shader_of(plain.as_ref(), 2.0), address(&red));
+        assert_eq!(shader_of(painted.as_ref(), 2.0), address(&green));
+        // the outermost override wins, without one the inner one still applies
+        assert_eq!(shader_of(nested.as_ref(), 4.0), address(&blue));
+        assert_eq!(shader_of(unset.as_ref(), 4.0), address(&green));
+        assert_eq!(address(&nested.get_shader()), address(&blue));
+        assert_eq!(address(&plain.get_shader()), address(&red));
+
+        assert_eq!(plain.get_name(), "plain/proto");
+        assert_eq!(nested.get_name(), "outer/painted/proto");
+    }
+}
@@ -2,8 +2,13 @@ use std::sync::Arc;
 
 use tobj::load_obj;
 
-use crate::{prelude::*, shader::Shader};
+use crate::{
+    color,
+    prelude::*,
+    shader::{BlinnPhongShader, LambertianShader, NullShader, Shader},
+};
 
+use super::subdivision::{MeshRefinement, PolygonMesh};
 use super::{BBox, Shape, Triangle, BVH};
 
 #[derive(Debug)]
@@ -15,55 +20,152 @@ pub struct Mesh {
 }
 
 impl Mesh {
-    pub fn new(model_path: String, shader: Arc<dyn Shader>, name: &'static str) -> Self {
-        let (models, _) = load_obj(
+    /// Loads every model in the OBJ file at `model_path`. When `shader` is `None` each face is
+    /// shaded with its MTL material, faces without one use the `NullShader`.
+    ///
+    /// Refined meshes are shaded with smooth vertex normals, the others use the exact faces from
+    /// the file.
+    pub fn new(
+        model_path: String,
+        shader: Option<Arc<dyn Shader>>,
+        refinement: &MeshRefinement,
+        name: &'static str,
+    ) -> Result<Self, String> {
+        let (models, materials) = load_obj(
             model_path,
             &tobj::LoadOptions {
-                triangulate: true,
+                // subdivision works on the original polygons
+                triangulate: refinement.is_empty(),
                 ..Default::default()
             },
         )
-        .expect("Failed to load model for mesh");
+        .map_err(|e| format!("failed to load model for mesh {}: {}", name, e))?;
 
-        if models.len() != 1 {
-            panic!(
-                "expected exactly one model, found {} for mesh {}",
-                models.len(),
-                name
-            );
+        if models.is_empty() {
+            return Err(format!("expected at least one model for mesh {}", name));
         }
 
-        // take ownership of the model from the Vec
-        let model = models.into_iter().next().unwrap();
-
-        let positions = model
-            .mesh
-            .positions
-            .chunks(3)
-            .map(|p| P3::new(p[0] as f64, p[1] as f64, p[2] as f64))
-            .collect::<Vec<P3>>();
-        let triangles = model
-            .mesh
-            .indices
-            .chunks(3)
-            .map(|i| {
-                Arc::new(Triangle::new(
-                    positions[i[0] as usize],
-                    positions[i[1] as usize],
-                    positions[i[2] as usize],
+        let default_shader = shader.clone().unwrap_or_else(|| Arc::new(NullShader));
+        let material_shaders = match (&shader, materials) {
+            (None, Ok(materials)) => materials.iter().map(material_shader).collect(),
+            _ => Vec::new(),
+        };
+
+        let mut triangles: Vec<Arc<dyn Shape>> = Vec::new();
+        for model in models {
+            let shader = model
+                .mesh
+                .material_id
+                .and_then(|id| material_shaders.get(id))
+                .unwrap_or(&default_shader);
+
+            let positions = model
+                .mesh
+                .positions
+                .chunks(3)
+                .map(|p| P3::new(p[0] as Real, p[1] as Real, p[2] as Real))
+                .collect::<Vec<P3>>();
+
+            if refinement.is_empty() {
+                triangles.extend(model.mesh.indices.chunks(3).map(|i| {
+                    Arc::new(Triangle::new(
+                        positions[i[0] as usize],
+                        positions[i[1] as usize],
+                        positions[i[2] as usize],
+                        shader.clone(),
+                        name,
+                    )) as Arc<dyn Shape>
+                }));
+                continue;
+            }
+
+            if refinement.displacement.is_some() && model.mesh.texcoords.is_empty() {
+                return Err(format!(
+                    "displacing mesh {} requires texture coordinates",
+                    name
+                ));
+            }
+            let refined = polygon_mesh(&model.mesh, positions).refine(refinement);
+            let normals = refined.vertex_normals();
+            triangles.extend(refined.triangulate().faces.iter().map(|face| {
+                let [a, b, c] = [face[0], face[1], face[2]];
+                Arc::new(Triangle::with_normals(
+                    refined.positions[a],
+                    refined.positions[b],
+                    refined.positions[c],
+                    [normals[a], normals[b], normals[c]],
                     shader.clone(),
                     name,
                 )) as Arc<dyn Shape>
-            })
-            .collect::<Vec<Arc<dyn Shape>>>();
+            }));
+        }
+
         let bvh = BVH::new(triangles);
         let bbox = bvh.get_bbox().clone();
-        Self {
+        Ok(Self {
             bvh,
             bbox,
-            shader,
+            shader: default_shader,
             name,
+        })
+    }
+}
+
+/// Faces of an untriangulated model, with a texture coordinate for each of their corners
+fn polygon_mesh(mesh: &tobj::Mesh, positions: Vec<P3>) -> PolygonMesh {
+    let arities: Vec<usize> = if mesh.face_arities.is_empty() {
+        vec![3; mesh.indices.len() / 3]
+    } else {
+        mesh.face_arities.iter().map(|&n| n as usize).collect()
+    };
+    let has_uvs = !mesh.texcoords.is_empty() && mesh.texcoord_indices.len() == mesh.indices.len();
+
+    let mut faces = Vec::with_capacity(arities.len());
+    let mut uvs = Vec::new();
+    let mut start = 0;
+    for arity in arities {
+        let corners = start..start + arity;
+        faces.push(
+            mesh.indices[corners.clone()]
+                .iter()
+                .map(|&i| i as usize)
+                .collect(),
+        );
+        if has_uvs {
+            uvs.push(
+                mesh.texcoord_indices[corners]
+                    .iter()
+                    .map(|&i| {
+                        let i = i as usize * 2;
+                        (mesh.texcoords[i] as Real, mesh.texcoords[i + 1] as Real)
+                    })
+                    .collect(),
+            );
         }
+        start += arity;
+    }
+
+    PolygonMesh {
+        positions,
+        faces,
+        uvs,
+    }
+}
+
+/// Blinn-Phong shader for MTL materials with a specular color, Lambertian otherwise
+fn material_shader(material: &tobj::Material) -> Arc<dyn Shader> {
+    let diffuse = material
+        .diffuse
+        .map(|[r, g, b]| color!(r, g, b))
+        .unwrap_or(color!(0.0, 0.0, 0.0));
+
+    match material.specular {
+        Some([r, g, b]) if r > 0.0 || g > 0.0 || b > 0.0 => Arc::new(BlinnPhongShader::new(
+            diffuse,
+            color!(r, g, b),
+            material.shininess.unwrap_or(1.0),
+        )),
+        _ => Arc::new(LambertianShader::new(diffuse)),
     }
 }
 
@@ -92,3 +194,39 @@ impl Shape for Mesh {
         self.bvh.closest_hit(hit)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Displacement;
+    use crate::texture::GrayscaleTexture;
+
+    #[test]
+    fn test_displacement_requires_texture_coordinates() {
+        let dir = std::env::temp_dir();
+        let model_path = dir.join("raytracer_mesh_without_uvs.obj");
+        let texture_path = dir.join("raytracer_mesh_displacement.png");
+        std::fs::write(&model_path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        image::GrayImage::new(2, 2).save(&texture_path).unwrap();
+        let refinement = MeshRefinement {
+            subdivision: None,
+            displacement: Some(Displacement {
+                texture: GrayscaleTexture::open(&texture_path.to_string_lossy()).unwrap(),
+                scale: 0.1,
+            }),
+        };
+
+        let mesh = Mesh::new(
+            model_path.to_string_lossy().into_owned(),
+            None,
+            &refinement,
+            "triangle",
+        );
+        std::fs::remove_file(&model_path).unwrap();
+        std::fs::remove_file(&texture_path).unwrap();
+        assert_eq!(
+            mesh.unwrap_err(),
+            "displacing mesh triangle requires texture coordinates"
+        );
+    }
+}
@@ -2,18 +2,43 @@ use crate::prelude::*;
 
 mod bbox;
 mod bvh;
+mod cone;
+mod csg;
 mod cuboid;
+mod curve;
+mod cylinder;
+mod disk;
+mod heightfield;
 mod instance;
 mod mesh;
+mod plane;
+mod ply;
+mod point_cloud;
+mod quadric;
+mod sdf;
 mod sphere;
+mod subdivision;
+mod torus;
 mod triangle;
 
 pub use bbox::BBox;
 pub use bvh::BVH;
+pub use cone::Cone;
+pub use csg::{Csg, CsgOperation};
 pub use cuboid::Cuboid;
+pub use curve::{CurveMode, Curves, Strand};
+pub use cylinder::Cylinder;
+pub use disk::Disk;
+pub use heightfield::Heightfield;
 pub use instance::Instance;
 pub use mesh::Mesh;
+pub use plane::Plane;
+pub use point_cloud::{PointCloud, PointPrimitive, Points};
+pub use quadric::Quadric;
+pub use sdf::{Sdf, SdfNode};
 pub use sphere::Sphere;
+pub use subdivision::{Displacement, MeshRefinement, Subdivision, SubdivisionScheme};
+pub use torus::Torus;
 pub use triangle::Triangle;
 
 pub enum ShapeType {
@@ -23,6 +48,16 @@ pub enum ShapeType {
     Mesh,
     Instance,
     Plane,
+    Disk,
+    Cylinder,
+    Cone,
+    Torus,
+    Csg,
+    Sdf,
+    Quadric,
+    Heightfield,
+    Curves,
+    PointCloud,
 }
 
 pub trait Shape: Send + Sync + std::fmt::Debug {
@@ -33,3 +68,66 @@ pub trait Shape: Send + Sync + std::fmt::Debug {
     fn get_shader(&self) -> std::sync::Arc<dyn crate::shader::Shader>;
     fn closest_hit<'hit>(&'hit self, hit: &mut crate::shader::Hit<'hit>) -> bool;
 }
+
+/// Shared by the tests of the shapes
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::Arc;
+
+    use super::Shape;
+    use crate::{
+        math::Ray,
+        parse_scene,
+        prelude::*,
+        scene::Scene,
+        shader::{Hit, NormalShader, Shader},
+        RenderSettings, V3,
+    };
+
+    pub fn shader() -> Arc<dyn Shader> {
+        Arc::new(NormalShader)
+    }
+
+    /// Empty scene, since hits need one though shapes don't look at it
+    pub fn scene() -> Scene {
+        let scene_json = r#"{"scene": {
+            "camera": [{"_name": "main", "_type": "perspective", "position": "0 0 4",
+                "lookatPoint": "0 0 0", "vfov": 30}],
+            "shape": []
+        }}"#;
+        parse_scene(scene_json, "", &RenderSettings::default()).unwrap()
+    }
+
+    /// Hit for the ray from `origin` along `direction`, ready for `closest_hit`
+    pub fn hit(scene: &Scene, origin: P3, direction: V3) -> Hit<'_> {
+        let ray = Ray {
+            origin,
+            direction,
+            ..Default::default()
+        };
+        let mut hit = Hit::new(ray, scene);
+        hit.t_min = VERY_SMALL_NUMBER;
+        hit
+    }
+
+    /// `t` and unit normal where the ray from `origin` along `direction` first hits `shape`
+    pub fn trace(shape: &dyn Shape, origin: P3, direction: V3) -> Option<(Real, V3)> {
+        let scene = scene();
+        let mut hit = hit(&scene, origin, direction);
+        shape
+            .closest_hit(&mut hit)
+            .then(|| (hit.t, hit.normal.into_inner().normalize()))
+    }
+
+    pub fn assert_hit(hit: Option<(Real, V3)>, t: Real, normal: V3) {
+        let (hit_t, hit_normal) = hit.expect("expected a hit");
+        let normal = normal.normalize();
+        assert!((hit_t - t).abs() < 1e-6, "t {} instead of {}", hit_t, t);
+        assert!(
+            (hit_normal - normal).norm() < 1e-6,
+            "normal {:?} instead of {:?}",
+            hit_normal,
+            normal
+        );
+    }
+}
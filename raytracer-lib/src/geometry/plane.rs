@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    math::CoordinateSystem,
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+#[derive(Debug)]
+pub struct Plane {
+    frame: CoordinateSystem,
+    /// Width and length along the local `u` and `v` axes, `None` for an infinite plane
+    size: Option<(Real, Real)>,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Plane {
+    /// Plane through `center` facing `normal`. With a `size` it is a `width` by `length`
+    /// rectangle centered on `center`, otherwise it is infinite.
+    pub fn new(
+        center: P3,
+        normal: V3,
+        size: Option<(Real, Real)>,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let frame = CoordinateSystem::from_axis(center, &normal);
+        let bbox = match size {
+            Some((width, length)) => {
+                let half_u = frame.u * width / 2.0;
+                let half_v = frame.v * length / 2.0;
+                let extent = half_u.abs() + half_v.abs();
+                BBox::new(center - extent, center + extent)
+            }
+            None => BBox::new(
+                P3::new(-INFINITY, -INFINITY, -INFINITY),
+                P3::new(INFINITY, INFINITY, INFINITY),
+            ),
+        };
+
+        Self {
+            frame,
+            size,
+            bbox,
+            shader,
+            name,
+        }
+    }
+}
+
+impl Shape for Plane {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Plane
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.frame.position
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let origin = self.frame.to_local(hit.ray.origin);
+        let direction = self.frame.vector_to_local(&hit.ray.direction);
+        if direction.z.abs() < Real::EPSILON {
+            return false;
+        }
+
+        let t = -origin.z / direction.z;
+        if !(hit.t_min..hit.t).contains(&t) {
+            return false;
+        }
+
+        let point = origin + direction * t;
+        let uv = match self.size {
+            Some((width, length)) => {
+                let uv = (point.x / width + 0.5, point.y / length + 0.5);
+                if !(0.0..=1.0).contains(&uv.0) || !(0.0..=1.0).contains(&uv.1) {
+                    return false;
+                }
+                uv
+            }
+            // infinite planes repeat every unit
+            None => (point.x.rem_euclid(1.0), point.y.rem_euclid(1.0)),
+        };
+
+        hit.t = t;
+        hit.normal = Unit::new_unchecked(self.frame.w);
+        hit.uv = uv;
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+
+    #[test]
+    fn test_plane_intersection() {
+        let down = V3::new(0.0, 0.0, -1.0);
+        let up = V3::new(0.0, 0.0, 1.0);
+        let square = Plane::new(P3::origin(), up, Some((2.0, 2.0)), shader(), "square");
+        assert_hit(trace(&square, P3::new(0.5, 0.5, 3.0), down), 3.0, up);
+        // the normal stays on the side it was given
+        assert_hit(trace(&square, P3::new(0.5, 0.5, -3.0), up), 3.0, up);
+        assert!(trace(&square, P3::new(2.0, 0.0, 3.0), down).is_none());
+        assert!(trace(&square, P3::new(0.0, 0.0, 1.0), V3::new(1.0, 0.0, 0.0)).is_none());
+
+        let infinite = Plane::new(P3::origin(), up, None, shader(), "floor");
+        assert_hit(trace(&infinite, P3::new(100.0, 0.0, 3.0), down), 3.0, up);
+    }
+}
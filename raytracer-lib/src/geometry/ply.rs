@@ -0,0 +1,298 @@
+//! Minimal PLY reader for the vertices of point clouds
+
+use std::io::{BufRead, BufReader, Read};
+
+use super::point_cloud::Points;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Scalar::F32 | Scalar::F64)
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(Scalar, String),
+    /// Lists are skipped, only their types are needed to do so
+    List(Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+/// Reads the positions of the vertices of an ASCII or binary PLY file, along with their
+/// `red`, `green` and `blue` colors and `nx`, `ny` and `nz` normals when it has them
+pub(super) fn read_ply(reader: impl Read) -> std::io::Result<Points> {
+    let mut reader = BufReader::new(reader);
+    let (format, elements) = read_header(&mut reader)?;
+
+    let mut points = Points::default();
+    for element in &elements {
+        let columns: Vec<Option<&str>> = element
+            .properties
+            .iter()
+            .map(|property| match property {
+                Property::Scalar(_, name) => Some(name.as_str()),
+                Property::List(..) => None,
+            })
+            .collect();
+        let column = |names: &[&str]| {
+            columns
+                .iter()
+                .position(|c| c.is_some_and(|c| names.contains(&c)))
+        };
+        let is_vertex = element.name == "vertex";
+        let position = [column(&["x"]), column(&["y"]), column(&["z"])];
+        let color = [
+            column(&["red", "r", "diffuse_red"]),
+            column(&["green", "g", "diffuse_green"]),
+            column(&["blue", "b", "diffuse_blue"]),
+        ];
+        let normal = [column(&["nx"]), column(&["ny"]), column(&["nz"])];
+        if is_vertex && position.contains(&None) {
+            return Err(invalid("ply vertices need x, y and z properties"));
+        }
+        let has_color = is_vertex && !color.contains(&None);
+        let has_normal = is_vertex && !normal.contains(&None);
+
+        let mut values = vec![0.0; element.properties.len()];
+        let mut line = String::new();
+        for _ in 0..element.count {
+            match format {
+                Format::Ascii => {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 {
+                        return Err(invalid("ply file ends early"));
+                    }
+                    read_ascii_row(&line, &element.properties, &mut values)?;
+                }
+                _ => read_binary_row(&mut reader, format, &element.properties, &mut values)?,
+            }
+            if !is_vertex {
+                continue;
+            }
+
+            let get = |i: [Option<usize>; 3]| i.map(|i| values[i.unwrap()]);
+            points.positions.push(get(position).map(|x| x as f32));
+            if has_color {
+                let scale = match &element.properties[color[0].unwrap()] {
+                    Property::Scalar(Scalar::U16, _) => 255.0 / u16::MAX as f64,
+                    Property::Scalar(scalar, _) if scalar.is_float() => 255.0,
+                    _ => 1.0,
+                };
+                points
+                    .colors
+                    .push(get(color).map(|c| (c * scale).round().clamp(0.0, 255.0) as u8));
+            }
+            if has_normal {
+                points.normals.push(get(normal).map(|n| n as f32));
+            }
+        }
+
+        if is_vertex {
+            break;
+        }
+    }
+    Ok(points)
+}
+
+fn read_header(reader: &mut impl BufRead) -> std::io::Result<(Format, Vec<Element>)> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim() != "ply" {
+        return Err(invalid("not a ply file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("ply header has no end_header"));
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown ply format {}", name))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid ply element count {}", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, _name] => {
+                let property = match (Scalar::parse(count), Scalar::parse(item)) {
+                    (Some(count), Some(item)) => Property::List(count, item),
+                    _ => return Err(invalid(format!("unknown ply list type in {}", line.trim()))),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("ply property before any element"))?
+                    .properties
+                    .push(property);
+            }
+            ["property", scalar, name] => {
+                let scalar = Scalar::parse(scalar)
+                    .ok_or_else(|| invalid(format!("unknown ply type {}", scalar)))?;
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("ply property before any element"))?
+                    .properties
+                    .push(Property::Scalar(scalar, name.to_string()));
+            }
+            _ => (), // comments and obj_info
+        }
+    }
+
+    let format = format.ok_or_else(|| invalid("ply header has no format"))?;
+    Ok((format, elements))
+}
+
+fn read_ascii_row(line: &str, properties: &[Property], values: &mut [f64]) -> std::io::Result<()> {
+    let mut words = line.split_whitespace();
+    let mut next = || -> std::io::Result<f64> {
+        let word = words
+            .next()
+            .ok_or_else(|| invalid("ply row is too short"))?;
+        word.parse()
+            .map_err(|_| invalid(format!("invalid ply value {}", word)))
+    };
+    for (value, property) in values.iter_mut().zip(properties) {
+        match property {
+            Property::Scalar(..) => *value = next()?,
+            Property::List(..) => {
+                for _ in 0..next()? as usize {
+                    next()?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_binary_row(
+    reader: &mut impl Read,
+    format: Format,
+    properties: &[Property],
+    values: &mut [f64],
+) -> std::io::Result<()> {
+    let mut read = |scalar: Scalar| -> std::io::Result<f64> {
+        let mut buffer = [0u8; 8];
+        let bytes = &mut buffer[..scalar.size()];
+        reader.read_exact(bytes)?;
+        if format == Format::BinaryBigEndian {
+            bytes.reverse();
+        }
+        let a = buffer;
+        Ok(match scalar {
+            Scalar::I8 => a[0] as i8 as f64,
+            Scalar::U8 => a[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([a[0], a[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([a[0], a[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([a[0], a[1], a[2], a[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([a[0], a[1], a[2], a[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([a[0], a[1], a[2], a[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(a),
+        })
+    };
+    for (value, property) in values.iter_mut().zip(properties) {
+        match property {
+            Property::Scalar(scalar, _) => *value = read(*scalar)?,
+            Property::List(count, item) => {
+                for _ in 0..read(*count)? as usize {
+                    read(*item)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_ascii_ply() {
+        let ply = "ply
+format ascii 1.0
+comment skipped
+element vertex 2
+property float x
+property float y
+property float z
+property list uchar int skipped
+property float red
+property float green
+property float blue
+element face 1
+property list uchar int vertex_indices
+end_header
+1 2 3 2 7 8 1.0 0.5 0.0
+-1 0.5 0 0 0 0 1
+3 0 1 1
+";
+        let points = read_ply(ply.as_bytes()).unwrap();
+        assert_eq!(points.positions, vec![[1.0, 2.0, 3.0], [-1.0, 0.5, 0.0]]);
+        assert_eq!(points.colors, vec![[255, 128, 0], [0, 0, 255]]);
+        assert!(points.normals.is_empty());
+    }
+}
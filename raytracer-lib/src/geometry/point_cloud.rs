@@ -0,0 +1,439 @@
+use std::{path::Path, sync::Arc};
+
+use na::Unit;
+
+use super::{ply::read_ply, BBox, Shape, ShapeType};
+use crate::{
+    color,
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+/// What each point of a point cloud is drawn as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointPrimitive {
+    Sphere,
+    /// Disk perpendicular to the point's normal, or facing the ray for points without one
+    Disk,
+}
+
+/// Points as they are loaded, in single precision to keep large scans small
+#[derive(Debug, Default)]
+pub struct Points {
+    pub positions: Vec<[f32; 3]>,
+    /// Either empty or one color for each point
+    pub colors: Vec<[u8; 3]>,
+    /// Either empty or one normal for each point
+    pub normals: Vec<[f32; 3]>,
+}
+
+impl Points {
+    /// Loads a `.ply` file, any other extension is read as XYZ text
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("ply") => read_ply(file),
+            _ => Self::read_xyz(std::io::BufReader::new(file)),
+        }
+    }
+
+    /// XYZ text has one point per line, with its position, then optionally its color from 0
+    /// to 255 and then optionally its normal, separated by whitespace or commas. Every line
+    /// needs the same columns. Empty lines and lines starting with `#` are ignored.
+    pub fn read_xyz(reader: impl std::io::BufRead) -> std::io::Result<Self> {
+        let invalid = |line: usize, message: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line {}: {}", line + 1, message),
+            )
+        };
+
+        let mut points = Points::default();
+        let mut columns = None;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let numbers = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|word| !word.is_empty())
+                .map(|word| word.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid(i, &e.to_string()))?;
+            if !matches!(numbers.len(), 3 | 6 | 9) {
+                return Err(invalid(i, "expected a position, a color and a normal"));
+            }
+            if *columns.get_or_insert(numbers.len()) != numbers.len() {
+                return Err(invalid(i, "every point needs the same columns"));
+            }
+
+            points.positions.push([numbers[0], numbers[1], numbers[2]]);
+            if numbers.len() >= 6 {
+                points
+                    .colors
+                    .push([3, 4, 5].map(|c| numbers[c].round().clamp(0.0, 255.0) as u8));
+            }
+            if numbers.len() == 9 {
+                points.normals.push([numbers[6], numbers[7], numbers[8]]);
+            }
+        }
+        Ok(points)
+    }
+}
+
+/// Node of the point cloud's BVH, stored in depth first order
+#[derive(Debug)]
+struct Node {
+    bbox: BBox,
+    /// First point of a leaf, or the index of the second child of an inner node, whose
+    /// first child follows it
+    start: u32,
+    /// Number of points of a leaf, zero for inner nodes
+    count: u32,
+}
+
+/// Points rendered as they are, without meshing them. The points are sorted into their own
+/// BVH, whose leaves hold ranges of points instead of shapes.
+#[derive(Debug)]
+pub struct PointCloud {
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[u8; 3]>,
+    normals: Vec<[f32; 3]>,
+    nodes: Vec<Node>,
+    radius: Real,
+    primitive: PointPrimitive,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl PointCloud {
+    /// Points in a leaf of the BVH
+    const LEAF_SIZE: usize = 8;
+
+    pub fn new(
+        points: Points,
+        radius: Real,
+        primitive: PointPrimitive,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Result<Self, String> {
+        let count = points.positions.len();
+        if count == 0 {
+            return Err(format!("{} has no points", name));
+        }
+        for (values, what) in [
+            (points.colors.len(), "colors"),
+            (points.normals.len(), "normals"),
+        ] {
+            if values != 0 && values != count {
+                return Err(format!(
+                    "{} has {} {} for {} points",
+                    name, values, what, count
+                ));
+            }
+        }
+
+        let mut order: Vec<u32> = (0..count as u32).collect();
+        let mut nodes = Vec::with_capacity(2 * count / Self::LEAF_SIZE + 1);
+        build(&mut nodes, &mut order, 0, &points.positions, radius);
+
+        Ok(Self {
+            positions: reorder(&points.positions, &order),
+            colors: reorder(&points.colors, &order),
+            normals: reorder(&points.normals, &order),
+            nodes,
+            radius,
+            primitive,
+            shader,
+            name,
+        })
+    }
+
+    /// Distance along the ray to point `i` and the normal there, if it is in range
+    fn hit_point(&self, hit: &Hit, i: usize) -> Option<(Real, V3)> {
+        let center = position(&self.positions[i]);
+        let origin = hit.ray.origin;
+        let direction = hit.ray.direction;
+        let t_range = hit.t_min..hit.t;
+
+        match self.primitive {
+            PointPrimitive::Sphere => {
+                let to_origin = origin - center;
+                let a = direction.norm_squared();
+                let half_b = to_origin.dot(&direction);
+                let c = to_origin.norm_squared() - self.radius * self.radius;
+                let discriminant = half_b * half_b - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let root = discriminant.sqrt();
+                let t = [(-half_b - root) / a, (-half_b + root) / a]
+                    .into_iter()
+                    .find(|t| t_range.contains(t))?;
+                Some((t, (hit.ray.point_at(t) - center) / self.radius))
+            }
+            PointPrimitive::Disk => {
+                let normal = match self.normals.get(i) {
+                    Some(normal) => position(normal).coords,
+                    None => -direction,
+                };
+                let denominator = direction.dot(&normal);
+                if denominator.abs() < VERY_SMALL_NUMBER * normal.norm() {
+                    return None;
+                }
+                let t = (center - origin).dot(&normal) / denominator;
+                if !t_range.contains(&t)
+                    || (hit.ray.point_at(t) - center).norm_squared() > self.radius * self.radius
+                {
+                    return None;
+                }
+                // both sides of the disk face the ray
+                Some((t, -normal * denominator.signum()))
+            }
+        }
+    }
+}
+
+/// `values` in the order of the indices in `order`, or nothing if there are no values
+fn reorder<T: Copy>(values: &[T], order: &[u32]) -> Vec<T> {
+    order
+        .iter()
+        .filter_map(|&i| values.get(i as usize).copied())
+        .collect()
+}
+
+fn position(p: &[f32; 3]) -> P3 {
+    P3::new(p[0] as Real, p[1] as Real, p[2] as Real)
+}
+
+/// Adds the node for the points in `order`, which start at `offset` in the whole list, and
+/// its children to `nodes`. Returns the index of the node.
+fn build(
+    nodes: &mut Vec<Node>,
+    order: &mut [u32],
+    offset: usize,
+    positions: &[[f32; 3]],
+    radius: Real,
+) -> usize {
+    let (min, max) = order.iter().fold(
+        (
+            P3::from(V3::repeat(INFINITY)),
+            P3::from(V3::repeat(-INFINITY)),
+        ),
+        |(min, max), &i| {
+            let p = position(&positions[i as usize]);
+            (min.inf(&p), max.sup(&p))
+        },
+    );
+    let padding = V3::repeat(radius);
+    let index = nodes.len();
+    nodes.push(Node {
+        bbox: BBox::new(min - padding, max + padding),
+        start: offset as u32,
+        count: order.len() as u32,
+    });
+    if order.len() <= PointCloud::LEAF_SIZE {
+        return index;
+    }
+
+    // split at the median along the longest axis
+    let axis = (max - min).imax();
+    let middle = order.len() / 2;
+    order.select_nth_unstable_by(middle, |&a, &b| {
+        positions[a as usize][axis].total_cmp(&positions[b as usize][axis])
+    });
+    let (first, second) = order.split_at_mut(middle);
+    build(nodes, first, offset, positions, radius);
+    let second = build(nodes, second, offset + middle, positions, radius);
+    nodes[index].start = second as u32;
+    nodes[index].count = 0;
+    index
+}
+
+impl Shape for PointCloud {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::PointCloud
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.nodes[0].bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.nodes[0].bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let mut closest = None;
+        // the tree is balanced, so 64 levels are plenty
+        let mut stack = [0usize; 64];
+        let mut size = 1;
+        while size > 0 {
+            size -= 1;
+            let index = stack[size];
+            let node = &self.nodes[index];
+            if node.bbox.hit(&hit.ray, hit.t_min, hit.t).is_none() {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack[size] = node.start as usize;
+                stack[size + 1] = index + 1;
+                size += 2;
+                continue;
+            }
+            let start = node.start as usize;
+            for i in start..start + node.count as usize {
+                if let Some((t, normal)) = self.hit_point(hit, i) {
+                    hit.t = t;
+                    closest = Some((i, normal));
+                }
+            }
+        }
+
+        let Some((i, normal)) = closest else {
+            return false;
+        };
+        hit.normal = Unit::new_normalize(normal);
+        hit.uv = (0.0, 0.0);
+        hit.color = self
+            .colors
+            .get(i)
+            .map(|c| color!(c[0] as f32, c[1] as f32, c[2] as f32) / 255.0);
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{self, assert_hit, shader, trace};
+
+    fn cloud(points: Points, primitive: PointPrimitive) -> PointCloud {
+        PointCloud::new(points, 0.4, primitive, shader(), "cloud").unwrap()
+    }
+
+    #[test]
+    fn test_point_cloud_intersection() {
+        let (x, z) = (V3::x(), V3::z());
+        let points = || Points {
+            positions: vec![[0.0, 0.0, 0.0]],
+            ..Default::default()
+        };
+        let spheres = cloud(points(), PointPrimitive::Sphere);
+        assert_hit(trace(&spheres, P3::new(0.0, 0.0, 5.0), -z), 4.6, z);
+        assert_hit(trace(&spheres, P3::new(5.0, 0.0, 0.0), -x), 4.6, x);
+        assert!(trace(&spheres, P3::new(0.5, 0.0, 5.0), -z).is_none());
+
+        // without normals disks face the ray
+        let disks = cloud(points(), PointPrimitive::Disk);
+        assert_hit(trace(&disks, P3::new(0.3, 0.0, 5.0), -z), 5.0, z);
+        assert_hit(trace(&disks, P3::new(5.0, 0.3, 0.0), -x), 5.0, x);
+        assert!(trace(&disks, P3::new(0.5, 0.0, 5.0), -z).is_none());
+
+        // with one they are hit from both sides, and not edge on
+        let facing_z = cloud(
+            Points {
+                normals: vec![[0.0, 0.0, 1.0]],
+                ..points()
+            },
+            PointPrimitive::Disk,
+        );
+        assert_hit(trace(&facing_z, P3::new(0.3, 0.0, 5.0), -z), 5.0, z);
+        assert_hit(trace(&facing_z, P3::new(0.3, 0.0, -5.0), z), 5.0, -z);
+        assert!(trace(&facing_z, P3::new(5.0, 0.0, 0.0), -x).is_none());
+    }
+
+    #[test]
+    fn test_point_cloud_colors_and_bvh() {
+        // a row of points along x in shuffled order, spread over many leaves, each with its
+        // x as its red
+        let count = 100;
+        let order = (0..count).map(|k| (k * 37) % count);
+        let points = Points {
+            positions: order.clone().map(|k| [k as f32, 0.0, 0.0]).collect(),
+            colors: order.map(|k| [k as u8, 0, 0]).collect(),
+            normals: Vec::new(),
+        };
+        let spheres = cloud(points, PointPrimitive::Sphere);
+        assert!(spheres.nodes.len() > count / PointCloud::LEAF_SIZE);
+
+        let scene = testing::scene();
+        let trace_color = |origin: P3, direction: V3| {
+            let mut hit = testing::hit(&scene, origin, direction);
+            assert!(spheres.closest_hit(&mut hit));
+            (hit.t, hit.color.unwrap())
+        };
+        for k in [0, 7, 8, 42, 99] {
+            let (t, color) = trace_color(P3::new(k as Real, 0.0, 5.0), -V3::z());
+            assert!((t - 4.6).abs() < 1e-6);
+            assert_eq!(color, color!(k as f32 / 255.0, 0.0, 0.0));
+        }
+        // along the row only the point at the end facing the ray is hit
+        let (t, color) = trace_color(P3::new(-5.0, 0.0, 0.0), V3::x());
+        assert!((t - 4.6).abs() < 1e-6);
+        assert_eq!(color, Color::zeros());
+        let (t, color) = trace_color(P3::new(105.0, 0.0, 0.0), -V3::x());
+        assert!((t - 5.6).abs() < 1e-6);
+        assert_eq!(color, color!(99.0 / 255.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_point_cloud_needs_matching_attributes() {
+        let new = |points| PointCloud::new(points, 0.1, PointPrimitive::Disk, shader(), "scan");
+        assert_eq!(new(Points::default()).unwrap_err(), "scan has no points");
+        let points = Points {
+            positions: vec![[0.0; 3]; 3],
+            colors: vec![[255; 3]; 2],
+            normals: Vec::new(),
+        };
+        assert_eq!(new(points).unwrap_err(), "scan has 2 colors for 3 points");
+        let points = Points {
+            positions: vec![[0.0; 3]; 2],
+            colors: Vec::new(),
+            normals: vec![[0.0, 1.0, 0.0]; 3],
+        };
+        assert_eq!(new(points).unwrap_err(), "scan has 3 normals for 2 points");
+    }
+
+    #[test]
+    fn test_read_xyz() {
+        let xyz = "# x, y, z, r, g, b
+1 2 3 255 128 0
+
+# commas work too
+-1.5,0,2.5, 0,0,300
+";
+        let points = Points::read_xyz(xyz.as_bytes()).unwrap();
+        assert_eq!(points.positions, vec![[1.0, 2.0, 3.0], [-1.5, 0.0, 2.5]]);
+        assert_eq!(points.colors, vec![[255, 128, 0], [0, 0, 255]]);
+        assert!(points.normals.is_empty());
+
+        let points = Points::read_xyz("0 0 0 1 2 3 0 1 0".as_bytes()).unwrap();
+        assert_eq!(points.normals, vec![[0.0, 1.0, 0.0]]);
+
+        let error = Points::read_xyz("0 0 0 1 2 3\n1 1 1\n".as_bytes()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: every point needs the same columns"
+        );
+        let error = Points::read_xyz("0 0 0 1\n".as_bytes()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 1: expected a position, a color and a normal"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+
+use na::{Matrix3, Unit};
+
+use super::disk::azimuth;
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    math::solve_quadratic,
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+/// Implicit surface `A x^2 + B y^2 + C z^2 + D xy + E xz + F yz + G x + H y + I z + J = 0`,
+/// clipped to `bounds`
+#[derive(Debug)]
+pub struct Quadric {
+    /// Symmetric matrix of the quadratic terms
+    quadratic: Matrix3<Real>,
+    linear: V3,
+    constant: Real,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Quadric {
+    /// `coefficients` are `A` through `J`. Normals point towards where the polynomial is
+    /// positive.
+    pub fn new(
+        coefficients: [Real; 10],
+        bounds: BBox,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let [a, b, c, d, e, f, g, h, i, j] = coefficients;
+        #[rustfmt::skip]
+        let quadratic = Matrix3::new(
+            a,       d / 2.0, e / 2.0,
+            d / 2.0, b,       f / 2.0,
+            e / 2.0, f / 2.0, c,
+        );
+        Self {
+            quadratic,
+            linear: V3::new(g, h, i),
+            constant: j,
+            bbox: bounds,
+            shader,
+            name,
+        }
+    }
+
+    /// Ellipsoid with semi-axes `radii` along x, y and z
+    pub fn ellipsoid(center: P3, radii: V3, shader: Arc<dyn Shader>, name: &'static str) -> Self {
+        let inverse = radii.map(|r| 1.0 / (r * r));
+        let bounds = BBox::new(center - radii, center + radii);
+        Self::centered(center, inverse, V3::zeros(), -1.0, bounds, shader, name)
+    }
+
+    /// Paraboloid opening up the y axis from `center`, `radii` is its x and z radius at `height`
+    pub fn paraboloid(
+        center: P3,
+        radii: (Real, Real),
+        height: Real,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let (rx, rz) = radii;
+        let quadratic = V3::new(height / (rx * rx), 0.0, height / (rz * rz));
+        let bounds = BBox::new(
+            center + V3::new(-rx, 0.0, -rz),
+            center + V3::new(rx, height, rz),
+        );
+        Self::centered(center, quadratic, -V3::y(), 0.0, bounds, shader, name)
+    }
+
+    /// Hyperboloid around the y axis with its waist at `center`, cut off `height` above and
+    /// below it. `radii` is the x and z radius of the waist of a one sheet hyperboloid or the
+    /// distance between `center` and the vertex of each sheet of a two sheet hyperboloid.
+    pub fn hyperboloid(
+        center: P3,
+        radii: V3,
+        height: Real,
+        two_sheets: bool,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let inverse = radii.map(|r| 1.0 / (r * r));
+        let quadratic = V3::new(inverse.x, -inverse.y, inverse.z);
+        let (constant, scale) = match two_sheets {
+            false => (-1.0, (1.0 + (height / radii.y).powi(2)).sqrt()),
+            true => (1.0, ((height / radii.y).powi(2) - 1.0).max(0.0).sqrt()),
+        };
+        // widest at the cut off, where x^2 / rx^2 + z^2 / rz^2 = y^2 / ry^2 -+ 1
+        let extent = V3::new(radii.x * scale, height, radii.z * scale);
+        let bounds = BBox::new(center - extent, center + extent);
+        Self::centered(
+            center,
+            quadratic,
+            V3::zeros(),
+            constant,
+            bounds,
+            shader,
+            name,
+        )
+    }
+
+    /// Quadric without cross terms, translated from the origin to `center`
+    fn centered(
+        center: P3,
+        quadratic: V3,
+        linear: V3,
+        constant: Real,
+        bounds: BBox,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        // substitute p - center into q . p^2 + l . p + c
+        let c = center.coords;
+        let shifted_linear = linear - 2.0 * quadratic.component_mul(&c);
+        let shifted_constant = quadratic.dot(&c.component_mul(&c)) - linear.dot(&c) + constant;
+        Self::new(
+            [
+                quadratic.x,
+                quadratic.y,
+                quadratic.z,
+                0.0,
+                0.0,
+                0.0,
+                shifted_linear.x,
+                shifted_linear.y,
+                shifted_linear.z,
+                shifted_constant,
+            ],
+            bounds,
+            shader,
+            name,
+        )
+    }
+
+    fn gradient(&self, p: &P3) -> V3 {
+        2.0 * self.quadratic * p.coords + self.linear
+    }
+}
+
+impl Shape for Quadric {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Quadric
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let Some((near, far)) = self.bbox.hit_interval(&hit.ray) else {
+            return false;
+        };
+        let o = hit.ray.origin.coords;
+        let d = hit.ray.direction;
+
+        let a = d.dot(&(self.quadratic * d));
+        let b = 2.0 * o.dot(&(self.quadratic * d)) + self.linear.dot(&d);
+        let c = o.dot(&(self.quadratic * o)) + self.linear.dot(&o) + self.constant;
+        let Some((t0, t1)) = solve_quadratic(a, b, c) else {
+            return false;
+        };
+
+        // the surface only exists inside the bounds
+        let t_range = hit.t_min.max(near - VERY_SMALL_NUMBER)..hit.t.min(far + VERY_SMALL_NUMBER);
+        let Some(t) = [t0, t1].into_iter().find(|t| t_range.contains(t)) else {
+            return false;
+        };
+
+        let p = hit.ray.point_at(t);
+        let local = p - self.bbox.min;
+        hit.t = t;
+        hit.normal = Unit::new_normalize(self.gradient(&p));
+        hit.uv = (
+            azimuth(p.x - self.bbox.centroid.x, p.z - self.bbox.centroid.z),
+            local.y / self.bbox.extent.y,
+        );
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+
+    #[test]
+    fn test_quadric_intersection() {
+        let (x, y, z) = (V3::x(), V3::y(), V3::z());
+        // the saddle z = xy, with its cross term
+        let saddle = Quadric::new(
+            [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0],
+            BBox::new(P3::new(-1.0, -1.0, -1.0), P3::new(1.0, 1.0, 1.0)),
+            shader(),
+            "saddle",
+        );
+        let normal = V3::new(0.5, 0.5, -1.0);
+        assert_hit(trace(&saddle, P3::new(0.5, 0.5, 5.0), -z), 4.75, normal);
+        assert!(trace(&saddle, P3::new(2.0, 0.5, 5.0), -z).is_none());
+
+        // the upper half of the unit sphere, a ray from below passes the cut off half
+        let dome = Quadric::new(
+            [1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+            BBox::new(P3::new(-1.0, 0.0, -1.0), P3::new(1.0, 1.0, 1.0)),
+            shader(),
+            "dome",
+        );
+        assert_hit(trace(&dome, P3::new(0.0, -5.0, 0.0), y), 6.0, y);
+        let (width, normal) = (Real::sqrt(0.75), V3::new(Real::sqrt(0.75), 0.5, 0.0));
+        assert_hit(
+            trace(&dome, P3::new(5.0, 0.5, 0.0), -x),
+            5.0 - width,
+            normal,
+        );
+    }
+
+    #[test]
+    fn test_ellipsoid_intersection() {
+        let (x, y) = (V3::x(), V3::y());
+        let ellipsoid = Quadric::ellipsoid(
+            P3::new(1.0, 0.0, 0.0),
+            V3::new(2.0, 1.0, 1.0),
+            shader(),
+            "egg",
+        );
+        assert_hit(trace(&ellipsoid, P3::new(5.0, 0.0, 0.0), -x), 2.0, x);
+        assert_hit(trace(&ellipsoid, P3::new(1.0, 5.0, 0.0), -y), 4.0, y);
+        // from the center the far side is hit from inside
+        assert_hit(trace(&ellipsoid, P3::new(1.0, 0.0, 0.0), -x), 2.0, -x);
+        assert!(trace(&ellipsoid, P3::new(1.0, 2.0, 0.0), -x).is_none());
+    }
+
+    #[test]
+    fn test_paraboloid_intersection() {
+        let y = V3::y();
+        // y = x^2 + z^2 up to y = 1, its normals point away from the inside of the bowl
+        let bowl = Quadric::paraboloid(P3::origin(), (1.0, 1.0), 1.0, shader(), "bowl");
+        assert_hit(
+            trace(&bowl, P3::new(0.5, 5.0, 0.0), -y),
+            4.75,
+            V3::new(1.0, -1.0, 0.0),
+        );
+        assert_hit(trace(&bowl, P3::new(0.0, -5.0, 0.0), y), 5.0, -y);
+        // outside of the bounds the surface is cut off
+        assert!(trace(&bowl, P3::new(1.5, 5.0, 0.0), -y).is_none());
+    }
+
+    #[test]
+    fn test_hyperboloid_intersection() {
+        let (x, y) = (V3::x(), V3::y());
+        let radii = V3::new(1.0, 1.0, 1.0);
+        // x^2 - y^2 + z^2 = 1
+        let one = Quadric::hyperboloid(P3::origin(), radii, 1.0, false, shader(), "tower");
+        assert_hit(trace(&one, P3::new(5.0, 0.0, 0.0), -x), 4.0, x);
+        let (width, normal) = (Real::sqrt(2.0), V3::new(Real::sqrt(2.0), -1.0, 0.0));
+        assert_hit(trace(&one, P3::new(5.0, 1.0, 0.0), -x), 5.0 - width, normal);
+        // straight down the middle
+        assert!(trace(&one, P3::new(0.0, 5.0, 0.0), -y).is_none());
+
+        // y^2 - x^2 - z^2 = 1, its normals point into the gap between the sheets
+        let two = Quadric::hyperboloid(P3::origin(), radii, 2.0, true, shader(), "cups");
+        assert_hit(trace(&two, P3::new(0.0, 5.0, 0.0), -y), 4.0, -y);
+        assert_hit(trace(&two, P3::new(0.0, -5.0, 0.0), y), 4.0, y);
+        let (width, normal) = (Real::sqrt(1.25), V3::new(Real::sqrt(1.25), -1.5, 0.0));
+        assert_hit(trace(&two, P3::new(5.0, 1.5, 0.0), -x), 5.0 - width, normal);
+        // the gap between the sheets
+        assert!(trace(&two, P3::new(5.0, 0.0, 0.0), -x).is_none());
+    }
+}
@@ -0,0 +1,441 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+/// Distance below which a marched point counts as being on the surface
+static SURFACE_DISTANCE: Real = 1e-4;
+/// Rays that take more steps than this, typically grazing the surface, count as misses
+static MAX_STEPS: usize = 512;
+
+/// Node of a signed distance function tree
+#[derive(Debug)]
+pub enum SdfNode {
+    Sphere {
+        center: P3,
+        radius: Real,
+    },
+    /// Box of `extent` whose edges are rounded off by `rounding`
+    Box {
+        center: P3,
+        extent: V3,
+        rounding: Real,
+    },
+    /// Torus around the y axis
+    Torus {
+        center: P3,
+        major_radius: Real,
+        minor_radius: Real,
+    },
+    /// Segment from `a` to `b` swept by a sphere
+    Capsule {
+        a: P3,
+        b: P3,
+        radius: Real,
+    },
+    /// Union that blends surfaces closer than `smoothness`, a hard union when it is zero
+    Union {
+        children: Vec<SdfNode>,
+        smoothness: Real,
+    },
+    /// Rotates the child around the y axis by `rate` radians per unit of height
+    Twist {
+        child: Box<SdfNode>,
+        rate: Real,
+    },
+    /// Copies of the child every `spacing`, from `-limit` to `limit` cells along each axis
+    /// around `center`, which should be the child's centroid. Axes with a spacing of zero are
+    /// not repeated.
+    Repeat {
+        child: Box<SdfNode>,
+        center: P3,
+        spacing: V3,
+        limit: V3,
+    },
+    /// Offsets the child's surface by a sine pattern
+    Displace {
+        child: Box<SdfNode>,
+        amplitude: Real,
+        frequency: Real,
+    },
+}
+
+impl SdfNode {
+    /// Signed distance from `p` to the surface, negative inside. Only a bound on the distance
+    /// for nodes that distort space, see `lipschitz`.
+    pub fn distance(&self, p: &P3) -> Real {
+        match self {
+            SdfNode::Sphere { center, radius } => (p - center).norm() - radius,
+            SdfNode::Box {
+                center,
+                extent,
+                rounding,
+            } => {
+                let q = (p - center).abs() - (extent / 2.0).add_scalar(-rounding);
+                q.sup(&V3::zeros()).norm() + q.max().min(0.0) - rounding
+            }
+            SdfNode::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let p = p - center;
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            SdfNode::Capsule { a, b, radius } => {
+                let pa = p - a;
+                let ba = b - a;
+                let h = (pa.dot(&ba) / ba.norm_squared()).clamp(0.0, 1.0);
+                (pa - ba * h).norm() - radius
+            }
+            SdfNode::Union {
+                children,
+                smoothness,
+            } => children
+                .iter()
+                .map(|child| child.distance(p))
+                .reduce(|a, b| smooth_min(a, b, *smoothness))
+                .unwrap_or(INFINITY),
+            SdfNode::Twist { child, rate } => {
+                let (sin, cos) = (rate * p.y).sin_cos();
+                child.distance(&P3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z))
+            }
+            SdfNode::Repeat {
+                child,
+                center,
+                spacing,
+                limit,
+            } => {
+                let cell = (p - center).zip_zip_map(spacing, limit, |x, spacing, limit| {
+                    if spacing == 0.0 {
+                        0.0
+                    } else {
+                        spacing * (x / spacing).round().clamp(-limit, limit)
+                    }
+                });
+                child.distance(&(p - cell))
+            }
+            SdfNode::Displace {
+                child,
+                amplitude,
+                frequency,
+            } => {
+                let f = p.coords * *frequency;
+                child.distance(p) + amplitude * f.x.sin() * f.y.sin() * f.z.sin()
+            }
+        }
+    }
+
+    /// Conservative bounds of the surface
+    pub fn bbox(&self) -> BBox {
+        match self {
+            SdfNode::Sphere { center, radius } => {
+                BBox::new(center - V3::repeat(*radius), center + V3::repeat(*radius))
+            }
+            SdfNode::Box { center, extent, .. } => {
+                BBox::new(center - extent / 2.0, center + extent / 2.0)
+            }
+            SdfNode::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+                let extent = V3::new(outer, *minor_radius, outer);
+                BBox::new(center - extent, center + extent)
+            }
+            SdfNode::Capsule { a, b, radius } => {
+                let r = V3::repeat(*radius);
+                BBox::new(a.inf(b) - r, a.sup(b) + r)
+            }
+            SdfNode::Union {
+                children,
+                smoothness,
+            } => {
+                let bbox = children
+                    .iter()
+                    .map(SdfNode::bbox)
+                    .reduce(|a, b| BBox::combine(&a, &b))
+                    .unwrap_or_else(|| BBox::new(P3::origin(), P3::origin()));
+                // blending grows the surface by at most a quarter of the smoothness
+                expand(&bbox, smoothness / 4.0)
+            }
+            SdfNode::Twist { child, .. } => {
+                let bbox = child.bbox();
+                let radius = twist_radius(&bbox);
+                BBox::new(
+                    P3::new(-radius, bbox.min.y, -radius),
+                    P3::new(radius, bbox.max.y, radius),
+                )
+            }
+            SdfNode::Repeat {
+                child,
+                spacing,
+                limit,
+                ..
+            } => {
+                let bbox = child.bbox();
+                let offset = spacing.abs().component_mul(limit);
+                BBox::new(bbox.min - offset, bbox.max + offset)
+            }
+            SdfNode::Displace {
+                child, amplitude, ..
+            } => expand(&child.bbox(), amplitude.abs()),
+        }
+    }
+
+    /// Bound on how fast `distance` changes, the step along a ray is the distance divided by
+    /// this so nodes that distort space don't step through the surface
+    pub fn lipschitz(&self) -> Real {
+        match self {
+            SdfNode::Sphere { .. }
+            | SdfNode::Box { .. }
+            | SdfNode::Torus { .. }
+            | SdfNode::Capsule { .. } => 1.0,
+            SdfNode::Union { children, .. } => {
+                children.iter().map(SdfNode::lipschitz).fold(1.0, Real::max)
+            }
+            SdfNode::Twist { child, rate } => {
+                child.lipschitz() * (1.0 + rate.abs() * twist_radius(&child.bbox()))
+            }
+            SdfNode::Repeat { child, .. } => child.lipschitz(),
+            SdfNode::Displace {
+                child,
+                amplitude,
+                frequency,
+            } => child.lipschitz() + (amplitude * frequency).abs() * (3.0 as Real).sqrt(),
+        }
+    }
+}
+
+/// Polynomial smooth minimum
+fn smooth_min(a: Real, b: Real, k: Real) -> Real {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k / 4.0
+}
+
+fn expand(bbox: &BBox, amount: Real) -> BBox {
+    BBox::new(bbox.min - V3::repeat(amount), bbox.max + V3::repeat(amount))
+}
+
+/// Largest distance from the y axis of a point in `bbox`
+fn twist_radius(bbox: &BBox) -> Real {
+    let x = bbox.min.x.abs().max(bbox.max.x.abs());
+    let z = bbox.min.z.abs().max(bbox.max.z.abs());
+    (x * x + z * z).sqrt()
+}
+
+/// Shape whose surface is the zero set of a signed distance function, rendered by sphere
+/// tracing
+#[derive(Debug)]
+pub struct Sdf {
+    root: SdfNode,
+    lipschitz: Real,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Sdf {
+    pub fn new(root: SdfNode, shader: Arc<dyn Shader>, name: &'static str) -> Self {
+        Self {
+            lipschitz: root.lipschitz(),
+            bbox: root.bbox(),
+            root,
+            shader,
+            name,
+        }
+    }
+
+    fn normal(&self, p: &P3) -> V3 {
+        // tetrahedron of samples around p
+        let h = SURFACE_DISTANCE;
+        [
+            V3::new(1.0, -1.0, -1.0),
+            V3::new(-1.0, -1.0, 1.0),
+            V3::new(-1.0, 1.0, -1.0),
+            V3::new(1.0, 1.0, 1.0),
+        ]
+        .iter()
+        .map(|k| k * self.root.distance(&(p + k * h)))
+        .sum()
+    }
+}
+
+impl Shape for Sdf {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Sdf
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.bbox.centroid
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let Some((near, far)) = self.bbox.hit_interval(&hit.ray) else {
+            return false;
+        };
+        let t_end = far.min(hit.t);
+        let mut t = near.max(hit.t_min);
+        if t > t_end {
+            return false;
+        }
+
+        let length = hit.ray.direction.norm();
+        let start = hit.ray.point_at(t);
+        let start_distance = self.root.distance(&start);
+        // march towards the surface from whichever side the ray starts on. Rays entering the
+        // box start outside, rays starting on the surface are outside if they are leaving it.
+        let side = if near > hit.t_min {
+            1.0
+        } else if start_distance.abs() > SURFACE_DISTANCE {
+            start_distance.signum()
+        } else if self.normal(&start).dot(&hit.ray.direction) > 0.0 {
+            1.0
+        } else {
+            -1.0
+        };
+
+        let mut previous = side * start_distance;
+        t += previous.max(SURFACE_DISTANCE) / (self.lipschitz * length);
+        for _ in 0..MAX_STEPS {
+            if t > t_end {
+                return false;
+            }
+
+            let distance = side * self.root.distance(&hit.ray.point_at(t));
+            // only count surfaces the ray is approaching, not the one it just left
+            if distance < SURFACE_DISTANCE && distance < previous {
+                let normal = self.normal(&hit.ray.point_at(t));
+                hit.t = t;
+                hit.normal = Unit::new_normalize(normal);
+                hit.uv = (0.0, 0.0);
+                hit.shape = Some(self);
+                hit.shader = Some(self.shader.as_ref());
+                return true;
+            }
+
+            t += distance.max(SURFACE_DISTANCE) / (self.lipschitz * length);
+            previous = distance;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{shader, trace};
+
+    /// Sphere tracing stops within `SURFACE_DISTANCE` of the surface, so hits are only close
+    fn assert_near_hit(hit: Option<(Real, V3)>, t: Real, normal: V3) {
+        let (hit_t, hit_normal) = hit.expect("expected a hit");
+        let normal = normal.normalize();
+        assert!((hit_t - t).abs() < 1e-3, "t {} instead of {}", hit_t, t);
+        assert!(
+            (hit_normal - normal).norm() < 1e-3,
+            "normal {:?} instead of {:?}",
+            hit_normal,
+            normal
+        );
+    }
+
+    #[test]
+    fn test_sdf_intersection() {
+        let (x, y, z) = (V3::x(), V3::y(), V3::z());
+        let sphere = |x: Real| SdfNode::Sphere {
+            center: P3::new(x, 0.0, 0.0),
+            radius: 1.0,
+        };
+        let ball = Sdf::new(sphere(0.0), shader(), "ball");
+        assert_near_hit(trace(&ball, P3::new(0.0, 0.0, 5.0), -z), 4.0, z);
+        assert_near_hit(trace(&ball, P3::new(0.0, 0.0, 0.0), x), 1.0, x);
+        assert!(trace(&ball, P3::new(2.0, 0.0, 5.0), -z).is_none());
+
+        // the blend fills in the seam between the spheres, where each is 1.125 away
+        let blend = Sdf::new(
+            SdfNode::Union {
+                children: vec![sphere(-1.0), sphere(1.0)],
+                smoothness: 0.5,
+            },
+            shader(),
+            "blend",
+        );
+        let seam = Real::sqrt(1.125 * 1.125 - 1.0);
+        assert_near_hit(trace(&blend, P3::new(0.0, 5.0, 0.0), -y), 5.0 - seam, y);
+        assert_near_hit(trace(&blend, P3::new(1.0, 5.0, 0.0), -y), 4.0, y);
+
+        // a cube turned by an eighth of a turn per unit of height
+        let rate = PI / 4.0;
+        let twisted = Sdf::new(
+            SdfNode::Twist {
+                child: Box::new(SdfNode::Box {
+                    center: P3::origin(),
+                    extent: V3::new(2.0, 2.0, 2.0),
+                    rounding: 0.0,
+                }),
+                rate,
+            },
+            shader(),
+            "twisted",
+        );
+        assert_near_hit(trace(&twisted, P3::new(0.0, 0.0, 5.0), -z), 4.0, z);
+        // halfway up the face is turned by PI / 8 and leans with the twist
+        let angle = rate * 0.5;
+        let normal = V3::new(angle.sin(), -rate * angle.tan(), angle.cos());
+        let t = 5.0 - 1.0 / angle.cos();
+        assert_near_hit(trace(&twisted, P3::new(0.0, 0.5, 5.0), -z), t, normal);
+        assert!(trace(&twisted, P3::new(0.0, 1.5, 5.0), -z).is_none());
+    }
+
+    #[test]
+    fn test_sdf_distances() {
+        let sphere = SdfNode::Sphere {
+            center: P3::new(1.0, 0.0, 0.0),
+            radius: 0.5,
+        };
+        assert!((sphere.distance(&P3::new(3.0, 0.0, 0.0)) - 1.5).abs() < 1e-9);
+
+        let cube = SdfNode::Box {
+            center: P3::origin(),
+            extent: V3::new(2.0, 2.0, 2.0),
+            rounding: 0.0,
+        };
+        assert!((cube.distance(&P3::new(0.0, 3.0, 0.0)) - 2.0).abs() < 1e-9);
+        assert!((cube.distance(&P3::origin()) + 1.0).abs() < 1e-9);
+
+        // copies at x = -1, 1 and 3, nothing beyond them
+        let repeated = SdfNode::Repeat {
+            child: Box::new(sphere),
+            center: P3::new(1.0, 0.0, 0.0),
+            spacing: V3::new(2.0, 0.0, 0.0),
+            limit: V3::new(1.0, 0.0, 0.0),
+        };
+        assert!((repeated.distance(&P3::new(-1.0, 0.0, 0.0)) + 0.5).abs() < 1e-9);
+        assert!((repeated.distance(&P3::new(6.0, 0.0, 0.0)) - 2.5).abs() < 1e-9);
+        let bbox = repeated.bbox();
+        assert!((bbox.min.x + 1.5).abs() < 1e-9 && (bbox.max.x - 3.5).abs() < 1e-9);
+    }
+}
@@ -84,7 +84,13 @@ impl Shape for Sphere {
         }
 
         hit.normal = Unit::new_normalize(self.normal(&hit.hit_point()));
+        let n = hit.normal;
+        hit.uv = (
+            (n.z.atan2(n.x) / (2.0 * PI)).rem_euclid(1.0),
+            0.5 + n.y.clamp(-1.0, 1.0).asin() / PI,
+        );
         hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
         true
     }
 }
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use crate::{prelude::*, texture::GrayscaleTexture, V3};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubdivisionScheme {
+    /// Triangles only, other polygons are split into triangles first
+    Loop,
+    /// Any polygons, every level turns them into quads
+    CatmullClark,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Subdivision {
+    pub scheme: SubdivisionScheme,
+    pub levels: u32,
+}
+
+/// Moves vertices along their normal by `scale` times the texture's value at their texture
+/// coordinates
+#[derive(Debug)]
+pub struct Displacement {
+    pub texture: GrayscaleTexture,
+    pub scale: Real,
+}
+
+/// How a mesh is refined after it is loaded, subdivision happens before displacement
+#[derive(Debug, Default)]
+pub struct MeshRefinement {
+    pub subdivision: Option<Subdivision>,
+    pub displacement: Option<Displacement>,
+}
+
+impl MeshRefinement {
+    pub fn is_empty(&self) -> bool {
+        self.subdivision.is_none() && self.displacement.is_none()
+    }
+}
+
+/// Polygon mesh with texture coordinates per face corner, so seams in the texture don't split
+/// the surface
+#[derive(Debug, Clone)]
+pub(super) struct PolygonMesh {
+    pub positions: Vec<P3>,
+    /// Indices into `positions` of each face's corners
+    pub faces: Vec<Vec<usize>>,
+    /// Texture coordinates of each face's corners, empty when the model has none
+    pub uvs: Vec<Vec<(Real, Real)>>,
+}
+
+/// Faces using an edge and the index of the vertex added on it
+struct Edge {
+    faces: Vec<usize>,
+    point: usize,
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn midpoint((u0, v0): (Real, Real), (u1, v1): (Real, Real)) -> (Real, Real) {
+    ((u0 + u1) / 2.0, (v0 + v1) / 2.0)
+}
+
+impl PolygonMesh {
+    pub fn refine(mut self, refinement: &MeshRefinement) -> Self {
+        if let Some(subdivision) = refinement.subdivision {
+            for _ in 0..subdivision.levels {
+                self = match subdivision.scheme {
+                    SubdivisionScheme::Loop => self.triangulate().loop_subdivide(),
+                    SubdivisionScheme::CatmullClark => self.catmull_clark(),
+                };
+            }
+        }
+        if let Some(displacement) = &refinement.displacement {
+            self.displace(displacement);
+        }
+        self
+    }
+
+    /// Splits every face into a fan of triangles
+    pub fn triangulate(&self) -> Self {
+        let mut faces = Vec::new();
+        let mut uvs = Vec::new();
+        for (i, face) in self.faces.iter().enumerate() {
+            for k in 1..face.len() - 1 {
+                faces.push(vec![face[0], face[k], face[k + 1]]);
+                if let Some(face_uvs) = self.uvs.get(i) {
+                    uvs.push(vec![face_uvs[0], face_uvs[k], face_uvs[k + 1]]);
+                }
+            }
+        }
+        Self {
+            positions: self.positions.clone(),
+            faces,
+            uvs,
+        }
+    }
+
+    /// Edges of every face in order, each edge gets a new vertex index after `first_point`
+    fn edges(&self, first_point: usize) -> HashMap<(usize, usize), Edge> {
+        let mut edges: HashMap<(usize, usize), Edge> = HashMap::new();
+        for (i, face) in self.faces.iter().enumerate() {
+            for k in 0..face.len() {
+                let key = edge_key(face[k], face[(k + 1) % face.len()]);
+                let next_point = first_point + edges.len();
+                edges
+                    .entry(key)
+                    .or_insert(Edge {
+                        faces: Vec::new(),
+                        point: next_point,
+                    })
+                    .faces
+                    .push(i);
+            }
+        }
+        edges
+    }
+
+    /// Neighbours of each vertex, and the neighbours along boundary edges for vertices on
+    /// a boundary. Edges shared by more than two faces are treated as boundaries too, which
+    /// keeps them sharp.
+    fn neighbours(
+        &self,
+        edges: &HashMap<(usize, usize), Edge>,
+    ) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let mut neighbours = vec![Vec::new(); self.positions.len()];
+        let mut boundary = vec![Vec::new(); self.positions.len()];
+        for (&(a, b), edge) in edges {
+            neighbours[a].push(b);
+            neighbours[b].push(a);
+            if edge.faces.len() != 2 {
+                boundary[a].push(b);
+                boundary[b].push(a);
+            }
+        }
+        (neighbours, boundary)
+    }
+
+    /// Rule shared by both schemes for vertices on a boundary, `None` for corners and other
+    /// vertices that should stay put
+    fn boundary_vertex(&self, v: usize, boundary: &[usize]) -> Option<P3> {
+        match boundary {
+            [b0, b1] => Some(P3::from(
+                self.positions[v].coords * 0.75
+                    + (self.positions[*b0].coords + self.positions[*b1].coords) * 0.125,
+            )),
+            _ => None,
+        }
+    }
+
+    /// One level of Loop subdivision, every face must be a triangle
+    pub fn loop_subdivide(&self) -> Self {
+        let vertex_count = self.positions.len();
+        let edges = self.edges(vertex_count);
+        let (neighbours, boundary) = self.neighbours(&edges);
+
+        let mut positions = vec![P3::origin(); vertex_count + edges.len()];
+        for v in 0..vertex_count {
+            positions[v] = if !boundary[v].is_empty() {
+                self.boundary_vertex(v, &boundary[v])
+                    .unwrap_or(self.positions[v])
+            } else {
+                let n = neighbours[v].len();
+                let beta = if n == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * n as Real)
+                };
+                let sum: V3 = neighbours[v]
+                    .iter()
+                    .map(|&u| self.positions[u].coords)
+                    .sum();
+                P3::from(self.positions[v].coords * (1.0 - n as Real * beta) + sum * beta)
+            };
+        }
+
+        for (&(a, b), edge) in &edges {
+            let (pa, pb) = (self.positions[a].coords, self.positions[b].coords);
+            positions[edge.point] = P3::from(match edge.faces.as_slice() {
+                [f0, f1] => {
+                    // the corners across the edge in each face
+                    let opposite = |f: usize| {
+                        let face = &self.faces[f];
+                        let c = *face.iter().find(|&&c| c != a && c != b).unwrap();
+                        self.positions[c].coords
+                    };
+                    (pa + pb) * 0.375 + (opposite(*f0) + opposite(*f1)) * 0.125
+                }
+                _ => (pa + pb) * 0.5,
+            });
+        }
+
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        let mut uvs = Vec::with_capacity(self.uvs.len() * 4);
+        for (i, face) in self.faces.iter().enumerate() {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let ab = edges[&edge_key(a, b)].point;
+            let bc = edges[&edge_key(b, c)].point;
+            let ca = edges[&edge_key(c, a)].point;
+            faces.extend([
+                vec![a, ab, ca],
+                vec![ab, b, bc],
+                vec![ca, bc, c],
+                vec![ab, bc, ca],
+            ]);
+
+            if let Some(face_uvs) = self.uvs.get(i) {
+                let [ua, ub, uc] = [face_uvs[0], face_uvs[1], face_uvs[2]];
+                let (uab, ubc, uca) = (midpoint(ua, ub), midpoint(ub, uc), midpoint(uc, ua));
+                uvs.extend([
+                    vec![ua, uab, uca],
+                    vec![uab, ub, ubc],
+                    vec![uca, ubc, uc],
+                    vec![uab, ubc, uca],
+                ]);
+            }
+        }
+
+        Self {
+            positions,
+            faces,
+            uvs,
+        }
+    }
+
+    /// One level of Catmull-Clark subdivision
+    pub fn catmull_clark(&self) -> Self {
+        let vertex_count = self.positions.len();
+        let edges = self.edges(vertex_count);
+        let (neighbours, boundary) = self.neighbours(&edges);
+        let first_face_point = vertex_count + edges.len();
+
+        let mut positions = vec![P3::origin(); first_face_point + self.faces.len()];
+        for (i, face) in self.faces.iter().enumerate() {
+            let sum: V3 = face.iter().map(|&v| self.positions[v].coords).sum();
+            positions[first_face_point + i] = P3::from(sum / face.len() as Real);
+        }
+
+        for (&(a, b), edge) in &edges {
+            let (pa, pb) = (self.positions[a].coords, self.positions[b].coords);
+            positions[edge.point] = P3::from(match edge.faces.as_slice() {
+                [f0, f1] => {
+                    let f0 = positions[first_face_point + f0].coords;
+                    let f1 = positions[first_face_point + f1].coords;
+                    (pa + pb + f0 + f1) / 4.0
+                }
+                _ => (pa + pb) / 2.0,
+            });
+        }
+
+        // faces around each vertex, for the average of their face points
+        let mut vertex_faces = vec![Vec::new(); vertex_count];
+        for (i, face) in self.faces.iter().enumerate() {
+            for &v in face {
+                vertex_faces[v].push(i);
+            }
+        }
+        for v in 0..vertex_count {
+            positions[v] = if !boundary[v].is_empty() {
+                self.boundary_vertex(v, &boundary[v])
+                    .unwrap_or(self.positions[v])
+            } else if vertex_faces[v].is_empty() {
+                self.positions[v]
+            } else {
+                let p = self.positions[v].coords;
+                let n = neighbours[v].len() as Real;
+                let faces: V3 = vertex_faces[v]
+                    .iter()
+                    .map(|f| positions[first_face_point + f].coords)
+                    .sum::<V3>()
+                    / vertex_faces[v].len() as Real;
+                let edge_midpoints: V3 = neighbours[v]
+                    .iter()
+                    .map(|&u| (p + self.positions[u].coords) / 2.0)
+                    .sum::<V3>()
+                    / n;
+                P3::from((faces + edge_midpoints * 2.0 + p * (n - 3.0)) / n)
+            };
+        }
+
+        let mut faces = Vec::new();
+        let mut uvs = Vec::new();
+        for (i, face) in self.faces.iter().enumerate() {
+            let k = face.len();
+            let edge_point = |j: usize| edges[&edge_key(face[j], face[(j + 1) % k])].point;
+            for (j, &corner) in face.iter().enumerate() {
+                let previous = (j + k - 1) % k;
+                faces.push(vec![
+                    corner,
+                    edge_point(j),
+                    first_face_point + i,
+                    edge_point(previous),
+                ]);
+            }
+
+            if let Some(face_uvs) = self.uvs.get(i) {
+                let (su, sv) = face_uvs
+                    .iter()
+                    .fold((0.0, 0.0), |(su, sv), (u, v)| (su + u, sv + v));
+                let center = (su / k as Real, sv / k as Real);
+                for j in 0..k {
+                    let previous = (j + k - 1) % k;
+                    uvs.push(vec![
+                        face_uvs[j],
+                        midpoint(face_uvs[j], face_uvs[(j + 1) % k]),
+                        center,
+                        midpoint(face_uvs[previous], face_uvs[j]),
+                    ]);
+                }
+            }
+        }
+
+        Self {
+            positions,
+            faces,
+            uvs,
+        }
+    }
+
+    /// Area weighted normal of every vertex
+    pub fn vertex_normals(&self) -> Vec<V3> {
+        let mut normals = vec![V3::zeros(); self.positions.len()];
+        for face in &self.faces {
+            // Newell's method, the length is twice the polygon's area
+            let mut normal = V3::zeros();
+            for k in 0..face.len() {
+                let p = self.positions[face[k]].coords;
+                let q = self.positions[face[(k + 1) % face.len()]].coords;
+                normal += p.cross(&q);
+            }
+            for &v in face {
+                normals[v] += normal;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| n.try_normalize(Real::EPSILON).unwrap_or(V3::y()))
+            .collect()
+    }
+
+    /// Vertices on texture seams have several texture coordinates, they move by the average of
+    /// the values at each of them
+    fn displace(&mut self, displacement: &Displacement) {
+        let mut totals = vec![(0.0, 0); self.positions.len()];
+        for (face, face_uvs) in self.faces.iter().zip(&self.uvs) {
+            for (&v, &uv) in face.iter().zip(face_uvs) {
+                totals[v].0 += displacement.texture.sample(uv);
+                totals[v].1 += 1;
+            }
+        }
+
+        let normals = self.vertex_normals();
+        for (v, (total, count)) in totals.into_iter().enumerate() {
+            if count > 0 {
+                let offset = displacement.scale * total / count as Real;
+                self.positions[v] += normals[v] * offset;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube() -> PolygonMesh {
+        let positions = (0..8)
+            .map(|i| {
+                let bit = |b: usize| if i & (1 << b) != 0 { 1.0 } else { -1.0 };
+                P3::new(bit(0), bit(1), bit(2))
+            })
+            .collect();
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+        PolygonMesh {
+            positions,
+            faces,
+            uvs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_subdivision_shrinks_towards_limit_surface() {
+        let cube = cube();
+
+        let quads = cube.catmull_clark();
+        assert_eq!(quads.positions.len(), 8 + 12 + 6);
+        assert_eq!(quads.faces.len(), 24);
+        // cube corners move to (5/9, 5/9, 5/9) after one level
+        assert!((quads.positions[7].x - 5.0 / 9.0).abs() < 1e-9);
+
+        let triangles = cube.triangulate().loop_subdivide();
+        assert_eq!(triangles.faces.len(), 12 * 4);
+        for p in &triangles.positions {
+            assert!(p.coords.abs().max() <= 1.0);
+        }
+
+        // every vertex of a closed mesh keeps an outward normal
+        for (p, n) in quads.positions.iter().zip(quads.vertex_normals()) {
+            assert!(p.coords.dot(&n) > 0.0);
+        }
+    }
+}
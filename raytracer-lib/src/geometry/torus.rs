@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use na::Unit;
+
+use super::disk::{azimuth, disk_bbox};
+use super::{BBox, Shape, ShapeType};
+use crate::{
+    math::{solve_quartic, CoordinateSystem, Ray},
+    prelude::*,
+    shader::{Hit, Shader},
+    V3,
+};
+
+#[derive(Debug)]
+pub struct Torus {
+    frame: CoordinateSystem,
+    major_radius: Real,
+    minor_radius: Real,
+    /// Bounds in the local frame, used to start the root search close to the surface
+    local_bbox: BBox,
+    bbox: BBox,
+    shader: Arc<dyn Shader>,
+    name: &'static str,
+}
+
+impl Torus {
+    /// Torus around `axis` through `center`, with a tube of `minor_radius` swept along a
+    /// circle of `major_radius`
+    pub fn new(
+        center: P3,
+        axis: V3,
+        major_radius: Real,
+        minor_radius: Real,
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        let frame = CoordinateSystem::from_axis(center, &axis);
+        let outer = major_radius + minor_radius;
+        let local_bbox = BBox::new(
+            P3::new(-outer, -outer, -minor_radius),
+            P3::new(outer, outer, minor_radius),
+        );
+
+        let ring = disk_bbox(&center, &frame.w, major_radius);
+        let tube = V3::repeat(minor_radius);
+        let bbox = BBox::new(ring.min - tube, ring.max + tube);
+
+        Self {
+            frame,
+            major_radius,
+            minor_radius,
+            local_bbox,
+            bbox,
+            shader,
+            name,
+        }
+    }
+}
+
+impl Shape for Torus {
+    fn get_type(&self) -> ShapeType {
+        ShapeType::Torus
+    }
+
+    fn get_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_bbox(&self) -> &BBox {
+        &self.bbox
+    }
+
+    fn get_centroid(&self) -> P3 {
+        self.frame.position
+    }
+
+    fn get_shader(&self) -> Arc<dyn Shader> {
+        Arc::clone(&self.shader)
+    }
+
+    fn closest_hit<'hit>(&'hit self, hit: &mut Hit<'hit>) -> bool {
+        let local_ray = Ray {
+            origin: self.frame.to_local(hit.ray.origin),
+            direction: self.frame.vector_to_local(&hit.ray.direction),
+            time: hit.ray.time,
+        };
+        let Some(t_start) = self.local_bbox.hit(&local_ray, hit.t_min, hit.t) else {
+            return false;
+        };
+
+        // the quartic is much better conditioned with a unit direction starting near the torus
+        let length = local_ray.direction.norm();
+        let d = local_ray.direction / length;
+        let o = local_ray.point_at(t_start);
+
+        let r2 = self.major_radius * self.major_radius;
+        let k = o.coords.norm_squared() + r2 - self.minor_radius * self.minor_radius;
+        let od = o.coords.dot(&d);
+        let coefficients = [
+            1.0,
+            4.0 * od,
+            4.0 * od * od + 2.0 * k - 4.0 * r2 * (d.x * d.x + d.y * d.y),
+            4.0 * od * k - 8.0 * r2 * (o.x * d.x + o.y * d.y),
+            k * k - 4.0 * r2 * (o.x * o.x + o.y * o.y),
+        ];
+
+        let Some(t) = solve_quartic(coefficients)
+            .into_iter()
+            .map(|s| t_start + s / length)
+            .filter(|t| (hit.t_min..hit.t).contains(t))
+            .min_by(|a, b| a.total_cmp(b))
+        else {
+            return false;
+        };
+
+        let p = local_ray.point_at(t);
+        let ring_distance = (p.x * p.x + p.y * p.y).sqrt();
+        let ring_point = V3::new(p.x, p.y, 0.0) * (self.major_radius / ring_distance);
+        let normal = p.coords - ring_point;
+
+        hit.t = t;
+        hit.normal = Unit::new_normalize(self.frame.vector_to_global(&normal));
+        hit.uv = (
+            azimuth(p.x, p.y),
+            azimuth(ring_distance - self.major_radius, p.z),
+        );
+        hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::testing::{assert_hit, shader, trace};
+
+    #[test]
+    fn test_torus_intersection() {
+        let (right, up) = (V3::new(1.0, 0.0, 0.0), V3::new(0.0, 0.0, 1.0));
+        let torus = Torus::new(P3::origin(), up, 2.0, 0.5, shader(), "donut");
+        assert_hit(trace(&torus, P3::new(-5.0, 0.0, 0.0), right), 2.5, -right);
+        assert_hit(trace(&torus, P3::new(2.0, 0.0, 3.0), -up), 2.5, up);
+        assert!(trace(&torus, P3::new(0.0, 0.0, 5.0), -up).is_none());
+        assert!(trace(&torus, P3::new(-5.0, 0.0, 1.0), right).is_none());
+        // inside the tube the ray leaves through its inner side
+        assert_hit(trace(&torus, P3::new(-2.0, 0.0, 0.0), right), 0.5, right);
+    }
+}
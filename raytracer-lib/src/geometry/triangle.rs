@@ -12,6 +12,8 @@ pub struct Triangle {
     b: P3,
     c: P3,
     normal: V3,
+    /// Normals at `a`, `b` and `c` for smooth shading, the face normal is used without them
+    vertex_normals: Option<[V3; 3]>,
     bbox: BBox,
     shader: Arc<dyn Shader>,
     name: &'static str,
@@ -35,11 +37,27 @@ impl Triangle {
             b,
             c,
             normal,
+            vertex_normals: None,
             bbox: BBox::new(min, max),
             shader,
             name,
         }
     }
+
+    /// Triangle shaded with normals interpolated from `vertex_normals`
+    pub fn with_normals(
+        a: P3,
+        b: P3,
+        c: P3,
+        vertex_normals: [V3; 3],
+        shader: Arc<dyn Shader>,
+        name: &'static str,
+    ) -> Self {
+        Self {
+            vertex_normals: Some(vertex_normals),
+            ..Self::new(a, b, c, shader, name)
+        }
+    }
 }
 
 impl Shape for Triangle {
@@ -96,7 +114,7 @@ impl Shape for Triangle {
         let det_gamma = matrix_gamma.determinant();
         let gamma = det_gamma / det_a;
 
-        if gamma < 0.0 || gamma > 1.0 {
+        if !(0.0..=1.0).contains(&gamma) {
             return false;
         }
 
@@ -111,8 +129,15 @@ impl Shape for Triangle {
 
         // We have a valid hit, update the hit record
         hit.t = intersect_t;
-        hit.normal = Unit::new_unchecked(self.normal);
+        hit.normal = match &self.vertex_normals {
+            Some([na, nb, nc]) => {
+                Unit::new_normalize(na * (1.0 - beta - gamma) + nb * beta + nc * gamma)
+            }
+            None => Unit::new_unchecked(self.normal),
+        };
+        hit.uv = (beta, gamma);
         hit.shape = Some(self);
+        hit.shader = Some(self.shader.as_ref());
 
         true
     }
@@ -3,7 +3,9 @@ extern crate serde;
 
 use crate::prelude::*;
 
+mod accumulation;
 mod antialias;
+mod aov;
 mod camera;
 mod framebuffer;
 mod geometry;
@@ -12,12 +14,19 @@ mod math;
 mod prelude;
 mod render;
 mod scene;
+mod settings;
 mod shader;
+mod texture;
+mod tonemap;
 
+pub use accumulation::AccumulationBuffer;
 pub use antialias::AntialiasMethod;
+pub use aov::{Aov, RenderedImage};
 pub use framebuffer::Framebuffer;
 pub use prelude::public_consts;
 pub use prelude::Real;
-pub use render::{render, render_mut, render_pixel};
+pub use render::{render, render_mut, render_pass, render_pixel, render_sample, render_views};
 pub use scene::parse_scene;
 pub use scene::Scene;
+pub use settings::RenderSettings;
+pub use tonemap::{DisplayTransform, ToneMapping, TransferFunction};
@@ -28,11 +28,14 @@ impl Light for PointLight {
     }
 
     fn illuminates(&self, hit: &Hit) -> Option<V3> {
-        let surface_to_light = Ray::atob(hit.hit_point(), self.get_position());
-        let mut shadow_hit = Hit::to_light(surface_to_light, &hit.scene);
+        let surface_to_light = Ray {
+            time: hit.ray.time,
+            ..Ray::atob(hit.hit_point(), self.get_position())
+        };
+        let mut shadow_hit = Hit::to_light(surface_to_light, hit.scene);
 
         // if shadows are enabled and a shape blocks the light
-        if !hit.scene.disable_shadows && hit.scene.bvh.closest_hit(&mut shadow_hit) {
+        if !hit.scene.settings.disable_shadows() && hit.scene.bvh.closest_hit(&mut shadow_hit) {
             return None;
         }
 
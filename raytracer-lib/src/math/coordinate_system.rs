@@ -1,6 +1,8 @@
+use na::{Matrix3, Matrix4, Rotation3, UnitQuaternion};
+
 use crate::{prelude::*, V3};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CoordinateSystem {
     pub u: V3,
     pub v: V3,
@@ -10,11 +12,17 @@ pub struct CoordinateSystem {
 
 impl CoordinateSystem {
     pub fn new(position: P3, view_direction: &V3) -> Self {
+        Self::oriented(position, view_direction, &V3::y(), 0.0)
+    }
+
+    /// Camera frame looking along `view_direction`, with `v` as close to `up` as it can be.
+    /// `roll` then turns the frame counterclockwise around the view direction, in radians.
+    pub fn oriented(position: P3, view_direction: &V3, up: &V3, roll: Real) -> Self {
         let w = -view_direction.normalize();
-        let mut temp_up = V3::new(0.0, 1.0, 0.0);
+        let mut temp_up = up.normalize();
         let tdotw = temp_up.dot(&w);
         if tdotw.abs() > 0.999 {
-            temp_up = w.clone();
+            temp_up = w;
             let x = temp_up.x.abs();
             let y = temp_up.y.abs();
             let z = temp_up.z.abs();
@@ -26,26 +34,72 @@ impl CoordinateSystem {
                 temp_up.z = 1.0;
             }
         }
-        let u = temp_up.cross(&w);
-        let v = w.cross(&u);
+        let u = temp_up.cross(&w).normalize();
+        let v = w.cross(&u).normalize();
 
-        // let (u, v) = create_coordinate_system(&w);
+        let (sin, cos) = roll.sin_cos();
         Self {
-            u: u.normalize(),
-            v: v.normalize(),
+            u: u * cos + v * sin,
+            v: v * cos - u * sin,
             w,
             position,
         }
     }
 
-    fn to_local(&self, global: P3) -> P3 {
+    /// Right handed frame at `position` whose `w` axis is `axis`, for placing shapes
+    pub fn from_axis(position: P3, axis: &V3) -> Self {
+        let w = axis.normalize();
+        let (u, v) = create_coordinate_system(&w);
+        Self { u, v, w, position }
+    }
+
+    pub fn to_local(&self, global: P3) -> P3 {
         let temp = global - self.position;
 
         P3::new(self.u.dot(&temp), self.v.dot(&temp), self.w.dot(&temp))
     }
 
-    fn to_global(&self, local: V3) -> P3 {
-        self.position + V3::new(self.u.dot(&local), self.v.dot(&local), self.w.dot(&local))
+    pub fn to_global(&self, local: P3) -> P3 {
+        self.position + self.vector_to_global(&local.coords)
+    }
+
+    pub fn vector_to_local(&self, global: &V3) -> V3 {
+        V3::new(self.u.dot(global), self.v.dot(global), self.w.dot(global))
+    }
+
+    pub fn vector_to_global(&self, local: &V3) -> V3 {
+        self.u * local.x + self.v * local.y + self.w * local.z
+    }
+
+    /// Frame part way from `self` to `other`, turning along the shortest arc
+    pub fn interpolate(&self, other: &Self, s: Real) -> Self {
+        let rotation = |frame: &Self| {
+            UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(
+                Matrix3::from_columns(&[frame.u, frame.v, frame.w]),
+            ))
+        };
+        let (from, to) = (rotation(self), rotation(other));
+        let rotation = from
+            .try_slerp(&to, s, 1e-9)
+            .unwrap_or(if s < 0.5 { from } else { to })
+            .to_rotation_matrix();
+        let axes = rotation.matrix();
+        Self {
+            u: axes.column(0).into_owned(),
+            v: axes.column(1).into_owned(),
+            w: axes.column(2).into_owned(),
+            position: self.position + (other.position - self.position) * s,
+        }
+    }
+
+    /// Homogeneous matrix taking local points to global points
+    pub fn to_homogeneous(&self) -> Matrix4<Real> {
+        Matrix4::from_columns(&[
+            self.u.push(0.0),
+            self.v.push(0.0),
+            self.w.push(0.0),
+            self.position.coords.push(1.0),
+        ])
     }
 }
 
@@ -0,0 +1,89 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::prelude::*;
+
+/// How values are interpolated between keyframes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Smooth curve through every keyframe, with tangents from the keyframes on either side
+    CatmullRom,
+}
+
+/// Values at points in time, interpolated between them. Before the first and after the last
+/// keyframe the value holds still.
+#[derive(Debug, Clone)]
+pub struct Keyframes<T> {
+    /// Sorted by time
+    keyframes: Vec<(Real, T)>,
+    interpolation: Interpolation,
+}
+
+impl<T> Keyframes<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Real, Output = T>,
+{
+    /// `keyframes` are `(time, value)` pairs in any order, there must be at least one
+    pub fn new(mut keyframes: Vec<(Real, T)>, interpolation: Interpolation) -> Self {
+        assert!(!keyframes.is_empty(), "animation needs a keyframe");
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            keyframes,
+            interpolation,
+        }
+    }
+
+    pub fn at(&self, time: Real) -> T {
+        let keyframes = &self.keyframes;
+        let next = keyframes.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return keyframes[0].1;
+        }
+        if next == keyframes.len() {
+            return keyframes[next - 1].1;
+        }
+
+        let (t0, p0) = keyframes[next - 1];
+        let (t1, p1) = keyframes[next];
+        let span = t1 - t0;
+        let s = (time - t0) / span;
+        match self.interpolation {
+            Interpolation::Linear => p0 * (1.0 - s) + p1 * s,
+            Interpolation::CatmullRom => {
+                // tangents scaled to the span, so unevenly spaced keyframes don't overshoot
+                let tangent = |i: usize| {
+                    let before = keyframes[i.saturating_sub(1)];
+                    let after = keyframes[(i + 1).min(keyframes.len() - 1)];
+                    (after.1 - before.1) * (span / (after.0 - before.0))
+                };
+                let (m0, m1) = (tangent(next - 1), tangent(next));
+                let (s2, s3) = (s * s, s * s * s);
+                p0 * (2.0 * s3 - 3.0 * s2 + 1.0)
+                    + m0 * (s3 - 2.0 * s2 + s)
+                    + p1 * (-2.0 * s3 + 3.0 * s2)
+                    + m1 * (s3 - s2)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyframes_pass_through_every_keyframe() {
+        let points = vec![(0.0, 0.0), (1.0, 2.0), (3.0, 1.0), (4.0, 5.0)];
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let keyframes = Keyframes::new(points.clone(), interpolation);
+            for (time, value) in &points {
+                assert!((keyframes.at(*time) - value).abs() < 1e-6);
+            }
+            assert_eq!(keyframes.at(-1.0), 0.0);
+            assert_eq!(keyframes.at(10.0), 5.0);
+        }
+        let linear = Keyframes::new(points.clone(), Interpolation::Linear);
+        assert!((linear.at(2.0) - 1.5).abs() < 1e-6);
+    }
+}
@@ -1,5 +1,11 @@
 mod coordinate_system;
+mod keyframes;
+mod polynomial;
 mod ray;
+mod transform;
 
 pub use self::coordinate_system::{create_coordinate_system, CoordinateSystem};
+pub use self::keyframes::{Interpolation, Keyframes};
+pub use self::polynomial::{solve_quadratic, solve_quartic};
 pub use self::ray::Ray;
+pub use self::transform::{AnimatedTransform, Transform, TransformOp};
@@ -0,0 +1,124 @@
+use crate::prelude::*;
+
+/// Real roots of `a x^2 + b x + c`, smallest first
+pub fn solve_quadratic(a: Real, b: Real, c: Real) -> Option<(Real, Real)> {
+    if a.abs() < Real::EPSILON {
+        if b.abs() < Real::EPSILON {
+            return None;
+        }
+        let x = -c / b;
+        return Some((x, x));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // avoids the cancellation in the textbook formula
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x0, x1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((x0.min(x1), x0.max(x1)))
+}
+
+/// Largest real root of the monic cubic `x^3 + a x^2 + b x + c`
+fn largest_cubic_root(a: Real, b: Real, c: Real) -> Real {
+    // depressed cubic t^3 + p t + q with x = t - a / 3
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let offset = -a / 3.0;
+
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    if discriminant > 0.0 {
+        // one real root
+        let sqrt_d = discriminant.sqrt();
+        (-q / 2.0 + sqrt_d).cbrt() + (-q / 2.0 - sqrt_d).cbrt() + offset
+    } else if p.abs() < Real::EPSILON {
+        offset
+    } else {
+        // three real roots, the largest is k = 0
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let theta = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        m * theta.cos() + offset
+    }
+}
+
+/// Real roots of `c[0] x^4 + c[1] x^3 + c[2] x^2 + c[3] x + c[4]` using Ferrari's method,
+/// polished with a couple of Newton steps
+pub fn solve_quartic(coefficients: [Real; 5]) -> Vec<Real> {
+    let [c4, c3, c2, c1, c0] = coefficients;
+    if c4.abs() < Real::EPSILON {
+        return Vec::new();
+    }
+    let (a, b, c, d) = (c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+
+    // depressed quartic y^4 + p y^2 + q y + r with x = y - a / 4
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+    let offset = -a / 4.0;
+
+    let mut roots = Vec::with_capacity(4);
+    if q.abs() < VERY_SMALL_NUMBER {
+        // biquadratic
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1] {
+                if z >= 0.0 {
+                    roots.push(z.sqrt() + offset);
+                    roots.push(-z.sqrt() + offset);
+                }
+            }
+        }
+    } else {
+        // resolvent cubic always has a positive root when q != 0
+        let z = largest_cubic_root(2.0 * p, p * p - 4.0 * r, -q * q).max(0.0);
+        let s = z.sqrt();
+        for (linear, constant) in [
+            (s, (p + z) / 2.0 - q / (2.0 * s)),
+            (-s, (p + z) / 2.0 + q / (2.0 * s)),
+        ] {
+            if let Some((y0, y1)) = solve_quadratic(1.0, linear, constant) {
+                roots.push(y0 + offset);
+                roots.push(y1 + offset);
+            }
+        }
+    }
+
+    for root in roots.iter_mut() {
+        for _ in 0..2 {
+            let x = *root;
+            let f = (((x + a) * x + b) * x + c) * x + d;
+            let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+            if df.abs() > Real::EPSILON {
+                *root = x - f / df;
+            }
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_quartic() {
+        // (x - 1)(x - 2)(x + 3)(x - 0.5)
+        let mut roots = solve_quartic([1.0, -0.5, -7.0, 9.5, -3.0]);
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let expected = [-3.0, 0.5, 1.0, 2.0];
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "{} != {}", root, expected);
+        }
+
+        // x^4 + 1 has no real roots
+        assert!(solve_quartic([1.0, 0.0, 0.0, 0.0, 1.0]).is_empty());
+
+        // (x^2 - 4)(x^2 + 1)
+        let roots = solve_quartic([1.0, 0.0, -3.0, 0.0, -4.0]);
+        assert_eq!(roots.len(), 2);
+        assert!(roots.iter().all(|root| (root.abs() - 2.0).abs() < 1e-9));
+    }
+}
@@ -1,10 +1,11 @@
-
 use crate::prelude::*;
 
 #[derive(Clone, Copy, Default)]
 pub struct Ray {
     pub origin: P3,
     pub direction: V3,
+    /// When the ray is traced, within the shutter interval, for shapes and cameras that move
+    pub time: Real,
 }
 
 impl Ray {
@@ -16,6 +17,7 @@ impl Ray {
         Self {
             origin: a,
             direction: b - a,
+            time: 0.0,
         }
     }
 }
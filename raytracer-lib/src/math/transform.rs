@@ -0,0 +1,205 @@
+use std::borrow::Cow;
+
+use na::{Matrix3, Matrix4, Rotation3, Translation3, Unit, UnitQuaternion};
+
+use crate::prelude::*;
+
+/// A single step of a transform stack
+#[derive(Debug, Clone)]
+pub enum TransformOp {
+    Translate(V3),
+    Scale(V3),
+    Rotate { axis: Unit<V3>, angle: Real },
+    Matrix(Matrix4<Real>),
+}
+
+impl TransformOp {
+    pub fn to_homogeneous(&self) -> Matrix4<Real> {
+        match self {
+            TransformOp::Translate(amount) => Translation3::from(*amount).to_homogeneous(),
+            TransformOp::Scale(amount) => Matrix4::new_nonuniform_scaling(amount),
+            TransformOp::Rotate { axis, angle } => {
+                Rotation3::from_axis_angle(axis, *angle).to_homogeneous()
+            }
+            TransformOp::Matrix(matrix) => *matrix,
+        }
+    }
+}
+
+/// An invertible affine transform along with the matrices needed to apply it to rays and
+/// normals
+#[derive(Debug, Clone)]
+pub struct Transform {
+    pub matrix: Matrix4<Real>,
+    pub inverse: Matrix4<Real>,
+    pub normal_matrix: Matrix3<Real>,
+}
+
+impl Transform {
+    /// Returns `None` if `matrix` is not invertible
+    pub fn new(matrix: Matrix4<Real>) -> Option<Self> {
+        let inverse = matrix.try_inverse()?;
+        let normal_matrix = inverse.fixed_view::<3, 3>(0, 0).transpose();
+        Some(Self {
+            matrix,
+            inverse,
+            normal_matrix,
+        })
+    }
+
+    /// Composes a transform stack, the first op is applied to the object first
+    pub fn from_ops(ops: &[TransformOp]) -> Option<Self> {
+        Self::new(
+            ops.iter()
+                .fold(Matrix4::identity(), |acc, op| op.to_homogeneous() * acc),
+        )
+    }
+}
+
+/// Transform that changes over time, interpolated between keyframes. Keyframes are split into
+/// a translation, a rotation and a stretch, so rotations stay rotations in between instead of
+/// shearing.
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    /// Sorted by time
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(Debug, Clone)]
+struct Keyframe {
+    time: Real,
+    transform: Transform,
+    translation: V3,
+    rotation: UnitQuaternion<Real>,
+    stretch: Matrix3<Real>,
+}
+
+impl AnimatedTransform {
+    /// `keyframes` are `(time, transform)` pairs in any order, there must be at least one.
+    /// Before the first and after the last keyframe the transform holds still.
+    pub fn new(mut keyframes: Vec<(Real, Transform)>) -> Self {
+        assert!(!keyframes.is_empty(), "animated transform needs a keyframe");
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let keyframes = keyframes
+            .into_iter()
+            .map(|(time, transform)| {
+                // polar decomposition, the linear part is a rotation followed by a stretch
+                let linear = transform.matrix.fixed_view::<3, 3>(0, 0).into_owned();
+                let svd = linear.svd(true, true);
+                let mut rotation = svd.u.unwrap() * svd.v_t.unwrap();
+                if rotation.determinant() < 0.0 {
+                    // mirroring is left to the stretch
+                    rotation = -rotation;
+                }
+                Keyframe {
+                    time,
+                    translation: transform.matrix.fixed_view::<3, 1>(0, 3).into_owned(),
+                    rotation: UnitQuaternion::from_rotation_matrix(
+                        &Rotation3::from_matrix_unchecked(rotation),
+                    ),
+                    stretch: rotation.transpose() * linear,
+                    transform,
+                }
+            })
+            .collect();
+        Self { keyframes }
+    }
+
+    /// Transform that doesn't move
+    pub fn fixed(transform: Transform) -> Self {
+        Self::new(vec![(0.0, transform)])
+    }
+
+    pub fn keyframe_times(&self) -> impl Iterator<Item = Real> + '_ {
+        self.keyframes.iter().map(|keyframe| keyframe.time)
+    }
+
+    pub fn at(&self, time: Real) -> Cow<'_, Transform> {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return Cow::Borrowed(&self.keyframes[0].transform);
+        }
+        if next == self.keyframes.len() {
+            return Cow::Borrowed(&self.keyframes[next - 1].transform);
+        }
+
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let s = (time - a.time) / (b.time - a.time);
+        let rotation = a
+            .rotation
+            .try_slerp(&b.rotation, s, 1e-9)
+            .unwrap_or(if s < 0.5 { a.rotation } else { b.rotation });
+        let linear =
+            rotation.to_rotation_matrix().into_inner() * (a.stretch * (1.0 - s) + b.stretch * s);
+        let mut matrix = linear.to_homogeneous();
+        matrix
+            .fixed_view_mut::<3, 1>(0, 3)
+            .copy_from(&a.translation.lerp(&b.translation, s));
+
+        // only a keyframe that mirrors next to one that doesn't passes through a flat stretch
+        match Transform::new(matrix) {
+            Some(transform) => Cow::Owned(transform),
+            None => Cow::Borrowed(if s < 0.5 { &a.transform } else { &b.transform }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_stack_order() {
+        // scale, then rotate a quarter turn about z, then translate
+        let transform = Transform::from_ops(&[
+            TransformOp::Scale(V3::new(2.0, 1.0, 1.0)),
+            TransformOp::Rotate {
+                axis: V3::z_axis(),
+                angle: PI / 2.0,
+            },
+            TransformOp::Translate(V3::new(0.0, 0.0, 3.0)),
+        ])
+        .unwrap();
+
+        let point = transform.matrix.transform_point(&P3::new(1.0, 0.0, 0.0));
+        assert!((point - P3::new(0.0, 2.0, 3.0)).norm() < 1e-9);
+
+        let back = transform.inverse.transform_point(&point);
+        assert!((back - P3::new(1.0, 0.0, 0.0)).norm() < 1e-9);
+
+        // the normal of the plane x = 1 is stretched less than the plane itself
+        let normal = (transform.normal_matrix * V3::x()).normalize();
+        assert!((normal - V3::y()).norm() < 1e-9);
+
+        assert!(Transform::from_ops(&[TransformOp::Scale(V3::new(0.0, 1.0, 1.0))]).is_none());
+    }
+
+    #[test]
+    fn test_animated_transform_rotates_between_keyframes() {
+        let start = Transform::from_ops(&[TransformOp::Scale(V3::new(2.0, 2.0, 2.0))]).unwrap();
+        let end = Transform::from_ops(&[
+            TransformOp::Scale(V3::new(2.0, 2.0, 2.0)),
+            TransformOp::Rotate {
+                axis: V3::z_axis(),
+                angle: PI / 2.0,
+            },
+            TransformOp::Translate(V3::new(0.0, 0.0, 4.0)),
+        ])
+        .unwrap();
+        let animated = AnimatedTransform::new(vec![(1.0, end), (0.0, start)]);
+
+        // halfway the point has turned an eighth of a turn without shrinking
+        let point = animated
+            .at(0.5)
+            .matrix
+            .transform_point(&P3::new(1.0, 0.0, 0.0));
+        let expected = P3::new(2.0 * (PI / 4.0).cos(), 2.0 * (PI / 4.0).sin(), 2.0);
+        assert!((point - expected).norm() < 1e-9);
+
+        // and holds still outside of the keyframes
+        let after = animated.at(3.0).matrix.transform_point(&P3::origin());
+        assert!((after - P3::new(0.0, 0.0, 4.0)).norm() < 1e-9);
+    }
+}
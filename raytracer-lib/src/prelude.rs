@@ -49,11 +49,16 @@ mod constants {
 }
 
 pub mod public_consts {
-    use crate::AntialiasMethod;
+    use super::Real;
+    use crate::{AntialiasMethod, ToneMapping, TransferFunction};
 
     pub static DEFAULT_IMAGE_WIDTH: u32 = 360;
     pub static DEFAULT_IMAGE_HEIGHT: u32 = 360;
     pub static DEFAULT_RAYS_PER_PIXEL: u16 = 4;
     pub static DEFAULT_RECURSION_DEPTH: u16 = 3;
     pub static DEFAULT_ANTIALIAS_METHOD: AntialiasMethod = AntialiasMethod::Normal;
+    pub static DEFAULT_SHUTTER_OPEN: Real = 0.0;
+    pub static DEFAULT_SHUTTER_CLOSE: Real = 1.0;
+    pub static DEFAULT_TONE_MAPPING: ToneMapping = ToneMapping::Clamp;
+    pub static DEFAULT_TRANSFER_FUNCTION: TransferFunction = TransferFunction::Srgb;
 }
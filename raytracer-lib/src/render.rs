@@ -1,51 +1,30 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::accumulation::AccumulationBuffer;
 use crate::antialias::{antialias, AntialiasMethod};
+use crate::aov::{Aov, RenderedImage};
+use crate::camera::{Camera, StereoLayout, View};
 use crate::scene::Scene;
-use crate::shader::Hit;
+use crate::shader::{Hit, NormalShader, Shader};
 use crate::Framebuffer;
 use crate::{color, prelude::*};
+use rand::Rng;
 
-pub fn render(
-    scene: &Scene,
-    sqrt_rays_per_pixel: u16,
-    antialias_method: AntialiasMethod,
-    per_pixel_cb: Option<&dyn Fn() -> ()>,
-) -> Framebuffer {
-    let mut fb = Framebuffer::new(scene.image_width, scene.image_width);
-    render_mut(
-        &mut fb,
-        scene,
-        sqrt_rays_per_pixel,
-        antialias_method,
-        per_pixel_cb,
-        None,
-    );
+/// Renders the scene from its main camera, see [`Scene::camera`]
+pub fn render(scene: &Scene, per_pixel_cb: Option<&dyn Fn()>) -> Framebuffer {
+    let mut fb = Framebuffer::new(scene.settings.image_width(), scene.settings.image_height());
+    render_mut(&mut fb, scene, per_pixel_cb);
     fb
 }
 
-pub fn render_mut(
-    fb: &mut Framebuffer,
-    scene: &Scene,
-    sqrt_rays_per_pixel: u16,
-    antialias_method: AntialiasMethod,
-    per_pixel_cb: Option<&dyn Fn() -> ()>,
-    wasm_log: Option<&dyn Fn(&str) -> ()>,
-) {
-    let width = scene.image_width;
-    let height = scene.image_height;
+pub fn render_mut(fb: &mut Framebuffer, scene: &Scene, per_pixel_cb: Option<&dyn Fn()>) {
+    let width = scene.settings.image_width();
+    let height = scene.settings.image_height();
 
     for i in 0..width {
         for j in 0..height {
-            render_pixel(
-                fb,
-                scene,
-                sqrt_rays_per_pixel,
-                antialias_method,
-                i,
-                j,
-                per_pixel_cb,
-                wasm_log,
-            )
-            // wasm_log(&format!("On pixel {} {}", i, j));
+            render_pixel(fb, scene, i, j, per_pixel_cb)
         }
     }
 }
@@ -53,32 +32,432 @@ pub fn render_mut(
 pub fn render_pixel(
     fb: &mut Framebuffer,
     scene: &Scene,
-    sqrt_rays_per_pixel: u16,
-    antialias_method: AntialiasMethod,
     i: u32,
     j: u32,
-    per_pixel_cb: Option<&dyn Fn() -> ()>,
-    wasm_log: Option<&dyn Fn(&str) -> ()>,
+    per_pixel_cb: Option<&dyn Fn()>,
 ) {
+    let pixel = trace_pixel(scene, scene.camera(), i, j, &AovContext::default());
+
+    if let Some(cb) = per_pixel_cb {
+        cb();
+    }
+    fb.set_pixel(i, j, pixel.color);
+    fb.set_alpha(i, j, pixel.alpha);
+}
+
+/// Adds one sample to every pixel of the main camera's image, see [`render_sample`]
+pub fn render_pass(
+    accumulation: &mut AccumulationBuffer,
+    scene: &Scene,
+    per_pixel_cb: Option<&dyn Fn()>,
+) {
+    for i in 0..accumulation.width {
+        for j in 0..accumulation.height {
+            render_sample(accumulation, scene, i, j);
+            if let Some(cb) = per_pixel_cb {
+                cb();
+            }
+        }
+    }
+}
+
+/// Adds the next sample of pixel `(i, j)` of the main camera to `accumulation`. The `n`th
+/// sample of a pixel is its `n`th antialiasing ray, so once every pixel has `rays_per_pixel`
+/// samples the image is the one [`render`] makes. Samples after that are at random positions.
+pub fn render_sample(accumulation: &mut AccumulationBuffer, scene: &Scene, i: u32, j: u32) {
+    let sqrt_rays_per_pixel = scene.settings.sqrt_rays_per_pixel();
+    let n = accumulation.sample_count(i, j);
+    let sqrt = sqrt_rays_per_pixel as u32;
+    let position = if n < sqrt * sqrt {
+        let (p, q) = ((n / sqrt) as u16, (n % sqrt) as u16);
+        antialias(scene.settings.antialias_method(), sqrt_rays_per_pixel, p, q)
+    } else {
+        antialias(AntialiasMethod::Random, sqrt_rays_per_pixel, 0, 0)
+    };
+    let (color, hit) = trace_sample(
+        scene,
+        scene.camera(),
+        i,
+        j,
+        position,
+        &AovContext::default(),
+        &mut PixelSamples::new(),
+    );
+    accumulation.add_sample(i, j, color, hit);
+}
+
+/// Renders every view of the scene, all of them sharing its BVH, along with the AOVs asked
+/// for in its settings. Each image is named as in [`View::images`].
+pub fn render_views(scene: &Scene, per_pixel_cb: Option<&dyn Fn()>) -> Vec<RenderedImage> {
+    let width = scene.settings.image_width();
+    let height = scene.settings.image_height();
+    let aovs = AovContext::new(scene);
+
+    let mut images = Vec::new();
+    for view in &scene.views {
+        let names = view.images(width, height).into_iter();
+        let buffers = match view {
+            View::Mono { camera, .. } => {
+                vec![render_image(width, height, &aovs, per_pixel_cb, |i, j| {
+                    trace_pixel(scene, camera.as_ref(), i, j, &aovs)
+                })]
+            }
+            View::Stereo {
+                left,
+                right,
+                layout,
+                ..
+            } => match layout {
+                StereoLayout::Separate => [left, right]
+                    .into_iter()
+                    .map(|eye| {
+                        render_image(width, height, &aovs, per_pixel_cb, |i, j| {
+                            trace_pixel(scene, eye.as_ref(), i, j, &aovs)
+                        })
+                    })
+                    .collect(),
+                StereoLayout::SideBySide => {
+                    vec![render_image(
+                        2 * width,
+                        height,
+                        &aovs,
+                        per_pixel_cb,
+                        |i, j| {
+                            let eye = if i < width { left } else { right };
+                            trace_pixel(scene, eye.as_ref(), i % width, j, &aovs)
+                        },
+                    )]
+                }
+                StereoLayout::Anaglyph => {
+                    // the alpha and AOVs are the left eye's
+                    vec![render_image(width, height, &aovs, per_pixel_cb, |i, j| {
+                        let left = trace_pixel(scene, left.as_ref(), i, j, &aovs);
+                        let right =
+                            trace_pixel(scene, right.as_ref(), i, j, &AovContext::default());
+                        PixelValue {
+                            color: color!(left.color.x, right.color.y, right.color.z),
+                            ..left
+                        }
+                    })]
+                }
+            },
+        };
+        images.extend(
+            names
+                .zip(buffers)
+                .map(|((name, ..), (framebuffer, aov_buffers))| RenderedImage {
+                    name,
+                    framebuffer,
+                    aovs: aovs.aovs.iter().copied().zip(aov_buffers).collect(),
+                }),
+        );
+    }
+    images
+}
+
+/// Image of the given size and its AOVs, with each pixel colored by `pixel`
+fn render_image(
+    width: u32,
+    height: u32,
+    aovs: &AovContext,
+    per_pixel_cb: Option<&dyn Fn()>,
+    pixel: impl Fn(u32, u32) -> PixelValue,
+) -> (Framebuffer, Vec<Framebuffer>) {
+    let mut fb = Framebuffer::new(width, height);
+    let mut aov_buffers: Vec<Framebuffer> = aovs
+        .aovs
+        .iter()
+        .map(|_| Framebuffer::new(width, height))
+        .collect();
+    for i in 0..width {
+        for j in 0..height {
+            let pixel = pixel(i, j);
+            fb.set_pixel(i, j, pixel.color);
+            fb.set_alpha(i, j, pixel.alpha);
+            for (aov_fb, value) in aov_buffers.iter_mut().zip(pixel.aovs) {
+                aov_fb.set_pixel(i, j, value);
+            }
+            if let Some(cb) = per_pixel_cb {
+                cb();
+            }
+        }
+    }
+    (fb, aov_buffers)
+}
+
+/// AOVs to render, with the lookups from what was hit to the values of the ID AOVs
+#[derive(Default)]
+struct AovContext<'a> {
+    aovs: &'a [Aov],
+    shape_ids: HashMap<String, u32>,
+    /// Keyed by the address of the shader
+    shader_ids: HashMap<*const (), u32>,
+}
+
+impl<'a> AovContext<'a> {
+    fn new(scene: &'a Scene) -> Self {
+        let aovs = scene.settings.aovs();
+        if aovs.is_empty() {
+            return Self::default();
+        }
+        let ids = |names: Vec<String>| names.into_iter().zip(1..);
+        let shape_ids = ids(scene.shape_names()).collect();
+        let shader_ids = ids(scene.shader_names())
+            .map(|(name, id)| (Arc::as_ptr(&scene.shaders[&name]) as *const (), id))
+            .collect();
+        Self {
+            aovs,
+            shape_ids,
+            shader_ids,
+        }
+    }
+}
+
+/// What the rays through a pixel saw, averaged
+struct PixelValue {
+    color: Color,
+    /// Share of the rays that hit something
+    alpha: f32,
+    aovs: Vec<Color>,
+}
+
+/// Sums of what the rays through a pixel saw
+struct PixelSamples {
+    depth: f32,
+    normal: Color,
+    albedo: Color,
+    direct: Color,
+    indirect: Color,
+    shape_ids: Vec<u32>,
+    shader_ids: Vec<u32>,
+}
+
+impl PixelSamples {
+    fn new() -> Self {
+        Self {
+            depth: f32::INFINITY,
+            normal: Color::zeros(),
+            albedo: Color::zeros(),
+            direct: Color::zeros(),
+            indirect: Color::zeros(),
+            shape_ids: Vec::new(),
+            shader_ids: Vec::new(),
+        }
+    }
+
+    /// Value of `aov` for the pixel, out of `samples` rays
+    fn value(&self, aov: Aov, samples: f32) -> Color {
+        let id = |ids: &[u32]| {
+            // the ID most rays saw, including the background's 0 for rays that missed
+            let mut counts: HashMap<u32, usize> = HashMap::new();
+            for id in ids {
+                *counts.entry(*id).or_default() += 1;
+            }
+            counts.insert(
+                0,
+                counts.get(&0).copied().unwrap_or(0) + samples as usize - ids.len(),
+            );
+            let (id, _) = counts
+                .into_iter()
+                .max_by_key(|(id, count)| (*count, std::cmp::Reverse(*id)))
+                .unwrap_or_default();
+            id as f32
+        };
+        match aov {
+            Aov::Depth => color!(self.depth, self.depth, self.depth),
+            Aov::Normal => self.normal / samples,
+            Aov::Albedo => self.albedo / samples,
+            Aov::ShapeId => Color::repeat(id(&self.shape_ids)),
+            Aov::ShaderId => Color::repeat(id(&self.shader_ids)),
+            Aov::Direct => self.direct / samples,
+            Aov::Indirect => self.indirect / samples,
+        }
+    }
+}
+
+/// Average color of the rays through pixel `(i, j)` of `camera`, its coverage and the values of
+/// `aovs`
+fn trace_pixel(
+    scene: &Scene,
+    camera: &dyn Camera,
+    i: u32,
+    j: u32,
+    aovs: &AovContext,
+) -> PixelValue {
+    let sqrt_rays_per_pixel = scene.settings.sqrt_rays_per_pixel();
+    let antialias_method = scene.settings.antialias_method();
+
     let mut color = color!(0.0, 0.0, 0.0);
+    let mut hits = 0;
+    let mut samples = PixelSamples::new();
     for p in 0..sqrt_rays_per_pixel {
         for q in 0..sqrt_rays_per_pixel {
-            let (di, dj) = antialias(antialias_method, sqrt_rays_per_pixel, p, q);
-            let ray = scene.camera.generate_ray(i, j, di, dj);
-            let mut hit = Hit::new(ray, &scene);
-
-            if scene.bvh.closest_hit(&mut hit) {
-                color += hit.shape.unwrap().get_shader().apply(&hit);
-            } else {
-                color += scene.background_color;
-            }
+            let position = antialias(antialias_method, sqrt_rays_per_pixel, p, q);
+            let (sample, hit) = trace_sample(scene, camera, i, j, position, aovs, &mut samples);
+            color += sample;
+            hits += hit as u32;
         }
     }
     // divide by number of samples
-    color /= (sqrt_rays_per_pixel * sqrt_rays_per_pixel) as f32;
+    let sample_count = (sqrt_rays_per_pixel * sqrt_rays_per_pixel) as f32;
+    color /= sample_count;
+    PixelValue {
+        color,
+        alpha: hits as f32 / sample_count,
+        aovs: aovs
+            .aovs
+            .iter()
+            .map(|aov| samples.value(*aov, sample_count))
+            .collect(),
+    }
+}
 
-    if let Some(cb) = per_pixel_cb {
-        cb();
+/// Color of the ray through `position` within pixel `(i, j)` of `camera` and whether it hit
+/// anything, adding what it saw to `samples` when there are `aovs`
+fn trace_sample(
+    scene: &Scene,
+    camera: &dyn Camera,
+    i: u32,
+    j: u32,
+    (di, dj): (Real, Real),
+    aovs: &AovContext,
+    samples: &mut PixelSamples,
+) -> (Color, bool) {
+    // samples the camera can't see through stay black
+    let Some(ray) = camera.generate_ray(i, j, di, dj, sample_time(scene)) else {
+        return (Color::zeros(), false);
+    };
+    let mut hit = Hit::new(ray, scene);
+
+    if !scene.bvh.closest_hit(&mut hit) {
+        let background = if scene.settings.transparent_background() {
+            Color::zeros()
+        } else {
+            scene.background_color
+        };
+        return (background, false);
+    }
+    let shader = if scene.settings.render_normals() {
+        &NormalShader
+    } else {
+        hit.shader.unwrap()
+    };
+    if aovs.aovs.is_empty() {
+        return (shader.apply(&hit), true);
+    }
+
+    let (direct, indirect) = shader.apply_direct_indirect(&hit);
+    samples.direct += direct;
+    samples.indirect += indirect;
+    samples.albedo += shader.albedo(&hit);
+    let normal = hit.normal.into_inner().cast::<f32>();
+    samples.normal += normal;
+    let depth = (hit.t * hit.ray.direction.norm()) as f32;
+    samples.depth = samples.depth.min(depth);
+    let shape_name = hit.shape.map(|shape| shape.get_name()).unwrap_or_default();
+    samples
+        .shape_ids
+        .push(aovs.shape_ids.get(shape_name).copied().unwrap_or(0));
+    let shader_address = shader as *const dyn Shader as *const ();
+    samples
+        .shader_ids
+        .push(aovs.shader_ids.get(&shader_address).copied().unwrap_or(0));
+    (direct + indirect, true)
+}
+
+/// Random moment while the shutter is open
+fn sample_time(scene: &Scene) -> Real {
+    let (open, close) = scene.settings.shutter();
+    if open == close {
+        return open;
+    }
+    open + (close - open) * rand::thread_rng().gen::<Real>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_scene, RenderSettings};
+
+    /// A mirror ball in front of a wall, which leaves the corners of the image to the
+    /// background. `settings` override the scene's.
+    fn test_scene(settings: RenderSettings) -> Scene {
+        let scene_json = r#"{"scene": {
+            "sceneParameters": {"width": 8, "height": 8, "raysPerPixel": 1, "bgColor": "1 1 1"},
+            "camera": [{"_name": "main", "_type": "perspective", "position": "0 0 4",
+                "lookatPoint": "0 0 0", "vfov": 30}],
+            "light": [{"_type": "point", "position": "0 4 4", "intensity": "1 1 1"}],
+            "shader": [{"_name": "mirror", "_type": "PerfectMirror"},
+                {"_name": "wall", "_type": "Lambertian", "diffuse": "0.5 0.5 0.5"}],
+            "shape": [{"_name": "wall", "_type": "disk", "_shader": "wall",
+                    "center": "0 0 -2", "normal": "0 0 1", "radius": 1.5},
+                {"_name": "ball", "_type": "sphere", "_shader": "mirror",
+                    "center": "0 0 0", "radius": 0.5}]
+        }}"#;
+        parse_scene(scene_json, "", &settings).unwrap()
+    }
+
+    #[test]
+    fn test_aovs_split_the_image() {
+        let scene = test_scene(RenderSettings {
+            aovs: Some(vec![Aov::Direct, Aov::Indirect, Aov::Depth, Aov::ShapeId]),
+            ..Default::default()
+        });
+        let image = render_views(&scene, None).remove(0);
+        let aov = |aov: Aov| &image.aovs.iter().find(|(a, _)| *a == aov).unwrap().1;
+
+        // the ball is in the middle of the wall, the corners are background
+        assert_eq!(aov(Aov::ShapeId).get_pixel(4, 4).x, 2.0);
+        assert_eq!(aov(Aov::ShapeId).get_pixel(1, 4).x, 1.0);
+        assert_eq!(aov(Aov::ShapeId).get_pixel(0, 0).x, 0.0);
+        assert!((aov(Aov::Depth).get_pixel(4, 4).x - 3.5).abs() < 0.1);
+        assert_eq!(aov(Aov::Direct).get_pixel(4, 4), Color::zeros());
+        assert_eq!(aov(Aov::Indirect).get_pixel(1, 4), Color::zeros());
+        for (k, pixel) in image.framebuffer.pixels.iter().enumerate() {
+            // the background is neither direct nor indirect light
+            if image.framebuffer.alpha[k] == 0.0 {
+                continue;
+            }
+            let split = aov(Aov::Direct).pixels[k][0] + aov(Aov::Indirect).pixels[k][0];
+            assert!((pixel[0] - split).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_transparent_background_has_coverage_alpha() {
+        let scene = test_scene(RenderSettings {
+            rays_per_pixel: Some(16),
+            transparent_background: Some(true),
+            ..Default::default()
+        });
+        let fb = render(&scene, None);
+
+        assert_eq!(fb.get_alpha(4, 4), 1.0);
+        assert_eq!(fb.get_alpha(0, 0), 0.0);
+        assert_eq!(fb.get_pixel(0, 0), Color::zeros());
+        // antialiased edges are partly covered
+        assert!(fb.alpha.iter().any(|alpha| *alpha > 0.0 && *alpha < 1.0));
+    }
+
+    #[test]
+    fn test_passes_add_up_to_the_render() {
+        let scene = test_scene(RenderSettings {
+            rays_per_pixel: Some(4),
+            ..Default::default()
+        });
+        let fb = render(&scene, None);
+
+        let mut accumulation = AccumulationBuffer::new(fb.width, fb.height);
+        for _ in 0..4 {
+            render_pass(&mut accumulation, &scene, None);
+        }
+        assert_eq!(accumulation.min_sample_count(), 4);
+        let resolved = accumulation.resolve();
+        for (pixel, expected) in resolved.pixels.iter().zip(&fb.pixels) {
+            for c in 0..3 {
+                assert!((pixel[c] - expected[c]).abs() < 1e-5);
+            }
+        }
+        assert_eq!(resolved.alpha, fb.alpha);
     }
-    fb.set_pixel(i, j, color);
 }
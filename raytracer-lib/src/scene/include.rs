@@ -0,0 +1,375 @@
+use std::{
+    collections::HashSet,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{CameraType, MeshData, SceneData, SceneModel, ShapeType};
+
+/// Separator between an include's namespace and the names defined in it, e.g. `props::chair`
+pub(super) static NAMESPACE_SEPARATOR: &str = "::";
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub(super) enum IncludeData {
+    Path(String),
+    Namespaced {
+        #[serde(alias = "file")]
+        path: String,
+        namespace: Option<String>,
+    },
+}
+
+impl IncludeData {
+    fn path(&self) -> &str {
+        match self {
+            IncludeData::Path(path) => path,
+            IncludeData::Namespaced { path, .. } => path,
+        }
+    }
+
+    fn namespace(&self) -> Option<&str> {
+        match self {
+            IncludeData::Path(_) => None,
+            IncludeData::Namespaced { namespace, .. } => namespace.as_deref(),
+        }
+    }
+}
+
+/// Merges the definitions of every file in `scene.include` into `scene`.
+///
+/// Includes are resolved relative to the file that includes them, starting from
+/// `scene_data_path` for the top level scene, and may themselves include other files. Only
+/// asset definitions are merged, an included file's `sceneParameters` are ignored.
+pub(super) fn resolve_includes(
+    scene: &mut SceneData,
+    scene_data_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stack = Vec::new();
+    merge_includes(scene, Path::new(scene_data_path), Path::new(""), &mut stack)
+}
+
+fn merge_includes(
+    scene: &mut SceneData,
+    scene_data_path: &Path,
+    relative_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for include in std::mem::take(&mut scene.include) {
+        let relative_path = relative_dir.join(include.path());
+        let path = scene_data_path.join(&relative_path);
+        let canonical_path = path.canonicalize().map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("failed to include {}: {}", path.display(), e),
+            )
+        })?;
+        if stack.contains(&canonical_path) {
+            return Err(Box::new(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} includes itself", path.display()),
+            )));
+        }
+
+        let json = std::fs::read_to_string(&path)?;
+        let mut included = serde_json::from_str::<SceneModel>(&json)
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("failed to parse {}: {}", path.display(), e),
+                )
+            })?
+            .scene;
+
+        // nested includes are relative to the file including them and rebase their own paths,
+        // so this file's paths are rebased before they are merged in
+        let included_dir = relative_path.parent().unwrap_or(Path::new(""));
+        included.rebase(included_dir);
+        stack.push(canonical_path);
+        merge_includes(&mut included, scene_data_path, included_dir, stack)?;
+        stack.pop();
+
+        if let Some(namespace) = include.namespace() {
+            included.namespace(namespace);
+        }
+
+        scene.merge(included).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} in {}", e, path.display()),
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+impl SceneData {
+    /// Makes file paths relative to the top level scene's data path
+    fn rebase(&mut self, relative_dir: &Path) {
+        let rebase = |path: &mut String| {
+            *path = relative_dir.join(&path).to_string_lossy().into_owned();
+        };
+
+        for shape in self.shapes.iter_mut().chain(self.instances.iter_mut()) {
+            shape.visit_mut(&mut |shape| match &mut shape.shape {
+                ShapeType::Mesh(mesh) => rebase(&mut mesh.model_path),
+                ShapeType::Heightfield(heightfield) => rebase(&mut heightfield.image_path),
+                ShapeType::PointCloud(cloud) => rebase(&mut cloud.point_path),
+                ShapeType::Curves(curves) => {
+                    if let Some(strand_path) = &mut curves.strand_path {
+                        rebase(strand_path)
+                    }
+                }
+                _ => (),
+            });
+        }
+        for texture in self.textures.iter_mut() {
+            rebase(&mut texture.image_path);
+        }
+        for camera in self.cameras.iter_mut() {
+            if let CameraType::Realistic(realistic) = &mut camera.camera_type {
+                rebase(&mut realistic.lens_path);
+            }
+        }
+    }
+
+    /// Prefixes every name defined in this scene with `namespace`, along with the references
+    /// to those names. References to names defined elsewhere are left alone.
+    fn namespace(&mut self, namespace: &str) {
+        let prefix = |name: &mut String| {
+            *name = format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, name);
+        };
+
+        let shader_names: HashSet<String> = self.shaders.iter().map(|s| s.name.clone()).collect();
+        let instance_names: HashSet<String> =
+            self.instances.iter().map(|s| s.name.clone()).collect();
+        let texture_names: HashSet<String> = self.textures.iter().map(|t| t.name.clone()).collect();
+
+        for shader in self.shaders.iter_mut() {
+            prefix(&mut shader.name);
+        }
+        for texture in self.textures.iter_mut() {
+            prefix(&mut texture.name);
+        }
+        for camera in self.cameras.iter_mut() {
+            prefix(&mut camera.name);
+        }
+        for shape in self.shapes.iter_mut().chain(self.instances.iter_mut()) {
+            prefix(&mut shape.name);
+            shape.visit_mut(&mut |shape| {
+                if let Some(shader) = &mut shape.shader {
+                    if shader_names.contains(shader.name()) {
+                        prefix(shader.name_mut());
+                    }
+                }
+                for reference in shape.prototype_references_mut() {
+                    if instance_names.contains(reference) {
+                        prefix(reference);
+                    }
+                }
+                if let ShapeType::Mesh(MeshData {
+                    displacement: Some(displacement),
+                    ..
+                }) = &mut shape.shape
+                {
+                    if texture_names.contains(&displacement.texture) {
+                        prefix(&mut displacement.texture);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Moves the definitions from `other` into this scene, failing on name collisions
+    fn merge(&mut self, other: SceneData) -> Result<(), String> {
+        fn check<'a>(
+            kind: &str,
+            existing: impl Iterator<Item = &'a String>,
+            incoming: impl Iterator<Item = &'a String>,
+        ) -> Result<(), String> {
+            let existing: HashSet<&String> = existing.collect();
+            for name in incoming {
+                if existing.contains(name) {
+                    return Err(format!(
+                        "{} name collision: '{}' is already defined",
+                        kind, name
+                    ));
+                }
+            }
+            Ok(())
+        }
+
+        check(
+            "shader",
+            self.shaders.iter().map(|s| &s.name),
+            other.shaders.iter().map(|s| &s.name),
+        )?;
+        check(
+            "shape",
+            self.shapes.iter().map(|s| &s.name),
+            other.shapes.iter().map(|s| &s.name),
+        )?;
+        check(
+            "instance",
+            self.instances.iter().map(|s| &s.name),
+            other.instances.iter().map(|s| &s.name),
+        )?;
+        check(
+            "texture",
+            self.textures.iter().map(|t| &t.name),
+            other.textures.iter().map(|t| &t.name),
+        )?;
+        check(
+            "camera",
+            self.cameras.iter().map(|c| &c.name),
+            other.cameras.iter().map(|c| &c.name),
+        )?;
+
+        self.cameras.extend(other.cameras);
+        self.lights.extend(other.lights);
+        self.shaders.extend(other.shaders);
+        self.shapes.extend(other.shapes);
+        self.textures.extend(other.textures);
+        self.instances.extend(other.instances);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::ShapeData;
+
+    /// Writes `files` into a fresh directory named after `test` and resolves the includes of
+    /// its `scene.json`
+    fn resolve(
+        test: &str,
+        files: &[(&str, &str)],
+    ) -> Result<SceneData, Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("raytracer_{}", test));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        let json = std::fs::read_to_string(dir.join("scene.json")).unwrap();
+        let mut scene = serde_json::from_str::<SceneModel>(&json).unwrap().scene;
+        let resolved = resolve_includes(&mut scene, &dir.to_string_lossy());
+        std::fs::remove_dir_all(&dir).unwrap();
+        resolved.map(|_| scene)
+    }
+
+    static WOOD: &str = r#"{"_name": "wood", "_type": "Lambertian", "diffuse": "1 1 1"}"#;
+
+    fn model_path(shape: &ShapeData) -> &str {
+        match &shape.shape {
+            ShapeType::Mesh(mesh) => &mesh.model_path,
+            _ => panic!("{} is not a mesh", shape.name),
+        }
+    }
+
+    #[test]
+    fn test_nested_includes() {
+        let scene = format!(
+            r#"{{"scene": {{
+                "include": [{{"path": "props/room.json", "namespace": "room"}}],
+                "shader": [{}],
+                "shape": [{{"_name": "ball", "_type": "sphere", "_shader": "wood",
+                    "center": "0 0 0", "radius": 1}}]
+            }}}}"#,
+            WOOD
+        );
+        let room = format!(
+            r#"{{"scene": {{
+                "include": [{{"path": "furniture/chair.json", "namespace": "chair"}}],
+                "shader": [{}],
+                "shape": [{{"_name": "table", "_type": "mesh", "_shader": "wood",
+                    "file": "table.obj"}}]
+            }}}}"#,
+            WOOD
+        );
+        let chair = format!(
+            r#"{{"scene": {{
+                "shader": [{}],
+                "shape": [{{"_name": "seat", "_type": "mesh", "_shader": "wood",
+                    "file": "../models/seat.obj"}}]
+            }}}}"#,
+            WOOD
+        );
+        let scene = resolve(
+            "nested_includes",
+            &[
+                ("scene.json", &scene),
+                ("props/room.json", &room),
+                ("props/furniture/chair.json", &chair),
+            ],
+        )
+        .unwrap();
+
+        let shaders: Vec<&str> = scene.shaders.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(shaders, ["wood", "room::wood", "room::chair::wood"]);
+        let shapes: Vec<(&str, &str)> = scene
+            .shapes
+            .iter()
+            .map(|s| (s.name.as_str(), s.shader.as_ref().unwrap().name().as_str()))
+            .collect();
+        assert_eq!(
+            shapes,
+            [
+                ("ball", "wood"),
+                ("room::table", "room::wood"),
+                ("room::chair::seat", "room::chair::wood"),
+            ]
+        );
+        // asset paths are relative to the file they are in
+        assert_eq!(
+            Path::new(model_path(&scene.shapes[1])),
+            Path::new("props/table.obj")
+        );
+        assert_eq!(
+            Path::new(model_path(&scene.shapes[2])),
+            Path::new("props/furniture/../models/seat.obj")
+        );
+    }
+
+    #[test]
+    fn test_include_name_collisions() {
+        let prop = format!(
+            r#"{{"scene": {{
+                "shader": [{}],
+                "shape": [{{"_name": "plank", "_type": "sphere", "_shader": "wood",
+                    "center": "0 0 0", "radius": 1}}]
+            }}}}"#,
+            WOOD
+        );
+        let include = |includes: &str| {
+            format!(
+                r#"{{"scene": {{"include": [{}], "shader": [{}]}}}}"#,
+                includes, WOOD
+            )
+        };
+
+        // the same names in different namespaces don't collide
+        let scene = include(
+            r#"{"path": "prop.json", "namespace": "a"}, {"path": "prop.json", "namespace": "b"}"#,
+        );
+        let scene = resolve(
+            "namespaced",
+            &[("scene.json", &scene), ("prop.json", &prop)],
+        );
+        let shapes: Vec<String> = scene.unwrap().shapes.into_iter().map(|s| s.name).collect();
+        assert_eq!(shapes, ["a::plank", "b::plank"]);
+
+        // without a namespace the included shader collides with the scene's own
+        let scene = include(r#""prop.json""#);
+        let error = resolve("colliding", &[("scene.json", &scene), ("prop.json", &prop)])
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("shader name collision: 'wood'"));
+    }
+}
@@ -1,9 +1,24 @@
+mod include;
 mod parse_vec3;
+mod sdf;
 
-use na::{Rotation3, Scale3, Translation3};
+use na::{Matrix4, Unit};
 use serde::{Deserialize, Serialize};
 
-use crate::{camera::*, color, geometry::*, light::*, prelude::*, shader::*, V3};
+use self::include::{resolve_includes, IncludeData};
+use self::sdf::SdfData;
+use crate::{
+    camera::*,
+    color,
+    geometry::*,
+    light::*,
+    math::{AnimatedTransform, CoordinateSystem, Interpolation, Keyframes, Transform, TransformOp},
+    prelude::*,
+    settings::RenderSettings,
+    shader::*,
+    texture::GrayscaleTexture,
+    V3,
+};
 use std::{
     collections::{HashMap, HashSet},
     path::Path,
@@ -12,16 +27,93 @@ use std::{
 
 #[derive(Debug)]
 pub struct Scene {
-    pub disable_shadows: bool,
+    pub settings: RenderSettings,
     pub background_color: Color,
-    pub camera: Box<dyn crate::camera::Camera>,
+    /// Views to render, the first is the main one
+    pub views: Vec<View>,
     pub shapes: Vec<Arc<dyn crate::geometry::Shape>>,
     pub shaders: std::collections::HashMap<String, Arc<dyn crate::shader::Shader>>,
     pub lights: Vec<Box<dyn crate::light::Light>>,
     pub bvh: crate::geometry::BVH,
-    pub recursion_depth: u16,
-    pub image_width: u32,
-    pub image_height: u32,
+    /// What each view was created from, to create it again for other frames
+    cameras: Vec<CameraData>,
+    scene_data_path: String,
+}
+
+impl Scene {
+    /// Camera of the main view, the left eye for stereo
+    pub fn camera(&self) -> &dyn Camera {
+        self.views[0].main_camera()
+    }
+
+    /// Whether the render is a single image from one camera, the only kind [`render_pass`]
+    /// can refine
+    ///
+    /// [`render_pass`]: crate::render_pass
+    pub fn is_single_image(&self) -> bool {
+        matches!(self.views.as_slice(), [View::Mono { .. }])
+    }
+
+    /// Poses the cameras with keyframes for `frame`, leaving the rest of the scene as it is
+    pub fn set_frame(&mut self, frame: Real) -> Result<(), Box<dyn std::error::Error>> {
+        self.settings.frame = Some(frame);
+        for (view, camera_data) in self.views.iter_mut().zip(self.cameras.iter_mut()) {
+            if !camera_data.keyframes.is_empty() {
+                camera_data.pose_at(Some(frame))?;
+                *view = create_view(camera_data, &self.settings, &self.scene_data_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the camera of the main view to `position`, looking at `lookat_point`. Its
+    /// keyframes and end pose no longer apply afterwards, the other views stay where they are.
+    pub fn set_camera_pose(
+        &mut self,
+        position: [Real; 3],
+        lookat_point: [Real; 3],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let camera_data = &mut self.cameras[0];
+        camera_data.keyframes.clear();
+        camera_data.end_pose = None;
+        let (position_data, orientation) = camera_data.camera_type.pose_mut();
+        *position_data = W(V3::from(position));
+        *orientation = CameraOrientation::LookAtPoint {
+            lookat_point: W(V3::from(lookat_point)),
+        };
+        self.views[0] = create_view(camera_data, &self.settings, &self.scene_data_path)?;
+        Ok(())
+    }
+
+    /// Number of pixels in all images of all views
+    pub fn pixel_count(&self) -> u64 {
+        let (width, height) = (self.settings.image_width(), self.settings.image_height());
+        self.views
+            .iter()
+            .flat_map(|view| view.images(width, height))
+            .map(|(_, width, height)| width as u64 * height as u64)
+            .sum()
+    }
+
+    /// Names of the shapes in the order they are in the scene file, shape `i` has ID `i + 1`
+    /// in the shape ID AOV
+    pub fn shape_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for shape in &self.shapes {
+            if !names.iter().any(|name| name == shape.get_name()) {
+                names.push(shape.get_name().to_string());
+            }
+        }
+        names
+    }
+
+    /// Names of the shaders sorted alphabetically, shader `i` has ID `i + 1` in the shader ID
+    /// AOV
+    pub fn shader_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.shaders.keys().cloned().collect();
+        names.sort();
+        names
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -31,15 +123,17 @@ struct SceneModel {
 
 #[derive(Deserialize, Serialize, Debug)]
 struct SceneData {
+    #[serde(alias = "includes", default)]
+    include: Vec<IncludeData>,
     #[serde(alias = "sceneParameters", default)]
     scene_parameters: SceneParameters,
-    #[serde(alias = "camera")]
+    #[serde(alias = "camera", default)]
     cameras: Vec<CameraData>,
     #[serde(alias = "light", default)]
     lights: Vec<LightData>,
-    #[serde(alias = "shader")]
+    #[serde(alias = "shader", default)]
     shaders: Vec<ShaderData>,
-    #[serde(alias = "shape")]
+    #[serde(alias = "shape", default)]
     shapes: Vec<ShapeData>,
     #[serde(alias = "texture", default)]
     textures: Vec<TextureData>,
@@ -52,6 +146,8 @@ struct SceneParameters {
     #[serde(flatten)]
     background: Option<Background>,
     camera: Option<String>,
+    #[serde(flatten)]
+    render_settings: RenderSettings,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -86,6 +182,165 @@ struct CameraData {
     camera_type: CameraType,
     #[serde(alias = "imagePlaneWidth", default)]
     image_plane_width: Option<Real>,
+    /// Direction the top of the image faces
+    #[serde(default = "default_up")]
+    up: W<V3>,
+    /// Counterclockwise turn around the view direction in degrees
+    #[serde(default)]
+    roll: Real,
+    /// Where the camera has moved to at time 1, for motion blur
+    #[serde(alias = "endPose", default)]
+    end_pose: Option<CameraPoseData>,
+    /// Poses of an animated camera by frame, overriding its position and look-at point, and
+    /// the focal length of perspective cameras
+    #[serde(default)]
+    keyframes: Vec<CameraKeyframeData>,
+    #[serde(default)]
+    interpolation: InterpolationData,
+}
+
+impl CameraData {
+    /// Moves the camera to where its keyframes put it at `frame`, or at its first keyframe
+    fn pose_at(&mut self, frame: Option<Real>) -> Result<(), Box<dyn std::error::Error>> {
+        let name = self.name.as_str();
+        let frame = frame.unwrap_or_else(|| {
+            self.keyframes
+                .iter()
+                .map(|keyframe| keyframe.frame)
+                .fold(INFINITY, Real::min)
+        });
+        let interpolation = match self.interpolation {
+            InterpolationData::Linear => Interpolation::Linear,
+            InterpolationData::CatmullRom => Interpolation::CatmullRom,
+        };
+        let animate = |value: fn(&CameraKeyframeData) -> V3| {
+            Keyframes::new(
+                self.keyframes
+                    .iter()
+                    .map(|keyframe| (keyframe.frame, value(keyframe)))
+                    .collect(),
+                interpolation,
+            )
+            .at(frame)
+        };
+        let position = animate(|keyframe| keyframe.position.0);
+        let lookat_point = animate(|keyframe| keyframe.lookat_point.0);
+        let focal_lengths: Vec<(Real, Real)> = self
+            .keyframes
+            .iter()
+            .filter_map(|keyframe| Some((keyframe.frame, keyframe.focal_length?)))
+            .collect();
+        let focal_length = match focal_lengths.len() {
+            0 => None,
+            n if n == self.keyframes.len() => {
+                Some(Keyframes::new(focal_lengths, interpolation).at(frame))
+            }
+            _ => {
+                return Err(invalid_data(format!(
+                    "camera {} needs a focal length in every keyframe or in none",
+                    name
+                )))
+            }
+        };
+
+        let (position_data, orientation) = self.camera_type.pose_mut();
+        *position_data = W(position);
+        *orientation = CameraOrientation::LookAtPoint {
+            lookat_point: W(lookat_point),
+        };
+        if let Some(focal_length) = focal_length {
+            let perspective = self.camera_type.perspective_mut().ok_or_else(|| {
+                invalid_data(format!("camera {} has no focal length to animate", name))
+            })?;
+            perspective.focal_length = Some(focal_length);
+            perspective.horizontal_fov = None;
+            perspective.vertical_fov = None;
+        }
+        Ok(())
+    }
+}
+
+impl CameraType {
+    fn pose_mut(&mut self) -> (&mut W<V3>, &mut CameraOrientation) {
+        match self {
+            CameraType::Perspective(PerspectiveCameraData {
+                position,
+                orientation,
+                ..
+            })
+            | CameraType::Stereo(StereoCameraData {
+                perspective:
+                    PerspectiveCameraData {
+                        position,
+                        orientation,
+                        ..
+                    },
+                ..
+            })
+            | CameraType::Orthographic(OrthographicCameraData {
+                position,
+                orientation,
+            })
+            | CameraType::Equirectangular(EquirectangularCameraData {
+                position,
+                orientation,
+            })
+            | CameraType::Cylindrical(CylindricalCameraData {
+                position,
+                orientation,
+                ..
+            })
+            | CameraType::Fisheye(FisheyeCameraData {
+                position,
+                orientation,
+                ..
+            })
+            | CameraType::Realistic(RealisticCameraData {
+                position,
+                orientation,
+                ..
+            }) => (position, orientation),
+        }
+    }
+
+    /// Settings of cameras with a focal length
+    fn perspective_mut(&mut self) -> Option<&mut PerspectiveCameraData> {
+        match self {
+            CameraType::Perspective(perspective) => Some(perspective),
+            CameraType::Stereo(stereo) => Some(&mut stereo.perspective),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct CameraKeyframeData {
+    frame: Real,
+    position: W<V3>,
+    #[serde(alias = "lookatPoint", alias = "lookAt")]
+    lookat_point: W<V3>,
+    #[serde(alias = "focalLength")]
+    focal_length: Option<Real>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy)]
+enum InterpolationData {
+    #[default]
+    #[serde(alias = "linear")]
+    Linear,
+    #[serde(alias = "catmullRom", alias = "catmull_rom", alias = "spline")]
+    CatmullRom,
+}
+
+fn default_up() -> W<V3> {
+    W(V3::y())
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct CameraPoseData {
+    position: W<V3>,
+    #[serde(flatten)]
+    orientation: CameraOrientation,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -95,6 +350,16 @@ enum CameraType {
     Perspective(PerspectiveCameraData),
     #[serde(alias = "orthographic")]
     Orthographic(OrthographicCameraData),
+    #[serde(alias = "equirectangular")]
+    Equirectangular(EquirectangularCameraData),
+    #[serde(alias = "cylindrical")]
+    Cylindrical(CylindricalCameraData),
+    #[serde(alias = "fisheye")]
+    Fisheye(FisheyeCameraData),
+    #[serde(alias = "stereo")]
+    Stereo(StereoCameraData),
+    #[serde(alias = "realistic")]
+    Realistic(RealisticCameraData),
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -102,8 +367,123 @@ struct PerspectiveCameraData {
     position: W<V3>,
     #[serde(flatten)]
     orientation: CameraOrientation,
+    /// Distance from the camera to the image plane, the field of view can be given instead
     #[serde(alias = "focalLength")]
-    focal_length: Real,
+    focal_length: Option<Real>,
+    /// Angle between the left and right edges of the image in degrees
+    #[serde(alias = "hfov", alias = "horizontalFov")]
+    horizontal_fov: Option<Real>,
+    /// Angle between the top and bottom edges of the image in degrees
+    #[serde(alias = "vfov", alias = "verticalFov")]
+    vertical_fov: Option<Real>,
+    /// Radius of the lens, the camera is a pinhole without one
+    #[serde(alias = "apertureRadius", alias = "aperture")]
+    aperture_radius: Option<Real>,
+    /// Alternative to `aperture_radius`, the focal length divided by the lens diameter
+    #[serde(alias = "fStop", alias = "fstop")]
+    f_stop: Option<Real>,
+    /// Distance in front of the camera that is in focus, the look-at point by default
+    #[serde(alias = "focusDistance")]
+    focus_distance: Option<Real>,
+    /// Number of sides of a polygonal aperture, round if not given
+    #[serde(alias = "apertureBlades", default)]
+    aperture_blades: u32,
+    /// Rotation of a polygonal aperture in degrees
+    #[serde(alias = "apertureRotation", default)]
+    aperture_rotation: Real,
+}
+
+impl PerspectiveCameraData {
+    /// The camera at `basis`, along with its focal length
+    fn camera(
+        &self,
+        name: &str,
+        basis: CoordinateSystem,
+        image_plane_width: Option<Real>,
+        aspect_ratio: Real,
+    ) -> Result<(PerspectiveCamera, Real), Box<dyn std::error::Error>> {
+        let image_plane_width = image_plane_width.unwrap_or(DEFAULT_IMAGE_PLANE_WIDTH);
+        let focal_length =
+            self.focal_length(name, image_plane_width, image_plane_width / aspect_ratio)?;
+        let mut camera =
+            PerspectiveCamera::new(basis, Some(image_plane_width), aspect_ratio, focal_length);
+        if let Some(lens) = self.lens(name, focal_length)? {
+            camera.set_lens(lens);
+        }
+        Ok((camera, focal_length))
+    }
+
+    fn focal_length(
+        &self,
+        name: &str,
+        image_plane_width: Real,
+        image_plane_height: Real,
+    ) -> Result<Real, Box<dyn std::error::Error>> {
+        let from_fov = |degrees: Real, extent: Real| {
+            if degrees <= 0.0 || degrees >= 180.0 {
+                return Err(invalid_data(format!(
+                    "camera {} needs a field of view between 0 and 180 degrees",
+                    name
+                )));
+            }
+            Ok(extent / 2.0 / (PI * degrees / 360.0).tan())
+        };
+
+        match (self.focal_length, self.horizontal_fov, self.vertical_fov) {
+            (Some(focal_length), None, None) => positive(focal_length, name, "focal length"),
+            (None, Some(degrees), None) => from_fov(degrees, image_plane_width),
+            (None, None, Some(degrees)) => from_fov(degrees, image_plane_height),
+            _ => Err(invalid_data(format!(
+                "camera {} needs one of a focal length, a horizontal field of view or a vertical \
+                 field of view",
+                name
+            ))),
+        }
+    }
+
+    fn lens(
+        &self,
+        name: &str,
+        focal_length: Real,
+    ) -> Result<Option<ThinLens>, Box<dyn std::error::Error>> {
+        let aperture_radius = match (self.aperture_radius, self.f_stop) {
+            (None, None) => return Ok(None),
+            (Some(radius), None) => positive(radius, name, "aperture radius")?,
+            (None, Some(f_stop)) => focal_length / (2.0 * positive(f_stop, name, "f-stop")?),
+            (Some(_), Some(_)) => {
+                return Err(invalid_data(format!(
+                    "camera {} needs either an aperture radius or an f-stop, not both",
+                    name
+                )))
+            }
+        };
+
+        let focus_distance = match (&self.orientation, self.focus_distance) {
+            (_, Some(distance)) => distance,
+            (CameraOrientation::LookAtPoint { lookat_point }, None) => {
+                (lookat_point.0 - self.position.0).norm()
+            }
+            (CameraOrientation::ViewDir { .. }, None) => {
+                return Err(invalid_data(format!(
+                    "camera {} needs a focus distance to focus without a look-at point",
+                    name
+                )))
+            }
+        };
+        if self.aperture_blades > 0 && self.aperture_blades < 3 {
+            return Err(invalid_data(format!(
+                "camera {} needs at least 3 aperture blades",
+                name
+            )));
+        }
+
+        Ok(Some(ThinLens {
+            aperture_radius,
+            focus_distance: positive(focus_distance, name, "focus distance")?,
+            blades: self.aperture_blades,
+            rotation: PI * self.aperture_rotation / 180.0,
+        }))
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -113,6 +493,162 @@ struct OrthographicCameraData {
     orientation: CameraOrientation,
 }
 
+/// Pair of perspective cameras side by side, set up like the perspective camera between them
+#[derive(Deserialize, Serialize, Debug)]
+struct StereoCameraData {
+    #[serde(flatten)]
+    perspective: PerspectiveCameraData,
+    /// Distance between the eyes, in scene units
+    #[serde(
+        alias = "interocularDistance",
+        alias = "eyeSeparation",
+        default = "default_interocular_distance"
+    )]
+    interocular_distance: Real,
+    /// Distance of the screen plane, the look-at point by default. Parallel eyes without one.
+    #[serde(alias = "convergenceDistance", alias = "convergence")]
+    convergence_distance: Option<Real>,
+    #[serde(default)]
+    layout: StereoLayoutData,
+}
+
+fn default_interocular_distance() -> Real {
+    0.065
+}
+
+impl StereoCameraData {
+    fn rig(&self, name: &str) -> Result<StereoRig, Box<dyn std::error::Error>> {
+        let convergence_distance = match (&self.perspective.orientation, self.convergence_distance)
+        {
+            (_, Some(distance)) => Some(positive(distance, name, "convergence distance")?),
+            (CameraOrientation::LookAtPoint { lookat_point }, None) => {
+                Some((lookat_point.0 - self.perspective.position.0).norm())
+            }
+            (CameraOrientation::ViewDir { .. }, None) => None,
+        };
+        Ok(StereoRig {
+            interocular_distance: positive(
+                self.interocular_distance,
+                name,
+                "interocular distance",
+            )?,
+            convergence_distance,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy)]
+enum StereoLayoutData {
+    #[default]
+    #[serde(alias = "separate")]
+    Separate,
+    #[serde(alias = "sideBySide", alias = "side_by_side")]
+    SideBySide,
+    #[serde(alias = "anaglyph")]
+    Anaglyph,
+}
+
+impl From<StereoLayoutData> for StereoLayout {
+    fn from(layout: StereoLayoutData) -> Self {
+        match layout {
+            StereoLayoutData::Separate => StereoLayout::Separate,
+            StereoLayoutData::SideBySide => StereoLayout::SideBySide,
+            StereoLayoutData::Anaglyph => StereoLayout::Anaglyph,
+        }
+    }
+}
+
+/// Camera looking through a real lens, described by a prescription file
+#[derive(Deserialize, Serialize, Debug)]
+struct RealisticCameraData {
+    position: W<V3>,
+    #[serde(flatten)]
+    orientation: CameraOrientation,
+    #[serde(alias = "lensPath", alias = "lensFile", alias = "prescription")]
+    lens_path: String,
+    /// Scene units per millimeter of the prescription, so meters by default
+    #[serde(alias = "lensScale", default = "default_lens_scale")]
+    lens_scale: Real,
+    /// Diagonal of the film in millimeters, full frame by default
+    #[serde(alias = "filmDiagonal", default = "default_film_diagonal")]
+    film_diagonal: Real,
+    /// Diameter of the aperture stop in millimeters, as wide as the prescription allows if not
+    /// given
+    #[serde(alias = "apertureDiameter")]
+    aperture_diameter: Option<Real>,
+    /// Distance in front of the film that is in focus, the look-at point by default
+    #[serde(alias = "focusDistance")]
+    focus_distance: Option<Real>,
+}
+
+fn default_lens_scale() -> Real {
+    0.001
+}
+
+fn default_film_diagonal() -> Real {
+    43.3
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct EquirectangularCameraData {
+    position: W<V3>,
+    #[serde(flatten)]
+    orientation: CameraOrientation,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct CylindricalCameraData {
+    position: W<V3>,
+    #[serde(flatten)]
+    orientation: CameraOrientation,
+    /// Angle around the cylinder in degrees, all the way around by default
+    #[serde(
+        alias = "hfov",
+        alias = "horizontalFov",
+        default = "default_panorama_fov"
+    )]
+    horizontal_fov: Real,
+    /// Angle between the top and bottom edges of the image in degrees
+    #[serde(
+        alias = "vfov",
+        alias = "verticalFov",
+        default = "default_cylindrical_vertical_fov"
+    )]
+    vertical_fov: Real,
+}
+
+fn default_panorama_fov() -> Real {
+    360.0
+}
+
+fn default_cylindrical_vertical_fov() -> Real {
+    90.0
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct FisheyeCameraData {
+    position: W<V3>,
+    #[serde(flatten)]
+    orientation: CameraOrientation,
+    /// Angle across the image circle in degrees
+    #[serde(default = "default_fisheye_fov")]
+    fov: Real,
+    #[serde(default)]
+    projection: FisheyeProjectionData,
+}
+
+fn default_fisheye_fov() -> Real {
+    180.0
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum FisheyeProjectionData {
+    #[default]
+    Equidistant,
+    Equisolid,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(untagged)]
 enum CameraOrientation {
@@ -258,6 +794,13 @@ impl ShaderRefType {
             ShaderRefType::Inline(name) => name,
         }
     }
+
+    fn name_mut(&mut self) -> &mut String {
+        match self {
+            ShaderRefType::Nested(ShaderRef { name }) => name,
+            ShaderRefType::Inline(name) => name,
+        }
+    }
 }
 
 impl Serialize for ShaderRefType {
@@ -274,11 +817,14 @@ impl Serialize for ShaderRefType {
 
 #[derive(Deserialize, Serialize, Debug)]
 struct ShapeData {
-    #[serde(rename = "_name")]
+    /// Only optional for shapes defined inline in a CSG node
+    #[serde(rename = "_name", default)]
     name: String,
+    /// Optional for instances, which use their prototype's shader unless one is given, and
+    /// for meshes, which can use the materials from their model
     #[serde(rename = "_shader")]
-    #[serde(alias = "shader")]
-    shader: ShaderRefType,
+    #[serde(alias = "shader", default)]
+    shader: Option<ShaderRefType>,
     #[serde(flatten)]
     shape: ShapeType,
 }
@@ -292,6 +838,68 @@ enum ShapeType {
     Triangle(TriangleData),
     Mesh(MeshData),
     Instance(InstanceData),
+    Plane(PlaneData),
+    Disk(DiskData),
+    Cylinder(CylinderData),
+    Cone(CylinderData),
+    Torus(TorusData),
+    Csg(CsgData),
+    Sdf(SdfData),
+    Quadric(QuadricData),
+    Ellipsoid(EllipsoidData),
+    Paraboloid(ParaboloidData),
+    Hyperboloid(HyperboloidData),
+    Heightfield(HeightfieldData),
+    Curves(CurvesData),
+    #[serde(alias = "point_cloud", alias = "pointCloud")]
+    PointCloud(PointCloudData),
+}
+
+impl ShapeData {
+    /// Names of the instance prototypes this shape is built from, including those used by
+    /// shapes defined inline in it
+    fn prototype_references(&self) -> Vec<&str> {
+        match &self.shape {
+            ShapeType::Instance(instance) => vec![instance.instance_of.as_str()],
+            ShapeType::Csg(csg) => csg
+                .shapes
+                .iter()
+                .flat_map(|operand| match operand {
+                    CsgOperand::Reference(prototype) => vec![prototype.as_str()],
+                    CsgOperand::Inline(shape) => shape.prototype_references(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Same as `prototype_references` but without the references of inline shapes
+    fn prototype_references_mut(&mut self) -> Vec<&mut String> {
+        match &mut self.shape {
+            ShapeType::Instance(instance) => vec![&mut instance.instance_of],
+            ShapeType::Csg(csg) => csg
+                .shapes
+                .iter_mut()
+                .filter_map(|operand| match operand {
+                    CsgOperand::Reference(prototype) => Some(prototype),
+                    CsgOperand::Inline(_) => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Calls `f` on this shape and then on every shape defined inline in it
+    fn visit_mut(&mut self, f: &mut impl FnMut(&mut ShapeData)) {
+        f(self);
+        if let ShapeType::Csg(csg) = &mut self.shape {
+            for operand in csg.shapes.iter_mut() {
+                if let CsgOperand::Inline(shape) = operand {
+                    shape.visit_mut(f);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -329,13 +937,229 @@ struct TriangleData {
 struct MeshData {
     #[serde(alias = "file")]
     model_path: String,
+    subdivision: Option<SubdivisionData>,
+    displacement: Option<DisplacementData>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct SubdivisionData {
+    scheme: SubdivisionSchemeData,
+    #[serde(default = "default_subdivision_levels")]
+    levels: u32,
+}
+
+fn default_subdivision_levels() -> u32 {
+    1
+}
+
+/// Highest subdivision level, every level multiplies the number of faces by four
+static MAX_SUBDIVISION_LEVELS: u32 = 6;
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum SubdivisionSchemeData {
+    Loop,
+    #[serde(alias = "catmull-clark", alias = "catmullClark")]
+    CatmullClark,
+}
+
+/// Offsets the surface along its normals by `scale` times the value of a grayscale texture
+#[derive(Deserialize, Serialize, Debug)]
+struct DisplacementData {
+    #[serde(alias = "tex")]
+    texture: String,
+    scale: Real,
+}
+
+/// Infinite unless both `width` and `length` are given
+#[derive(Deserialize, Serialize, Debug)]
+struct PlaneData {
+    #[serde(alias = "point")]
+    center: W<V3>,
+    normal: W<V3>,
+    width: Option<Real>,
+    length: Option<Real>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct DiskData {
+    center: W<V3>,
+    normal: W<V3>,
+    radius: Real,
+    #[serde(alias = "innerRadius", default)]
+    inner_radius: Real,
+}
+
+/// Shared by cylinders and cones, a cone's apex is `height` along `axis` from `base`
+#[derive(Deserialize, Serialize, Debug)]
+struct CylinderData {
+    #[serde(alias = "center")]
+    base: W<V3>,
+    #[serde(default = "default_axis")]
+    axis: W<V3>,
+    radius: Real,
+    height: Real,
+    #[serde(default = "default_capped")]
+    capped: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct TorusData {
+    center: W<V3>,
+    #[serde(default = "default_axis")]
+    axis: W<V3>,
+    #[serde(alias = "majorRadius")]
+    major_radius: Real,
+    #[serde(alias = "minorRadius")]
+    minor_radius: Real,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct CsgData {
+    operation: CsgOperationData,
+    /// Combined from left to right, so a difference cuts every later shape out of the first
+    #[serde(alias = "operands")]
+    shapes: Vec<CsgOperand>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum CsgOperationData {
+    Union,
+    #[serde(alias = "intersect")]
+    Intersection,
+    #[serde(alias = "subtract")]
+    Difference,
+}
+
+/// Either the name of an instance prototype or a shape defined in place, which uses the CSG
+/// node's shader unless it has its own
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+enum CsgOperand {
+    Reference(String),
+    Inline(Box<ShapeData>),
+}
+
+/// Coefficients of `A x^2 + B y^2 + C z^2 + D xy + E xz + F yz + G x + H y + I z + J`,
+/// the surface is clipped to the box between `min` and `max`
+#[derive(Deserialize, Serialize, Debug)]
+struct QuadricData {
+    coefficients: Vec<Real>,
+    #[serde(alias = "minPt")]
+    min: W<V3>,
+    #[serde(alias = "maxPt")]
+    max: W<V3>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct EllipsoidData {
+    center: W<V3>,
+    radii: W<V3>,
+}
+
+/// Opens up the y axis from `center`, `radius` wide at `height`
+#[derive(Deserialize, Serialize, Debug)]
+struct ParaboloidData {
+    center: W<V3>,
+    radius: Real,
+    height: Real,
+}
+
+/// Around the y axis, cut off `height` above and below `center`
+#[derive(Deserialize, Serialize, Debug)]
+struct HyperboloidData {
+    center: W<V3>,
+    radii: W<V3>,
+    height: Real,
+    #[serde(alias = "twoSheets", default)]
+    two_sheets: bool,
+}
+
+/// Heights from a grayscale image, spread over `size.x` by `size.z` and scaled to `size.y`
+#[derive(Deserialize, Serialize, Debug)]
+struct HeightfieldData {
+    #[serde(alias = "file")]
+    image_path: String,
+    center: W<V3>,
+    size: W<V3>,
+}
+
+/// Strands given inline, loaded from a strand file or both
+#[derive(Deserialize, Serialize, Debug)]
+struct CurvesData {
+    #[serde(default)]
+    mode: CurveModeData,
+    #[serde(default)]
+    strands: Vec<StrandData>,
+    #[serde(alias = "strandPath", alias = "file")]
+    strand_path: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+enum CurveModeData {
+    Ribbon,
+    #[default]
+    Tube,
+}
+
+/// Cubic Bézier segments sharing their end points, tapering from `radius` at the root to
+/// `tip_radius`
+#[derive(Deserialize, Serialize, Debug)]
+struct StrandData {
+    #[serde(alias = "controlPoints")]
+    points: Vec<W<V3>>,
+    radius: Real,
+    #[serde(alias = "tipRadius")]
+    tip_radius: Option<Real>,
+}
+
+/// Points from a PLY or XYZ file, all drawn with the same radius
+#[derive(Deserialize, Serialize, Debug)]
+struct PointCloudData {
+    #[serde(alias = "pointPath", alias = "file")]
+    point_path: String,
+    radius: Real,
+    #[serde(alias = "splat", default)]
+    primitive: PointPrimitiveData,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+enum PointPrimitiveData {
+    #[default]
+    Sphere,
+    Disk,
+}
+
+fn default_axis() -> W<V3> {
+    W(V3::y())
+}
+
+fn default_capped() -> bool {
+    true
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 struct InstanceData {
     #[serde(alias = "_id")]
     instance_of: String,
-    #[serde(alias = "xform")]
+    /// Applied in the order written, the first transform is applied to the object first
+    #[serde(alias = "xform", default)]
+    transform: Vec<TransformData>,
+    /// Where the instance has moved to at time 1, it starts at `transform` at time 0
+    #[serde(alias = "endTransform", alias = "endXform", default)]
+    end_transform: Option<Vec<TransformData>>,
+    /// Transforms at any number of times, instead of `transform` and `end_transform`
+    #[serde(default)]
+    keyframes: Vec<TransformKeyframeData>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct TransformKeyframeData {
+    time: Real,
+    #[serde(alias = "xform", default)]
     transform: Vec<TransformData>,
 }
 
@@ -355,10 +1179,53 @@ enum TransformData {
         #[serde(alias = "amount")]
         degrees: Real,
     },
+    /// Row-major 4x4 matrix, or the top three rows of one
+    Matrix {
+        #[serde(alias = "amount")]
+        matrix: Vec<Real>,
+    },
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-enum RotationAxis {
+impl TransformData {
+    fn to_op(&self) -> Result<TransformOp, String> {
+        Ok(match self {
+            TransformData::Translate { amount } => TransformOp::Translate(amount.0),
+            TransformData::Scale { amount } => TransformOp::Scale(amount.0),
+            TransformData::Rotate { axis, degrees } => {
+                let axis = match axis {
+                    RotationAxis::Named(NamedAxis::X) => V3::x_axis(),
+                    RotationAxis::Named(NamedAxis::Y) => V3::y_axis(),
+                    RotationAxis::Named(NamedAxis::Z) => V3::z_axis(),
+                    RotationAxis::Vector(axis) => Unit::try_new(axis.0, VERY_SMALL_NUMBER)
+                        .ok_or("rotation axis must be non-zero")?,
+                };
+                TransformOp::Rotate {
+                    axis,
+                    angle: PI * degrees / 180.0,
+                }
+            }
+            TransformData::Matrix { matrix } => {
+                let mut rows = matrix.clone();
+                match rows.len() {
+                    16 => (),
+                    12 => rows.extend([0.0, 0.0, 0.0, 1.0]),
+                    _ => return Err("matrix transform must have 12 or 16 values".to_string()),
+                }
+                TransformOp::Matrix(Matrix4::from_row_slice(&rows))
+            }
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+enum RotationAxis {
+    Named(NamedAxis),
+    Vector(W<V3>),
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+enum NamedAxis {
     #[serde(alias = "x")]
     X,
     #[serde(alias = "y")]
@@ -375,86 +1242,77 @@ struct TextureData {
     name: String,
 }
 
+/// Parses a scene from JSON.
+///
+/// Render settings found in the scene's `sceneParameters` are overridden by any field set in
+/// `overrides`; whatever is left unset falls back to the library defaults. Included files and
+/// mesh models are resolved relative to `scene_data_path`.
 pub fn parse_scene(
     scene_json: &str,
     scene_data_path: &str,
-    image_width: Option<u32>,
-    image_height: Option<u32>,
-    aspect_ratio: Option<Real>,
-    recursion_depth: Option<u16>,
-    disable_shadows: bool,
-    render_normals: bool,
+    overrides: &RenderSettings,
 ) -> Result<Scene, Box<dyn std::error::Error>> {
     let scene_file: SceneModel = serde_json::from_str(scene_json)?;
-    let scene = scene_file.scene;
+    let mut scene = scene_file.scene;
+
+    // Pull in the definitions from included files
+    resolve_includes(&mut scene, scene_data_path)?;
 
     // print scene data
     #[cfg(debug_assertions)]
     println!("{:#?}", scene);
 
-    // Image size or default
-    let image_width = image_width.unwrap_or(DEFAULT_IMAGE_WIDTH);
-    let image_height = image_height.unwrap_or(DEFAULT_IMAGE_HEIGHT);
+    // Scene file settings, overridden by the caller's settings
+    let settings = scene
+        .scene_parameters
+        .render_settings
+        .with_overrides(overrides);
+    settings
+        .validate()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
 
-    // Calculate aspect ratio if not specified
-    let aspect_ratio = aspect_ratio.unwrap_or(image_width as Real / image_height as Real);
+    let render_normals = settings.render_normals();
 
     // Check that there is exactly one camera
     if scene.cameras.is_empty() {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "scene must have at least one camera",
-        )));
+        return Err(invalid_data("scene must have at least one camera"));
     }
 
-    // Select camera
-    let camera_index = if scene.cameras.len() == 1 {
-        0
+    // Select cameras, every one of them becomes a view rendered from the same BVH
+    let camera_names = if settings.all_cameras() {
+        scene.cameras.iter().map(|c| c.name.clone()).collect()
+    } else if let Some(names) = settings.cameras.clone().filter(|names| !names.is_empty()) {
+        names
+    } else if scene.cameras.len() == 1 {
+        vec![scene.cameras[0].name.clone()]
     } else {
         // camera name specified or default
-        let camera_name = scene
+        vec![scene
             .scene_parameters
             .camera
-            .unwrap_or(DEFAULT_CAMERA.to_string());
-
-        // filter scene.cameras by name
-        scene
+            .clone()
+            .unwrap_or(DEFAULT_CAMERA.to_string())]
+    };
+    let mut cameras = Vec::new();
+    for camera_name in camera_names {
+        if cameras.iter().any(|c: &CameraData| c.name == camera_name) {
+            continue;
+        }
+        let index = scene
             .cameras
             .iter()
             .position(|c| c.name == camera_name)
-            .ok_or_else(|| {
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("camera {} not found", camera_name),
-                )
-            })?
-    };
-
-    // Create camera
-    let mut camera: Box<dyn crate::camera::Camera> = match &scene.cameras[camera_index].camera_type
-    {
-        CameraType::Perspective(perspective) => {
-            let position = P3::from(perspective.position.0);
-            Box::new(PerspectiveCamera::new(
-                position,
-                &perspective.orientation.get_view_direction(position),
-                aspect_ratio,
-                perspective.focal_length,
-            ))
-        }
-        CameraType::Orthographic(orthographic) => {
-            let position = P3::from(orthographic.position.0);
-
-            Box::new(OrthographicCamera::new(
-                position,
-                &orthographic.orientation.get_view_direction(position),
-                aspect_ratio,
-            ))
+            .ok_or_else(|| invalid_data(format!("camera {} not found", camera_name)))?;
+        let mut camera_data = scene.cameras.remove(index);
+        if !camera_data.keyframes.is_empty() {
+            camera_data.pose_at(settings.frame)?;
         }
-    };
-
-    // Set image size
-    camera.set_image_pixels(image_width, image_height);
+        cameras.push(camera_data);
+    }
+    let views = cameras
+        .iter()
+        .map(|camera_data| create_view(camera_data, &settings, scene_data_path))
+        .collect::<Result<Vec<_>, _>>()?;
 
     // Create shaders
     let mut shaders: HashMap<String, Arc<dyn Shader>> = HashMap::new();
@@ -484,104 +1342,442 @@ pub fn parse_scene(
                     blinn_phong.shininess,
                 ))
             }
-            ShaderType::PerfectMirror => Arc::new(PerfectMirrorShader::default()),
+            ShaderType::PerfectMirror => Arc::new(PerfectMirrorShader),
             ShaderType::GGXMirror(mirror) => {
                 Arc::new(GGXMirrorShader::new(mirror.roughness, mirror.samples))
             }
-            _ => Arc::new(NullShader::default()),
+            _ => Arc::new(NullShader),
         };
         shaders.insert(shader_name, shader);
     }
 
-    // Create instances
-    let mut instances: HashMap<String, Arc<dyn Shape>> = HashMap::new();
+    // Create instance prototypes, the prototypes they are instances of are created first
+    let mut context = ShapeContext {
+        scene_data_path,
+        textures: scene
+            .textures
+            .iter()
+            .map(|texture| (texture.name.as_str(), texture.image_path.as_str()))
+            .collect(),
+        shaders: &shaders,
+        instances: HashMap::new(),
+    };
+    let prototypes: HashMap<&str, &ShapeData> = scene
+        .instances
+        .iter()
+        .map(|shape| (shape.name.as_str(), shape))
+        .collect();
     for shape in scene.instances.iter() {
-        let instance_name = Box::leak(shape.name.clone().into_boxed_str());
-        let shader = Arc::new(NullShader::default());
-        let shape: Arc<dyn Shape> = match &shape.shape {
-            ShapeType::Sphere(sphere) => Arc::new(Sphere::new(
-                P3::from(sphere.center.0),
-                sphere.radius,
-                shader,
-                instance_name,
-            )),
-            ShapeType::Box(cuboid) => Arc::new(match cuboid {
-                BoxData::MinMaxPoint {
-                    min: min_point,
-                    max: max_point,
-                } => Cuboid::new(
-                    P3::from(min_point.0),
-                    P3::from(max_point.0),
-                    shader,
-                    instance_name,
-                ),
-                BoxData::CenterExtent { center, extent } => {
-                    let center = P3::from(center.0);
-                    let half_extent = extent.0 / 2.0;
-                    let min_point = center - half_extent;
-                    let max_point = center + half_extent;
-                    Cuboid::new(min_point, max_point, shader, instance_name)
-                }
-            }),
-            ShapeType::Triangle(triangle) => Arc::new(Triangle::new(
-                P3::from(triangle.a.0),
-                P3::from(triangle.b.0),
-                P3::from(triangle.c.0),
-                shader,
-                instance_name,
-            )),
-            ShapeType::Mesh(mesh) => {
-                // TODO: this should be done differently
-                let model_path = String::from(
-                    Path::new(&scene_data_path)
-                        .join(&mesh.model_path)
-                        .to_str()
-                        .expect("failed to convert model path to string"),
-                );
-                Arc::new(Mesh::new(model_path, shader, instance_name))
-            }
-            ShapeType::Instance(_) => panic!("An instanced shape can not be type instance"),
-        };
-        instances.insert(instance_name.to_string(), shape);
+        context.create_prototype(&shape.name, &prototypes, &mut Vec::new())?;
     }
 
-    // normal shader
-    let normal_shader = Arc::new(NormalShader::default());
-
     // create a set of names for the shapes to that names are unique
     let mut shape_names: HashSet<&str> = HashSet::new();
 
     // Create shapes
     let mut shapes: Vec<Arc<dyn Shape>> = Vec::new();
     for shape in scene.shapes.iter() {
-        // extract shader, or just use normal shader
-        let shader = if !render_normals {
-            match shaders.get(shape.shader.name()) {
-                Some(s) => Arc::clone(s),
-                None => {
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "shape references non-existent shader",
+        let shape_name = Box::leak(shape.name.clone().into_boxed_str());
+        if !shape_names.insert(shape_name) {
+            return Err(invalid_data("shape names must be unique"));
+        }
+        shapes.push(context.create_shape(shape, shape_name, None)?);
+    }
+
+    // Create lights
+    let mut lights: Vec<Box<dyn crate::light::Light>> = Vec::new();
+    for light in scene.lights.iter() {
+        let light: Box<dyn Light> = match &light.light_type {
+            LightType::Ambient(ambient_light) => {
+                Box::new(AmbientLight::new(ambient_light.intensity.0))
+            }
+            LightType::Point(point_light) => Box::new(PointLight::new(
+                P3::from(point_light.position.0),
+                point_light.intensity.0,
+            )),
+            _ => unimplemented!("light type not implemented yet"),
+        };
+        lights.push(light);
+    }
+
+    // get background color
+    let background_color = if render_normals {
+        color!(0.0, 0.0, 0.0)
+    } else if let Some(background) = scene.scene_parameters.background {
+        match background {
+            Background::BackgroundColor { background_color } => background_color.0,
+            Background::EnvMap(_) => {
+                unimplemented!("environment maps aren't implemented yet")
+            }
+        }
+    } else {
+        DEFAULT_BACKGROUND_COLOR
+    };
+
+    let shape_refs = shapes.clone();
+    let bvh = BVH::new(shape_refs);
+
+    let scene = Scene {
+        settings,
+        background_color,
+        views,
+        shapes,
+        shaders,
+        lights,
+        bvh,
+        cameras,
+        scene_data_path: scene_data_path.to_string(),
+    };
+    Ok(scene)
+}
+
+/// Camera, or pair of cameras for stereo, described by `camera_data`
+fn create_view(
+    camera_data: &CameraData,
+    settings: &RenderSettings,
+    scene_data_path: &str,
+) -> Result<View, Box<dyn std::error::Error>> {
+    let aspect_ratio = settings.aspect_ratio();
+    let name = camera_data.name.as_str();
+    let up = nonzero(camera_data.up.0, name, "up direction")?;
+    let roll = PI * camera_data.roll / 180.0;
+    let basis = |position: P3, orientation: &CameraOrientation| {
+        let view_direction = orientation.get_view_direction(position);
+        CoordinateSystem::oriented(position, &view_direction, &up, roll)
+    };
+    let image_plane_width = camera_data
+        .image_plane_width
+        .map(|width| positive(width, name, "image plane width"))
+        .transpose()?;
+    let end_basis = camera_data
+        .end_pose
+        .as_ref()
+        .map(|end_pose| basis(P3::from(end_pose.position.0), &end_pose.orientation));
+
+    let mut camera: Box<dyn Camera> = match &camera_data.camera_type {
+        CameraType::Perspective(perspective) => {
+            let position = P3::from(perspective.position.0);
+            let (camera, _) = perspective.camera(
+                name,
+                basis(position, &perspective.orientation),
+                image_plane_width,
+                aspect_ratio,
+            )?;
+            Box::new(camera)
+        }
+        CameraType::Stereo(stereo) => {
+            let rig = stereo.rig(name)?;
+            let perspective = &stereo.perspective;
+            let position = P3::from(perspective.position.0);
+            let center = basis(position, &perspective.orientation);
+            let [left, right] = [Eye::Left, Eye::Right].map(|eye| {
+                let (mut camera, focal_length) = perspective.camera(
+                    name,
+                    rig.eye_basis(&center, eye),
+                    image_plane_width,
+                    aspect_ratio,
+                )?;
+                let base = camera.camera_base_mut();
+                base.shift_window(rig.window_shift(eye, focal_length));
+                if let Some(end_basis) = &end_basis {
+                    base.set_end_pose(rig.eye_basis(end_basis, eye));
+                }
+                camera.set_image_pixels(settings.image_width(), settings.image_height());
+                Ok::<_, Box<dyn std::error::Error>>(Box::new(camera) as Box<dyn Camera>)
+            });
+            return Ok(View::Stereo {
+                name: name.to_string(),
+                left: left?,
+                right: right?,
+                layout: stereo.layout.into(),
+            });
+        }
+        CameraType::Orthographic(orthographic) => {
+            let position = P3::from(orthographic.position.0);
+            Box::new(OrthographicCamera::new(
+                basis(position, &orthographic.orientation),
+                image_plane_width,
+                aspect_ratio,
+            ))
+        }
+        CameraType::Realistic(realistic) => {
+            let position = P3::from(realistic.position.0);
+            let lens_scale = positive(realistic.lens_scale, name, "lens scale")?;
+            let lens_path = Path::new(scene_data_path).join(&realistic.lens_path);
+            let mut elements = std::fs::File::open(&lens_path)
+                .and_then(|file| {
+                    LensElement::read_prescription(std::io::BufReader::new(file), lens_scale)
+                })
+                .map_err(|e| {
+                    invalid_data(format!(
+                        "failed to load the lens of camera {} from {}: {}",
+                        name,
+                        lens_path.display(),
+                        e
+                    ))
+                })?;
+            if let Some(diameter) = realistic.aperture_diameter {
+                let radius = positive(diameter, name, "aperture diameter")? * lens_scale / 2.0;
+                let stop = elements
+                    .iter_mut()
+                    .find(|element| element.curvature_radius == 0.0)
+                    .ok_or_else(|| {
+                        invalid_data(format!("the lens of camera {} has no aperture stop", name))
+                    })?;
+                if radius > stop.aperture_radius {
+                    return Err(invalid_data(format!(
+                        "camera {} has an aperture wider than its lens allows",
+                        name
+                    )));
+                }
+                stop.aperture_radius = radius;
+            }
+
+            let focus_distance = match (&realistic.orientation, realistic.focus_distance) {
+                (_, Some(distance)) => positive(distance, name, "focus distance")?,
+                (CameraOrientation::LookAtPoint { lookat_point }, None) => {
+                    (lookat_point.0 - realistic.position.0).norm()
+                }
+                (CameraOrientation::ViewDir { .. }, None) => {
+                    return Err(invalid_data(format!(
+                        "camera {} needs a focus distance to focus without a look-at point",
+                        name
                     )))
                 }
+            };
+            let film_diagonal = positive(realistic.film_diagonal, name, "film diagonal")?;
+            Box::new(
+                RealisticCamera::new(
+                    basis(position, &realistic.orientation),
+                    elements,
+                    film_diagonal * lens_scale,
+                    aspect_ratio,
+                    focus_distance,
+                )
+                .map_err(|e| invalid_data(format!("camera {}: {}", name, e)))?,
+            )
+        }
+        CameraType::Equirectangular(equirectangular) => {
+            let position = P3::from(equirectangular.position.0);
+            Box::new(EquirectangularCamera::new(
+                basis(position, &equirectangular.orientation),
+                aspect_ratio,
+            ))
+        }
+        CameraType::Cylindrical(cylindrical) => {
+            let position = P3::from(cylindrical.position.0);
+            if cylindrical.horizontal_fov <= 0.0 || cylindrical.horizontal_fov > 360.0 {
+                return Err(invalid_data(format!(
+                    "camera {} needs a horizontal field of view between 0 and 360 degrees",
+                    name
+                )));
             }
-        } else {
-            Arc::clone(&normal_shader)
-        };
+            if cylindrical.vertical_fov <= 0.0 || cylindrical.vertical_fov >= 180.0 {
+                return Err(invalid_data(format!(
+                    "camera {} needs a vertical field of view between 0 and 180 degrees",
+                    name
+                )));
+            }
+            Box::new(CylindricalCamera::new(
+                basis(position, &cylindrical.orientation),
+                aspect_ratio,
+                PI * cylindrical.horizontal_fov / 180.0,
+                PI * cylindrical.vertical_fov / 180.0,
+            ))
+        }
+        CameraType::Fisheye(fisheye) => {
+            let position = P3::from(fisheye.position.0);
+            if fisheye.fov <= 0.0 || fisheye.fov > 360.0 {
+                return Err(invalid_data(format!(
+                    "camera {} needs a field of view between 0 and 360 degrees",
+                    name
+                )));
+            }
+            let projection = match fisheye.projection {
+                FisheyeProjectionData::Equidistant => FisheyeProjection::Equidistant,
+                FisheyeProjectionData::Equisolid => FisheyeProjection::Equisolid,
+            };
+            Box::new(FisheyeCamera::new(
+                basis(position, &fisheye.orientation),
+                aspect_ratio,
+                PI * fisheye.fov / 180.0,
+                projection,
+            ))
+        }
+    };
 
-        let shape_name = Box::leak(shape.name.clone().into_boxed_str());
-        if !shape_names.insert(shape_name) {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "shape names must be unique",
+    if let Some(end_basis) = end_basis {
+        camera.camera_base_mut().set_end_pose(end_basis);
+    }
+
+    // Set image size
+    camera.set_image_pixels(settings.image_width(), settings.image_height());
+    Ok(View::Mono {
+        name: name.to_string(),
+        camera,
+    })
+}
+
+fn invalid_data(message: impl Into<String>) -> Box<dyn std::error::Error> {
+    Box::new(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.into(),
+    ))
+}
+
+fn nonzero(vector: V3, name: &str, field: &str) -> Result<V3, Box<dyn std::error::Error>> {
+    if vector.norm() < VERY_SMALL_NUMBER {
+        return Err(invalid_data(format!(
+            "{} must have a non-zero {}",
+            name, field
+        )));
+    }
+    Ok(vector)
+}
+
+fn positive(value: Real, name: &str, field: &str) -> Result<Real, Box<dyn std::error::Error>> {
+    if value <= 0.0 {
+        return Err(invalid_data(format!(
+            "{} must have a positive {}",
+            name, field
+        )));
+    }
+    Ok(value)
+}
+
+/// What shapes can reference while they are being created
+struct ShapeContext<'a> {
+    scene_data_path: &'a str,
+    /// Image paths of the scene's textures by name
+    textures: HashMap<&'a str, &'a str>,
+    shaders: &'a HashMap<String, Arc<dyn Shader>>,
+    instances: HashMap<String, Arc<dyn Shape>>,
+}
+
+impl ShapeContext<'_> {
+    /// Creates the instance prototype `name` after creating any prototype it depends on
+    fn create_prototype(
+        &mut self,
+        name: &str,
+        prototypes: &HashMap<&str, &ShapeData>,
+        dependents: &mut Vec<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.instances.contains_key(name) {
+            return Ok(());
+        }
+        if dependents.iter().any(|dependent| dependent == name) {
+            return Err(invalid_data(format!(
+                "instance {} is built from itself",
+                name
             )));
         }
+
+        let shape = prototypes
+            .get(name)
+            .ok_or_else(|| invalid_data(format!("instance {} is not defined", name)))?;
+        dependents.push(name.to_string());
+        for reference in shape.prototype_references() {
+            self.create_prototype(reference, prototypes, dependents)?;
+        }
+        dependents.pop();
+
+        let instance_name = Box::leak(shape.name.clone().into_boxed_str());
+        let prototype = self.create_shape(shape, instance_name, None)?;
+        self.instances.insert(name.to_string(), prototype);
+        Ok(())
+    }
+
+    fn mesh_refinement(
+        &self,
+        mesh: &MeshData,
+        name: &str,
+    ) -> Result<MeshRefinement, Box<dyn std::error::Error>> {
+        let subdivision = match &mesh.subdivision {
+            Some(subdivision) if subdivision.levels > MAX_SUBDIVISION_LEVELS => {
+                return Err(invalid_data(format!(
+                    "{} can be subdivided at most {} times",
+                    name, MAX_SUBDIVISION_LEVELS
+                )))
+            }
+            Some(subdivision) => Some(Subdivision {
+                scheme: match subdivision.scheme {
+                    SubdivisionSchemeData::Loop => SubdivisionScheme::Loop,
+                    SubdivisionSchemeData::CatmullClark => SubdivisionScheme::CatmullClark,
+                },
+                levels: subdivision.levels,
+            }),
+            None => None,
+        };
+
+        let displacement = match &mesh.displacement {
+            Some(displacement) => {
+                let image_path = self
+                    .textures
+                    .get(displacement.texture.as_str())
+                    .ok_or_else(|| {
+                        invalid_data(format!(
+                            "{} references non-existent texture {}",
+                            name, displacement.texture
+                        ))
+                    })?;
+                let image_path = Path::new(&self.scene_data_path).join(image_path);
+                let texture =
+                    GrayscaleTexture::open(&image_path.to_string_lossy()).map_err(|e| {
+                        invalid_data(format!(
+                            "failed to load texture {} from {}: {}",
+                            displacement.texture,
+                            image_path.display(),
+                            e
+                        ))
+                    })?;
+                Some(Displacement {
+                    texture,
+                    scale: displacement.scale,
+                })
+            }
+            None => None,
+        };
+
+        Ok(MeshRefinement {
+            subdivision,
+            displacement,
+        })
+    }
+
+    /// `default_shader` is used when `shape` does not reference a shader and needs one
+    fn create_shape(
+        &self,
+        shape: &ShapeData,
+        name: &'static str,
+        default_shader: Option<Arc<dyn Shader>>,
+    ) -> Result<Arc<dyn Shape>, Box<dyn std::error::Error>> {
+        let shader = match &shape.shader {
+            Some(shader) => Some(Arc::clone(self.shaders.get(shader.name()).ok_or_else(
+                || {
+                    invalid_data(format!(
+                        "{} references non-existent shader {}",
+                        name,
+                        shader.name()
+                    ))
+                },
+            )?)),
+            None => None,
+        };
+        // meshes and instances have their own fallbacks, so they only use a shader they are
+        // given explicitly
+        let shader_or_default = shader.clone().or(default_shader);
+        let required_shader = || {
+            shader_or_default
+                .clone()
+                .ok_or_else(|| invalid_data(format!("{} has no shader", name)))
+        };
+
         let shape: Arc<dyn Shape> = match &shape.shape {
             ShapeType::Sphere(sphere) => Arc::new(Sphere::new(
                 P3::from(sphere.center.0),
                 sphere.radius,
-                shader,
-                shape_name,
+                required_shader()?,
+                name,
             )),
             ShapeType::Box(cuboid) => Arc::new(match cuboid {
                 BoxData::MinMaxPoint {
@@ -590,126 +1786,390 @@ pub fn parse_scene(
                 } => Cuboid::new(
                     P3::from(min_point.0),
                     P3::from(max_point.0),
-                    shader,
-                    shape_name,
+                    required_shader()?,
+                    name,
                 ),
                 BoxData::CenterExtent { center, extent } => {
                     let center = P3::from(center.0);
                     let half_extent = extent.0 / 2.0;
                     let min_point = center - half_extent;
                     let max_point = center + half_extent;
-                    Cuboid::new(min_point, max_point, shader, shape_name)
+                    Cuboid::new(min_point, max_point, required_shader()?, name)
                 }
             }),
             ShapeType::Triangle(triangle) => Arc::new(Triangle::new(
                 P3::from(triangle.a.0),
                 P3::from(triangle.b.0),
                 P3::from(triangle.c.0),
-                shader,
-                shape_name,
+                required_shader()?,
+                name,
             )),
             ShapeType::Mesh(mesh) => {
                 // TODO: this should be done differently
                 let model_path = String::from(
-                    Path::new(&scene_data_path)
+                    Path::new(&self.scene_data_path)
                         .join(&mesh.model_path)
                         .to_str()
                         .expect("failed to convert model path to string"),
                 );
-                Arc::new(Mesh::new(model_path, shader, shape_name))
+                let refinement = self.mesh_refinement(mesh, name)?;
+                Arc::new(Mesh::new(model_path, shader, &refinement, name).map_err(invalid_data)?)
             }
             ShapeType::Instance(instance) => {
-                let shape = instances
-                    .get(&instance.instance_of)
-                    .expect("instance ID is not a valid instance")
-                    .clone();
-                let mut translate = V3::default();
-                let mut scale = V3::new(1.0, 1.0, 1.0);
-                let mut rotate = (
-                    Rotation3::identity(),
-                    Rotation3::identity(),
-                    Rotation3::identity(),
-                );
-                for transformation in instance.transform.iter() {
-                    match transformation {
-                        TransformData::Translate { amount } => translate += amount.0,
-                        TransformData::Scale { amount } => scale.component_mul_assign(&amount.0),
-                        TransformData::Rotate { axis, degrees } => {
-                            let angle = PI * degrees / 180.0;
-                            match axis {
-                                RotationAxis::X => {
-                                    rotate.0 = Rotation3::from_axis_angle(&V3::x_axis(), angle)
-                                }
-                                RotationAxis::Y => {
-                                    rotate.1 = Rotation3::from_axis_angle(&V3::y_axis(), angle)
-                                }
-                                RotationAxis::Z => {
-                                    rotate.2 = Rotation3::from_axis_angle(&V3::z_axis(), angle)
-                                }
-                            };
-                        }
-                    }
-                }
+                let prototype = self.instances.get(&instance.instance_of).ok_or_else(|| {
+                    invalid_data(format!(
+                        "{} is an instance of {}, which is not defined",
+                        name, instance.instance_of
+                    ))
+                })?;
+
+                let to_transform = |transform: &[TransformData]| {
+                    let ops = transform
+                        .iter()
+                        .map(TransformData::to_op)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(invalid_data)?;
+                    Transform::from_ops(&ops).ok_or_else(|| {
+                        invalid_data(format!(
+                            "the transform applied to {} is not invertible",
+                            name
+                        ))
+                    })
+                };
 
-                let rotation = rotate.2 * rotate.1 * rotate.0;
+                let keyframes = match (&instance.end_transform, instance.keyframes.as_slice()) {
+                    (None, []) => vec![(0.0, to_transform(&instance.transform)?)],
+                    (Some(end_transform), []) => vec![
+                        (0.0, to_transform(&instance.transform)?),
+                        (1.0, to_transform(end_transform)?),
+                    ],
+                    (None, keyframes) if instance.transform.is_empty() => keyframes
+                        .iter()
+                        .map(|keyframe| Ok((keyframe.time, to_transform(&keyframe.transform)?)))
+                        .collect::<Result<_, Box<dyn std::error::Error>>>()?,
+                    _ => {
+                        return Err(invalid_data(format!(
+                            "{} has keyframes and another transform",
+                            name
+                        )))
+                    }
+                };
 
-                Arc::new(Instance::new(
-                    shape,
-                    Translation3::from(translate),
-                    rotation,
-                    Scale3::from(scale),
-                    shader,
-                    shape_name,
+                Arc::new(match keyframes.as_slice() {
+                    [(_, transform)] => {
+                        Instance::new(prototype.clone(), transform.clone(), shader, name)
+                    }
+                    _ => Instance::animated(
+                        prototype.clone(),
+                        AnimatedTransform::new(keyframes),
+                        shader,
+                        name,
+                    ),
+                })
+            }
+            ShapeType::Plane(plane) => {
+                let size = match (plane.width, plane.length) {
+                    (Some(width), Some(length)) => Some((width, length)),
+                    (None, None) => None,
+                    _ => {
+                        return Err(invalid_data(format!(
+                            "plane {} needs both a width and a length to be bounded",
+                            name
+                        )))
+                    }
+                };
+                Arc::new(Plane::new(
+                    P3::from(plane.center.0),
+                    nonzero(plane.normal.0, name, "normal")?,
+                    size,
+                    required_shader()?,
+                    name,
                 ))
             }
-        };
-        shapes.push(shape);
-    }
-
-    // Create lights
-    let mut lights: Vec<Box<dyn crate::light::Light>> = Vec::new();
-    for light in scene.lights.iter() {
-        let light: Box<dyn Light> = match &light.light_type {
-            LightType::Ambient(ambient_light) => {
-                Box::new(AmbientLight::new(ambient_light.intensity.0))
+            ShapeType::Disk(disk) => {
+                if disk.inner_radius < 0.0 || disk.inner_radius >= disk.radius {
+                    return Err(invalid_data(format!(
+                        "disk {} must have an inner radius between 0 and its radius",
+                        name
+                    )));
+                }
+                Arc::new(Disk::new(
+                    P3::from(disk.center.0),
+                    nonzero(disk.normal.0, name, "normal")?,
+                    disk.radius,
+                    disk.inner_radius,
+                    required_shader()?,
+                    name,
+                ))
             }
-            LightType::Point(point_light) => Box::new(PointLight::new(
-                P3::from(point_light.position.0),
-                point_light.intensity.0,
+            ShapeType::Cylinder(cylinder) => Arc::new(Cylinder::new(
+                P3::from(cylinder.base.0),
+                nonzero(cylinder.axis.0, name, "axis")?,
+                positive(cylinder.radius, name, "radius")?,
+                positive(cylinder.height, name, "height")?,
+                cylinder.capped,
+                required_shader()?,
+                name,
             )),
-            _ => unimplemented!("light type not implemented yet"),
-        };
-        lights.push(light);
-    }
+            ShapeType::Cone(cone) => Arc::new(Cone::new(
+                P3::from(cone.base.0),
+                nonzero(cone.axis.0, name, "axis")?,
+                positive(cone.radius, name, "radius")?,
+                positive(cone.height, name, "height")?,
+                cone.capped,
+                required_shader()?,
+                name,
+            )),
+            ShapeType::Torus(torus) => Arc::new(Torus::new(
+                P3::from(torus.center.0),
+                nonzero(torus.axis.0, name, "axis")?,
+                positive(torus.major_radius, name, "major radius")?,
+                positive(torus.minor_radius, name, "minor radius")?,
+                required_shader()?,
+                name,
+            )),
+            ShapeType::Csg(csg) => {
+                let operation = match csg.operation {
+                    CsgOperationData::Union => CsgOperation::Union,
+                    CsgOperationData::Intersection => CsgOperation::Intersection,
+                    CsgOperationData::Difference => CsgOperation::Difference,
+                };
+                if csg.shapes.len() < 2 {
+                    return Err(invalid_data(format!(
+                        "{} needs at least two shapes to combine",
+                        name
+                    )));
+                }
 
-    // get background color
-    let background_color = if render_normals {
-        color!(0.0, 0.0, 0.0)
-    } else if let Some(background) = scene.scene_parameters.background {
-        match background {
-            Background::BackgroundColor { background_color } => background_color.0,
-            Background::EnvMap(_) => {
-                unimplemented!("environment maps aren't implemented yet")
+                let mut operands =
+                    csg.shapes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, operand)| match operand {
+                            CsgOperand::Reference(prototype) => {
+                                self.instances.get(prototype).cloned().ok_or_else(|| {
+                                    invalid_data(format!(
+                                        "{} combines {}, which is not defined",
+                                        name, prototype
+                                    ))
+                                })
+                            }
+                            CsgOperand::Inline(child) => {
+                                let child_name = match child.name.is_empty() {
+                                    true => format!("{}/{}", name, i),
+                                    false => child.name.clone(),
+                                };
+                                let child_name = Box::leak(child_name.into_boxed_str());
+                                self.create_shape(child, child_name, shader_or_default.clone())
+                            }
+                        });
+                let mut combined = operands.next().expect("checked above")?;
+                for operand in operands {
+                    combined = Arc::new(Csg::new(
+                        operation,
+                        combined,
+                        operand?,
+                        shader_or_default.clone(),
+                        name,
+                    ));
+                }
+                combined
             }
-        }
-    } else {
-        DEFAULT_BACKGROUND_COLOR
-    };
+            ShapeType::Quadric(quadric) => {
+                let coefficients: [Real; 10] =
+                    quadric.coefficients.as_slice().try_into().map_err(|_| {
+                        invalid_data(format!("quadric {} needs 10 coefficients", name))
+                    })?;
+                let bounds = BBox::new(P3::from(quadric.min.0), P3::from(quadric.max.0));
+                Arc::new(Quadric::new(coefficients, bounds, required_shader()?, name))
+            }
+            ShapeType::Ellipsoid(ellipsoid) => {
+                if ellipsoid.radii.0.iter().any(|r| *r <= 0.0) {
+                    return Err(invalid_data(format!("{} must have positive radii", name)));
+                }
+                Arc::new(Quadric::ellipsoid(
+                    P3::from(ellipsoid.center.0),
+                    ellipsoid.radii.0,
+                    required_shader()?,
+                    name,
+                ))
+            }
+            ShapeType::Paraboloid(paraboloid) => {
+                let radius = positive(paraboloid.radius, name, "radius")?;
+                Arc::new(Quadric::paraboloid(
+                    P3::from(paraboloid.center.0),
+                    (radius, radius),
+                    positive(paraboloid.height, name, "height")?,
+                    required_shader()?,
+                    name,
+                ))
+            }
+            ShapeType::Hyperboloid(hyperboloid) => {
+                if hyperboloid.radii.0.iter().any(|r| *r <= 0.0) {
+                    return Err(invalid_data(format!("{} must have positive radii", name)));
+                }
+                Arc::new(Quadric::hyperboloid(
+                    P3::from(hyperboloid.center.0),
+                    hyperboloid.radii.0,
+                    positive(hyperboloid.height, name, "height")?,
+                    hyperboloid.two_sheets,
+                    required_shader()?,
+                    name,
+                ))
+            }
+            ShapeType::Heightfield(heightfield) => {
+                let image_path = Path::new(&self.scene_data_path).join(&heightfield.image_path);
+                Arc::new(
+                    Heightfield::from_image(
+                        &image_path.to_string_lossy(),
+                        P3::from(heightfield.center.0),
+                        heightfield.size.0,
+                        required_shader()?,
+                        name,
+                    )
+                    .map_err(|e| {
+                        invalid_data(format!(
+                            "failed to load heightfield {} from {}: {}",
+                            name,
+                            image_path.display(),
+                            e
+                        ))
+                    })?,
+                )
+            }
+            ShapeType::Curves(curves) => {
+                let mut strands: Vec<Strand> = curves
+                    .strands
+                    .iter()
+                    .map(|strand| Strand {
+                        points: strand.points.iter().map(|p| P3::from(p.0)).collect(),
+                        radii: (strand.radius, strand.tip_radius.unwrap_or(strand.radius)),
+                    })
+                    .collect();
+                if let Some(strand_path) = &curves.strand_path {
+                    let strand_path = Path::new(&self.scene_data_path).join(strand_path);
+                    strands.extend(Strand::read_file(&strand_path.to_string_lossy()).map_err(
+                        |e| invalid_data(format!("failed to load strands of {}: {}", name, e)),
+                    )?);
+                }
 
-    let shape_refs = shapes.clone();
-    let bvh = BVH::new(shape_refs);
+                if strands.is_empty() {
+                    return Err(invalid_data(format!("{} has no strands", name)));
+                }
+                if strands
+                    .iter()
+                    .any(|strand| strand.radii.0 <= 0.0 || strand.radii.1 < 0.0)
+                {
+                    return Err(invalid_data(format!(
+                        "{} must have a positive strand radius",
+                        name
+                    )));
+                }
 
-    let scene = Scene {
-        disable_shadows,
-        background_color,
-        camera,
-        shapes,
-        shaders,
-        lights,
-        bvh,
-        recursion_depth: recursion_depth.unwrap_or(DEFAULT_RECURSION_DEPTH),
-        image_width,
-        image_height,
-    };
-    return Ok(scene);
+                let mode = match curves.mode {
+                    CurveModeData::Ribbon => CurveMode::Ribbon,
+                    CurveModeData::Tube => CurveMode::Tube,
+                };
+                Arc::new(
+                    Curves::new(&strands, mode, required_shader()?, name).map_err(invalid_data)?,
+                )
+            }
+            ShapeType::PointCloud(cloud) => {
+                let point_path = Path::new(&self.scene_data_path).join(&cloud.point_path);
+                let points = Points::open(&point_path.to_string_lossy()).map_err(|e| {
+                    invalid_data(format!(
+                        "failed to load points of {} from {}: {}",
+                        name,
+                        point_path.display(),
+                        e
+                    ))
+                })?;
+                let primitive = match cloud.primitive {
+                    PointPrimitiveData::Sphere => PointPrimitive::Sphere,
+                    PointPrimitiveData::Disk => PointPrimitive::Disk,
+                };
+                Arc::new(
+                    PointCloud::new(
+                        points,
+                        positive(cloud.radius, name, "radius")?,
+                        primitive,
+                        required_shader()?,
+                        name,
+                    )
+                    .map_err(invalid_data)?,
+                )
+            }
+            ShapeType::Sdf(sdf) => {
+                let root = sdf
+                    .to_node()
+                    .map_err(|e| invalid_data(format!("{}: {}", name, e)))?;
+                Arc::new(Sdf::new(root, required_shader()?, name))
+            }
+        };
+        Ok(shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_scene;
+
+    #[test]
+    fn test_perspective_camera_framing() {
+        let scene_json = r#"{"scene": {
+            "sceneParameters": {"width": 200, "height": 100, "allCameras": true},
+            "camera": [
+                {"_name": "plane", "_type": "perspective", "position": "0 0 0",
+                    "lookatPoint": "0 0 -1", "imagePlaneWidth": 2, "focalLength": 1},
+                {"_name": "hfov", "_type": "perspective", "position": "0 0 0",
+                    "lookatPoint": "0 0 -1", "hfov": 90},
+                {"_name": "vfov", "_type": "perspective", "position": "0 0 0",
+                    "lookatPoint": "0 0 -1", "vfov": 90},
+                {"_name": "up", "_type": "perspective", "position": "0 0 0",
+                    "lookatPoint": "0 0 -1", "vfov": 90, "up": "1 0 0"},
+                {"_name": "roll", "_type": "perspective", "position": "0 0 0",
+                    "lookatPoint": "0 0 -1", "vfov": 90, "roll": 90}
+            ],
+            "shape": []
+        }}"#;
+        let scene = parse_scene(scene_json, "", &RenderSettings::default()).unwrap();
+        let camera = |name: &str| {
+            let view = scene.views.iter().find(|view| view.name() == name);
+            view.unwrap().main_camera()
+        };
+        // through the middle of the right and the top edge of the image
+        let edges = |name: &str| {
+            let camera = camera(name);
+            let right = camera.generate_ray(200, 50, 0.0, 0.0, 0.0).unwrap();
+            let top = camera.generate_ray(100, 100, 0.0, 0.0, 0.0).unwrap();
+            (right.direction, top.direction)
+        };
+        let close = |a: V3, b: V3| (a.normalize() - b.normalize()).norm() < 1e-9;
+
+        // the image plane is 2 by 1, one unit in front of the camera
+        let (right, top) = edges("plane");
+        assert!((right - V3::new(1.0, 0.0, -1.0)).norm() < 1e-9);
+        assert!((top - V3::new(0.0, 0.5, -1.0)).norm() < 1e-9);
+
+        let (right, top) = edges("hfov");
+        assert!(close(right, V3::new(1.0, 0.0, -1.0)));
+        assert!(close(top, V3::new(0.0, 0.5, -1.0)));
+        let (right, top) = edges("vfov");
+        assert!(close(right, V3::new(2.0, 0.0, -1.0)));
+        assert!(close(top, V3::new(0.0, 1.0, -1.0)));
+
+        // the top of the image faces the up direction, which the image plane is turned to
+        let basis = &camera("up").camera_base().basis;
+        assert!(close(basis.v, V3::x()) && close(basis.u, -V3::y()));
+        let (right, top) = edges("up");
+        assert!(close(right, V3::new(0.0, -2.0, -1.0)));
+        assert!(close(top, V3::new(1.0, 0.0, -1.0)));
+
+        // a quarter turn counterclockwise turns the top of the image to the left
+        let basis = &camera("roll").camera_base().basis;
+        assert!(close(basis.v, -V3::x()) && close(basis.u, V3::y()));
+        let (_, top) = edges("roll");
+        assert!(close(top, V3::new(-1.0, 0.0, -1.0)));
+    }
 }
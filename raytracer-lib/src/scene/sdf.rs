@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{geometry::SdfNode, prelude::*, V3};
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(super) struct SdfData {
+    #[serde(alias = "sdf")]
+    root: SdfNodeData,
+}
+
+impl SdfData {
+    pub(super) fn to_node(&self) -> Result<SdfNode, String> {
+        self.root.to_node()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+enum SdfNodeData {
+    Sphere {
+        center: W<V3>,
+        radius: Real,
+    },
+    Box {
+        center: W<V3>,
+        extent: W<V3>,
+        #[serde(default)]
+        rounding: Real,
+    },
+    Torus {
+        center: W<V3>,
+        #[serde(alias = "majorRadius")]
+        major_radius: Real,
+        #[serde(alias = "minorRadius")]
+        minor_radius: Real,
+    },
+    Capsule {
+        a: W<V3>,
+        b: W<V3>,
+        radius: Real,
+    },
+    Union {
+        #[serde(alias = "nodes")]
+        children: Vec<SdfNodeData>,
+        #[serde(alias = "blend", default)]
+        smoothness: Real,
+    },
+    /// Rotation around the y axis per unit of height
+    Twist {
+        child: Box<SdfNodeData>,
+        #[serde(alias = "amount")]
+        degrees: Real,
+    },
+    Repeat {
+        child: Box<SdfNodeData>,
+        spacing: W<V3>,
+        /// Number of copies on each side of the original along each axis
+        limit: W<V3>,
+    },
+    Displace {
+        child: Box<SdfNodeData>,
+        amplitude: Real,
+        frequency: Real,
+    },
+}
+
+impl SdfNodeData {
+    fn to_node(&self) -> Result<SdfNode, String> {
+        Ok(match self {
+            SdfNodeData::Sphere { center, radius } => SdfNode::Sphere {
+                center: P3::from(center.0),
+                radius: *radius,
+            },
+            SdfNodeData::Box {
+                center,
+                extent,
+                rounding,
+            } => {
+                if *rounding < 0.0 || 2.0 * rounding > extent.0.min() {
+                    return Err("sdf box rounding must fit inside the box".to_string());
+                }
+                SdfNode::Box {
+                    center: P3::from(center.0),
+                    extent: extent.0,
+                    rounding: *rounding,
+                }
+            }
+            SdfNodeData::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => SdfNode::Torus {
+                center: P3::from(center.0),
+                major_radius: *major_radius,
+                minor_radius: *minor_radius,
+            },
+            SdfNodeData::Capsule { a, b, radius } => SdfNode::Capsule {
+                a: P3::from(a.0),
+                b: P3::from(b.0),
+                radius: *radius,
+            },
+            SdfNodeData::Union {
+                children,
+                smoothness,
+            } => {
+                if children.is_empty() {
+                    return Err("sdf union needs at least one child".to_string());
+                }
+                SdfNode::Union {
+                    children: children
+                        .iter()
+                        .map(SdfNodeData::to_node)
+                        .collect::<Result<_, _>>()?,
+                    smoothness: *smoothness,
+                }
+            }
+            SdfNodeData::Twist { child, degrees } => SdfNode::Twist {
+                child: Box::new(child.to_node()?),
+                rate: PI * degrees / 180.0,
+            },
+            SdfNodeData::Repeat {
+                child,
+                spacing,
+                limit,
+            } => {
+                let child = child.to_node()?;
+                SdfNode::Repeat {
+                    center: child.bbox().centroid,
+                    child: Box::new(child),
+                    spacing: spacing.0,
+                    limit: limit.0.map(|limit| limit.max(0.0).round()),
+                }
+            }
+            SdfNodeData::Displace {
+                child,
+                amplitude,
+                frequency,
+            } => SdfNode::Displace {
+                child: Box::new(child.to_node()?),
+                amplitude: *amplitude,
+                frequency: *frequency,
+            },
+        })
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    antialias::AntialiasMethod,
+    aov::Aov,
+    prelude::*,
+    tonemap::{DisplayTransform, ToneMapping, TransferFunction},
+};
+
+/// Options controlling how a scene is rendered.
+///
+/// Every field is optional so that settings can be layered: values from the scene file's
+/// `sceneParameters` are overridden by whatever the CLI or WASM caller passes in (see
+/// [`RenderSettings::with_overrides`]), and anything left unset falls back to the defaults in
+/// [`public_consts`](crate::public_consts) through the accessor methods.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct RenderSettings {
+    #[serde(alias = "width", alias = "imageWidth")]
+    pub image_width: Option<u32>,
+    #[serde(alias = "height", alias = "imageHeight")]
+    pub image_height: Option<u32>,
+    #[serde(alias = "aspectRatio")]
+    pub aspect_ratio: Option<Real>,
+    #[serde(alias = "raysPerPixel")]
+    pub rays_per_pixel: Option<u16>,
+    #[serde(alias = "recursionDepth")]
+    pub recursion_depth: Option<u16>,
+    #[serde(alias = "antialiasMethod")]
+    pub antialias_method: Option<AntialiasMethod>,
+    #[serde(alias = "disableShadows")]
+    pub disable_shadows: Option<bool>,
+    #[serde(alias = "renderNormals")]
+    pub render_normals: Option<bool>,
+    /// Start of the time interval rays are traced in, for motion blur
+    #[serde(alias = "shutterOpen")]
+    pub shutter_open: Option<Real>,
+    #[serde(alias = "shutterClose")]
+    pub shutter_close: Option<Real>,
+    /// Names of the cameras to render, each to images of its own, instead of the single one
+    /// picked by `sceneParameters.camera`
+    pub cameras: Option<Vec<String>>,
+    /// Renders every camera of the scene, overriding `cameras`
+    #[serde(alias = "allCameras")]
+    pub all_cameras: Option<bool>,
+    /// Frame of the animation to render, cameras with keyframes start at their first one
+    pub frame: Option<Real>,
+    /// Brightens low dynamic range output by this many stops before tone mapping
+    pub exposure: Option<Real>,
+    #[serde(alias = "toneMapping", alias = "tonemap")]
+    pub tone_mapping: Option<ToneMapping>,
+    /// Encoding of low dynamic range output
+    pub transfer: Option<TransferFunction>,
+    /// Dithers low dynamic range output to hide banding
+    pub dither: Option<bool>,
+    /// Buffers to render alongside each image
+    pub aovs: Option<Vec<Aov>>,
+    /// Leaves the background out of the image, so it can be composited using its alpha
+    #[serde(alias = "transparentBackground")]
+    pub transparent_background: Option<bool>,
+}
+
+impl RenderSettings {
+    /// Layers `overrides` on top of `self`, any field set in `overrides` wins.
+    pub fn with_overrides(&self, overrides: &RenderSettings) -> RenderSettings {
+        RenderSettings {
+            image_width: overrides.image_width.or(self.image_width),
+            image_height: overrides.image_height.or(self.image_height),
+            aspect_ratio: overrides.aspect_ratio.or(self.aspect_ratio),
+            rays_per_pixel: overrides.rays_per_pixel.or(self.rays_per_pixel),
+            recursion_depth: overrides.recursion_depth.or(self.recursion_depth),
+            antialias_method: overrides.antialias_method.or(self.antialias_method),
+            disable_shadows: overrides.disable_shadows.or(self.disable_shadows),
+            render_normals: overrides.render_normals.or(self.render_normals),
+            shutter_open: overrides.shutter_open.or(self.shutter_open),
+            shutter_close: overrides.shutter_close.or(self.shutter_close),
+            cameras: overrides.cameras.clone().or_else(|| self.cameras.clone()),
+            all_cameras: overrides.all_cameras.or(self.all_cameras),
+            frame: overrides.frame.or(self.frame),
+            exposure: overrides.exposure.or(self.exposure),
+            tone_mapping: overrides.tone_mapping.or(self.tone_mapping),
+            transfer: overrides.transfer.or(self.transfer),
+            dither: overrides.dither.or(self.dither),
+            aovs: overrides.aovs.clone().or_else(|| self.aovs.clone()),
+            transparent_background: overrides
+                .transparent_background
+                .or(self.transparent_background),
+        }
+    }
+
+    /// Checks that the settings can be rendered with.
+    pub fn validate(&self) -> Result<(), String> {
+        let rays_per_pixel = self.rays_per_pixel();
+        if rays_per_pixel == 0 {
+            return Err("rays_per_pixel must be at least 1".to_string());
+        }
+        let sqrt_rays_per_pixel = self.sqrt_rays_per_pixel();
+        if sqrt_rays_per_pixel * sqrt_rays_per_pixel != rays_per_pixel {
+            return Err("rays_per_pixel must be a perfect square".to_string());
+        }
+
+        if self.image_width() == 0 || self.image_height() == 0 {
+            return Err("image width and height must be non-zero".to_string());
+        }
+
+        let (open, close) = self.shutter();
+        if open > close {
+            return Err("shutter must close after it opens".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn image_width(&self) -> u32 {
+        self.image_width.unwrap_or(DEFAULT_IMAGE_WIDTH)
+    }
+
+    pub fn image_height(&self) -> u32 {
+        self.image_height.unwrap_or(DEFAULT_IMAGE_HEIGHT)
+    }
+
+    /// Aspect ratio of the image plane, calculated from the image size if not specified
+    pub fn aspect_ratio(&self) -> Real {
+        self.aspect_ratio
+            .unwrap_or(self.image_width() as Real / self.image_height() as Real)
+    }
+
+    pub fn rays_per_pixel(&self) -> u16 {
+        self.rays_per_pixel.unwrap_or(DEFAULT_RAYS_PER_PIXEL)
+    }
+
+    pub fn sqrt_rays_per_pixel(&self) -> u16 {
+        (self.rays_per_pixel() as f64).sqrt() as u16
+    }
+
+    pub fn recursion_depth(&self) -> u16 {
+        self.recursion_depth.unwrap_or(DEFAULT_RECURSION_DEPTH)
+    }
+
+    pub fn antialias_method(&self) -> AntialiasMethod {
+        self.antialias_method.unwrap_or(DEFAULT_ANTIALIAS_METHOD)
+    }
+
+    pub fn disable_shadows(&self) -> bool {
+        self.disable_shadows.unwrap_or(false)
+    }
+
+    pub fn render_normals(&self) -> bool {
+        self.render_normals.unwrap_or(false)
+    }
+
+    pub fn aovs(&self) -> &[Aov] {
+        self.aovs.as_deref().unwrap_or_default()
+    }
+
+    pub fn transparent_background(&self) -> bool {
+        self.transparent_background.unwrap_or(false)
+    }
+
+    pub fn all_cameras(&self) -> bool {
+        self.all_cameras.unwrap_or(false)
+    }
+
+    /// How the framebuffer is turned into low dynamic range images
+    pub fn display_transform(&self) -> DisplayTransform {
+        DisplayTransform {
+            scale: (2.0 as Real).powf(self.exposure.unwrap_or(0.0)) as f32,
+            tone_mapping: self.tone_mapping.unwrap_or(DEFAULT_TONE_MAPPING),
+            transfer: self.transfer.unwrap_or(DEFAULT_TRANSFER_FUNCTION),
+            dither: self.dither.unwrap_or(false),
+        }
+    }
+
+    /// Times the shutter opens and closes, moving shapes and cameras are at their first
+    /// keyframe at time 0 and their second at time 1
+    pub fn shutter(&self) -> (Real, Real) {
+        (
+            self.shutter_open.unwrap_or(DEFAULT_SHUTTER_OPEN),
+            self.shutter_close.unwrap_or(DEFAULT_SHUTTER_CLOSE),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_settings_precedence() {
+        let from_file: RenderSettings = serde_json::from_str(
+            r#"{ "width": 800, "height": 400, "raysPerPixel": 9, "disableShadows": true }"#,
+        )
+        .unwrap();
+        let overrides = RenderSettings {
+            image_width: Some(200),
+            disable_shadows: Some(false),
+            ..Default::default()
+        };
+
+        let settings = from_file.with_overrides(&overrides);
+        assert_eq!(settings.image_width(), 200);
+        assert_eq!(settings.image_height(), 400);
+        assert_eq!(settings.sqrt_rays_per_pixel(), 3);
+        assert!(!settings.disable_shadows());
+        assert_eq!(settings.recursion_depth(), DEFAULT_RECURSION_DEPTH);
+        assert_eq!(settings.aspect_ratio(), 0.5);
+        assert!(settings.validate().is_ok());
+
+        let settings = settings.with_overrides(&RenderSettings {
+            rays_per_pixel: Some(8),
+            ..Default::default()
+        });
+        assert!(settings.validate().is_err());
+
+        let settings = settings.with_overrides(&RenderSettings {
+            rays_per_pixel: Some(0),
+            ..Default::default()
+        });
+        assert!(settings.validate().is_err());
+    }
+}
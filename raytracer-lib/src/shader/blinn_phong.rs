@@ -21,6 +21,7 @@ impl BlinnPhongShader {
 
 impl Shader for BlinnPhongShader {
     fn apply(&self, hit: &super::Hit) -> Color {
+        let diffuse = hit.color.unwrap_or(self.diffuse);
         let mut color = color!(0.0, 0.0, 0.0);
         for (light, surface_to_light) in hit
             .scene
@@ -36,8 +37,7 @@ impl Shader for BlinnPhongShader {
             let stol_normal = surface_to_light.normalize();
             let cos_incidence = hit.normal.dot(&stol_normal);
 
-            color +=
-                self.diffuse.component_mul(&light.get_intensity()) * cos_incidence.max(0.0) as f32;
+            color += diffuse.component_mul(&light.get_intensity()) * cos_incidence.max(0.0) as f32;
 
             let half_vector = ((-hit.ray.direction.normalize()) + stol_normal).normalize();
             color += self.specular.component_mul(&light.get_intensity())
@@ -45,4 +45,8 @@ impl Shader for BlinnPhongShader {
         }
         color
     }
+
+    fn albedo(&self, hit: &super::Hit) -> Color {
+        hit.color.unwrap_or(self.diffuse)
+    }
 }
@@ -18,6 +18,7 @@ impl GGXMirrorShader {
     }
 
     // GGX/Trowbridge-Reitz distribution function
+    #[allow(dead_code)]
     fn ggx_distribution(&self, n_dot_h: Real) -> Real {
         let alpha2 = self.roughness * self.roughness;
         let nom = alpha2;
@@ -53,7 +54,7 @@ impl GGXMirrorShader {
 
 impl Shader for GGXMirrorShader {
     fn apply(&self, hit: &Hit) -> Color {
-        if hit.depth >= hit.scene.recursion_depth {
+        if hit.depth >= hit.scene.settings.recursion_depth() {
             return hit.scene.background_color;
         }
 
@@ -73,15 +74,16 @@ impl Shader for GGXMirrorShader {
                 crate::math::Ray {
                     origin: hit.hit_point(),
                     direction: outgoing.normalize(),
+                    time: hit.ray.time,
                 },
-                &hit.scene,
+                hit.scene,
             );
             mirror_hit.depth = hit.depth + 1;
             mirror_hit.t_min = VERY_SMALL_NUMBER;
 
             // Get color for this sample
             let sample_color = if hit.scene.bvh.closest_hit(&mut mirror_hit) {
-                mirror_hit.shape.unwrap().get_shader().apply(&mirror_hit)
+                mirror_hit.shader.unwrap().apply(&mirror_hit)
             } else {
                 hit.scene.background_color
             };
@@ -92,4 +94,12 @@ impl Shader for GGXMirrorShader {
         // Average the samples
         accumulated_color / self.samples as f32
     }
+
+    fn albedo(&self, _hit: &Hit) -> Color {
+        color!(1.0, 1.0, 1.0)
+    }
+
+    fn apply_direct_indirect(&self, hit: &Hit) -> (Color, Color) {
+        (Color::zeros(), self.apply(hit))
+    }
 }
@@ -1,6 +1,6 @@
 use na::Unit;
 
-use crate::{math::Ray, prelude::*, scene::Scene};
+use crate::{math::Ray, prelude::*, scene::Scene, shader::Shader};
 
 /// <'hit> lifetimes lives as long as a single pixel render takes.
 pub struct Hit<'hit> {
@@ -9,7 +9,16 @@ pub struct Hit<'hit> {
     pub depth: u16,
     pub ray: crate::math::Ray,
     pub normal: Unit<V3>,
+    /// Surface parameterization at the hit point, for shapes that have one
+    pub uv: (Real, Real),
+    /// Color of the surface at the hit point, for shapes with colored points or vertices.
+    /// Shaders use it instead of their own diffuse color. The BVH clears it when a closer
+    /// shape is hit.
+    pub color: Option<Color>,
     pub shape: Option<&'hit dyn crate::geometry::Shape>,
+    /// Shader for the surface that was hit, which is not necessarily `shape`'s shader when
+    /// `shape` is an instance
+    pub shader: Option<&'hit dyn Shader>,
     pub scene: &'hit Scene,
 }
 
@@ -21,7 +30,10 @@ impl<'hit> Hit<'hit> {
             depth: 0,
             ray,
             normal: Unit::new_unchecked(V3::default()),
+            uv: (0.0, 0.0),
+            color: None,
             shape: None,
+            shader: None,
             scene,
         }
     }
@@ -33,7 +45,10 @@ impl<'hit> Hit<'hit> {
             depth: 0,
             ray: to_light,
             normal: Unit::new_unchecked(V3::default()),
+            uv: (0.0, 0.0),
+            color: None,
             shape: None,
+            shader: None,
             scene,
         }
     }
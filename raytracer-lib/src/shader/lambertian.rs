@@ -15,6 +15,7 @@ impl LambertianShader {
 
 impl Shader for LambertianShader {
     fn apply(&self, hit: &super::Hit) -> Color {
+        let diffuse = hit.color.unwrap_or(self.diffuse);
         let mut color = color!(0.0, 0.0, 0.0);
         for (light, surface_to_light) in hit
             .scene
@@ -29,9 +30,12 @@ impl Shader for LambertianShader {
         {
             let cos_incidence = hit.normal.dot(&surface_to_light.normalize());
 
-            color +=
-                self.diffuse.component_mul(&light.get_intensity()) * cos_incidence.max(0.0) as f32;
+            color += diffuse.component_mul(&light.get_intensity()) * cos_incidence.max(0.0) as f32;
         }
         color
     }
+
+    fn albedo(&self, hit: &super::Hit) -> Color {
+        hit.color.unwrap_or(self.diffuse)
+    }
 }
@@ -18,4 +18,13 @@ pub use perfect_mirror::PerfectMirrorShader;
 
 pub trait Shader: Send + Sync + std::fmt::Debug {
     fn apply(&self, hit: &Hit) -> Color;
+
+    /// Color of the surface itself, without any lighting
+    fn albedo(&self, hit: &Hit) -> Color;
+
+    /// [`Shader::apply`] split into the light reaching the surface straight from the lights and
+    /// the light arriving through secondary rays
+    fn apply_direct_indirect(&self, hit: &Hit) -> (Color, Color) {
+        (self.apply(hit), Color::zeros())
+    }
 }
@@ -8,10 +8,14 @@ pub struct NormalShader;
 
 impl Shader for NormalShader {
     fn apply(&self, hit: &super::Hit) -> Color {
-        return color!(
+        color!(
             1.0 + hit.normal.x as f32,
             1.0 + hit.normal.y as f32,
             1.0 + hit.normal.z as f32
-        ) / 2.0;
+        ) / 2.0
+    }
+
+    fn albedo(&self, hit: &super::Hit) -> Color {
+        self.apply(hit)
     }
 }
@@ -7,4 +7,8 @@ impl super::Shader for NullShader {
     fn apply(&self, _hit: &super::Hit) -> Color {
         ERROR_COLOR
     }
+
+    fn albedo(&self, _hit: &super::Hit) -> Color {
+        ERROR_COLOR
+    }
 }
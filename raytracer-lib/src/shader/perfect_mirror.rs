@@ -1,4 +1,4 @@
-use crate::prelude::*;
+use crate::{color, prelude::*};
 
 use super::{Hit, Shader};
 
@@ -7,7 +7,7 @@ pub struct PerfectMirrorShader;
 
 impl Shader for PerfectMirrorShader {
     fn apply(&self, hit: &Hit) -> Color {
-        if hit.depth >= hit.scene.recursion_depth {
+        if hit.depth >= hit.scene.settings.recursion_depth() {
             return hit.scene.background_color;
         }
 
@@ -18,16 +18,25 @@ impl Shader for PerfectMirrorShader {
             crate::math::Ray {
                 origin: hit.hit_point(),
                 direction: outgoing,
+                time: hit.ray.time,
             },
-            &hit.scene,
+            hit.scene,
         );
         mirror_hit.depth = hit.depth + 1;
         mirror_hit.t_min = VERY_SMALL_NUMBER;
 
         if hit.scene.bvh.closest_hit(&mut mirror_hit) {
-            mirror_hit.shape.unwrap().get_shader().apply(&mirror_hit)
+            mirror_hit.shader.unwrap().apply(&mirror_hit)
         } else {
             hit.scene.background_color
         }
     }
+
+    fn albedo(&self, _hit: &Hit) -> Color {
+        color!(1.0, 1.0, 1.0)
+    }
+
+    fn apply_direct_indirect(&self, hit: &Hit) -> (Color, Color) {
+        (Color::zeros(), self.apply(hit))
+    }
 }
@@ -0,0 +1,55 @@
+use crate::prelude::*;
+
+/// Single channel image with values between 0 and 1, such as a height or displacement map
+#[derive(Debug)]
+pub struct GrayscaleTexture {
+    width: usize,
+    height: usize,
+    /// Row by row from the top of the image
+    values: Vec<Real>,
+}
+
+impl GrayscaleTexture {
+    /// Loads the brightness of the image at `path`
+    pub fn open(path: &str) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_luma16();
+        Ok(Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            values: image
+                .pixels()
+                .map(|pixel| pixel.0[0] as Real / u16::MAX as Real)
+                .collect(),
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn values(&self) -> &[Real] {
+        &self.values
+    }
+
+    /// Bilinearly filtered value at texture coordinates `(u, v)`, which repeat outside of
+    /// `[0, 1]`. `v` goes up the image like OBJ texture coordinates.
+    pub fn sample(&self, (u, v): (Real, Real)) -> Real {
+        let x = u.rem_euclid(1.0) * self.width as Real - 0.5;
+        let y = (1.0 - v.rem_euclid(1.0)) * self.height as Real - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let texel = |x: Real, y: Real| {
+            let x = (x as i64).rem_euclid(self.width as i64) as usize;
+            let y = (y as i64).rem_euclid(self.height as i64) as usize;
+            self.values[y * self.width + x]
+        };
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1.0, y0) * fx;
+        let bottom = texel(x0, y0 + 1.0) * (1.0 - fx) + texel(x0 + 1.0, y0 + 1.0) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
@@ -0,0 +1,228 @@
+use na::Matrix3;
+use serde::{Deserialize, Serialize};
+
+use crate::{color, prelude::*};
+
+/// Curve compressing the unbounded radiance of a render into the 0..1 a display can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMapping {
+    /// Cuts off everything above 1
+    #[serde(alias = "Clamp", alias = "none")]
+    Clamp,
+    /// `L / (1 + L)` on the luminance, keeping the hue
+    #[serde(alias = "Reinhard")]
+    Reinhard,
+    /// Fit of the ACES reference rendering and sRGB output transforms
+    #[serde(alias = "ACES", alias = "Aces")]
+    Aces,
+    /// Desaturates highlights toward white instead of skewing their hue
+    #[serde(alias = "AgX", alias = "Agx")]
+    Agx,
+}
+
+impl std::str::FromStr for ToneMapping {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clamp" | "none" => Ok(ToneMapping::Clamp),
+            "reinhard" => Ok(ToneMapping::Reinhard),
+            "aces" => Ok(ToneMapping::Aces),
+            "agx" => Ok(ToneMapping::Agx),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How display values are encoded in low dynamic range images
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFunction {
+    /// Stored as they are
+    #[serde(alias = "Linear")]
+    Linear,
+    /// The sRGB curve every 8 bit image is assumed to use
+    #[serde(alias = "sRGB", alias = "Srgb")]
+    Srgb,
+}
+
+/// Turns the linear radiance of a framebuffer into what is written to a low dynamic range image
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayTransform {
+    /// Scale applied before tone mapping, `2^exposure` for the exposure in stops
+    pub scale: f32,
+    pub tone_mapping: ToneMapping,
+    pub transfer: TransferFunction,
+    /// Adds noise of one step before quantizing, hiding banding in smooth gradients
+    pub dither: bool,
+}
+
+impl DisplayTransform {
+    /// Display value of a linear `color`, each channel in 0..1
+    pub fn apply(&self, color: Color) -> Color {
+        let color = (color * self.scale).map(|c| if c.is_nan() { 0.0 } else { c.max(0.0) });
+        let color = match self.tone_mapping {
+            ToneMapping::Clamp => color,
+            ToneMapping::Reinhard => reinhard(color),
+            ToneMapping::Aces => aces(color),
+            ToneMapping::Agx => agx(color),
+        };
+        color.map(|c| {
+            let c = c.clamp(0.0, 1.0);
+            match self.transfer {
+                TransferFunction::Linear => c,
+                TransferFunction::Srgb => srgb_encode(c),
+            }
+        })
+    }
+
+    /// `color` quantized to integers up to `max`, pixel `(i, j)` picks the dither noise so the
+    /// same image always comes out the same
+    pub fn quantize(&self, color: Color, i: u32, j: u32, max: u32) -> [u32; 3] {
+        let color = self.apply(color) * max as f32;
+        let mut quantized = [0; 3];
+        for (c, q) in quantized.iter_mut().enumerate() {
+            let noise = if self.dither {
+                triangular_noise(i, j, c as u32)
+            } else {
+                0.0
+            };
+            *q = (color[c] + noise).round().clamp(0.0, max as f32) as u32;
+        }
+        quantized
+    }
+
+    pub fn to_8bit(&self, color: Color, i: u32, j: u32) -> [u8; 3] {
+        self.quantize(color, i, j, u8::MAX as u32).map(|c| c as u8)
+    }
+
+    /// [`DisplayTransform::quantize`] for a `color` premultiplied by `alpha`, with the color
+    /// divided by it again since the transform only makes sense on the surface's own color.
+    /// Returns straight RGBA.
+    pub fn quantize_rgba(&self, color: Color, alpha: f32, i: u32, j: u32, max: u32) -> [u32; 4] {
+        let straight = if alpha > 0.0 {
+            color / alpha
+        } else {
+            Color::zeros()
+        };
+        let [r, g, b] = self.quantize(straight, i, j, max);
+        [r, g, b, (alpha.clamp(0.0, 1.0) * max as f32).round() as u32]
+    }
+}
+
+fn luminance(color: Color) -> f32 {
+    color.dot(&color!(0.2126, 0.7152, 0.0722))
+}
+
+fn reinhard(color: Color) -> Color {
+    let luminance = luminance(color);
+    if luminance <= 0.0 {
+        return color;
+    }
+    color * (1.0 / (1.0 + luminance))
+}
+
+/// Stephen Hill's fit, the matrices go from sRGB into the space of the curve and back
+fn aces(color: Color) -> Color {
+    #[rustfmt::skip]
+    let input = Matrix3::new(
+        0.59719, 0.35458, 0.04823,
+        0.07600, 0.90834, 0.01566,
+        0.02840, 0.13383, 0.83777,
+    );
+    #[rustfmt::skip]
+    let output = Matrix3::new(
+        1.60475, -0.53108, -0.07367,
+        -0.10208, 1.10813, -0.00605,
+        -0.00327, -0.07276, 1.07602,
+    );
+    let color = (input * color).map(|v| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.432951) + 0.238081;
+        a / b
+    });
+    output * color
+}
+
+/// The base look of AgX with its log encoding approximated by a polynomial
+fn agx(color: Color) -> Color {
+    #[rustfmt::skip]
+    let inset = Matrix3::new(
+        0.842479, 0.0784336, 0.07922375,
+        0.04232824, 0.8784686, 0.07916613,
+        0.04237565, 0.0784336, 0.879143,
+    );
+    #[rustfmt::skip]
+    let outset = Matrix3::new(
+        1.196879, -0.09802088, -0.09902974,
+        -0.05289685, 1.1519031, -0.09896118,
+        -0.05297164, -0.09804345, 1.1510737,
+    );
+    let (min_ev, max_ev) = (-12.47393_f32, 4.026069_f32);
+    let color = (inset * color).map(|c| {
+        let x = (c.max(1e-10).log2().clamp(min_ev, max_ev) - min_ev) / (max_ev - min_ev);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    // the curve comes out display encoded, undo that so sRGB isn't applied twice
+    (outset * color).map(|c| c.max(0.0).powf(2.2))
+}
+
+fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Noise in -1..1 peaking at 0, hashed from the pixel and channel
+fn triangular_noise(i: u32, j: u32, channel: u32) -> f32 {
+    let hash = |seed: u32| {
+        let mut x = i
+            .wrapping_mul(0x8da6b343)
+            .wrapping_add(j.wrapping_mul(0xd8163841))
+            .wrapping_add(channel.wrapping_mul(0xcb1ab31f))
+            .wrapping_add(seed);
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x7feb352d);
+        x ^= x >> 15;
+        x = x.wrapping_mul(0x846ca68b);
+        x ^= x >> 16;
+        x as f32 / u32::MAX as f32
+    };
+    hash(0) - hash(0x9e3779b9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_mapping_stays_in_range() {
+        for tone_mapping in [
+            ToneMapping::Clamp,
+            ToneMapping::Reinhard,
+            ToneMapping::Aces,
+            ToneMapping::Agx,
+        ] {
+            let transform = DisplayTransform {
+                scale: 1.0,
+                tone_mapping,
+                transfer: TransferFunction::Srgb,
+                dither: false,
+            };
+            let mut previous = -1.0;
+            for value in [0.0, 0.01, 0.18, 1.0, 4.0, 100.0] {
+                let display = transform.apply(color!(value, value, value));
+                assert!((0.0..=1.0).contains(&display[0]), "{:?}", tone_mapping);
+                assert!(display[0] >= previous, "{:?}", tone_mapping);
+                previous = display[0];
+            }
+            assert!(transform.apply(color!(0.0, 0.0, 0.0))[0] < 0.01);
+        }
+        assert!((srgb_encode(0.5) - 0.7354).abs() < 1e-3);
+    }
+}
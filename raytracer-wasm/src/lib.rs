@@ -1,29 +1,10 @@
-use std::str::FromStr;
-
-use js_sys::{Float32Array, Promise};
-use raytracer_lib::{parse_scene, public_consts, render_mut, render_pixel, Framebuffer, Real, Scene};
-use serde::Deserialize;
+use js_sys::Promise;
+use raytracer_lib::{
+    parse_scene, render_pass, render_sample, AccumulationBuffer, RenderSettings, Scene,
+};
 use wasm_bindgen::prelude::*;
 use web_sys::{WebGl2RenderingContext, WebGlContextAttributes, WebGlProgram, WebGlShader};
 
-#[derive(Deserialize, Debug)]
-pub struct RayTracerArgs {
-    width: u32,
-    height: u32,
-    #[serde(default)]
-    rays_per_pixel: Option<u16>,
-    #[serde(default)]
-    recursion_depth: Option<u16>,
-    #[serde(default)]
-    aspect_ratio: Option<Real>,
-    #[serde(default)]
-    disable_shadows: bool,
-    #[serde(default)]
-    render_normals: bool,
-    #[serde(default)]
-    antialias_method: Option<String>,
-}
-
 // macro for wasm log does format!
 macro_rules! log {
     ( $( $t:tt )* ) => {
@@ -34,11 +15,11 @@ macro_rules! log {
 #[wasm_bindgen]
 pub struct RayTracer {
     context: WebGl2RenderingContext,
-    fb: Framebuffer,
+    /// Refined one pass over the whole image at a time so it can be shown while it renders
+    accumulation: AccumulationBuffer,
     scene: Scene,
-    sqrt_rays_per_pixel: u16,
-    antialias_method: raytracer_lib::AntialiasMethod,
-    next_pixel: (u32, u32),
+    /// Pass and pixel the next sample goes to
+    next_sample: (u32, u32, u32),
     pub complete: bool,
 }
 
@@ -56,40 +37,30 @@ impl RayTracer {
             None => return Err(JsValue::from_str("Failed to get canvas")),
         };
 
-        // Parse the raytrace args from JSON
-        let args: RayTracerArgs = serde_wasm_bindgen::from_value(raytracer_args)?;
-
-        let scene = parse_scene(
-            &scene_json,
-            Some(args.width),
-            Some(args.height),
-            args.aspect_ratio,
-            args.recursion_depth,
-            args.disable_shadows,
-            args.render_normals,
-        )
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        // Parse the raytrace args from JSON, these override the scene file's settings
+        let settings: RenderSettings = serde_wasm_bindgen::from_value(raytracer_args)?;
+
+        // there is no file system to resolve scene data against in the browser
+        let scene = parse_scene(&scene_json, "", &settings)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        // only the main camera's image is rendered to the canvas
+        if !scene.is_single_image() {
+            return Err(JsValue::from_str(
+                "the browser can only render one mono camera",
+            ));
+        }
+        if !scene.settings.aovs().is_empty() {
+            return Err(JsValue::from_str("the browser can't render AOVs"));
+        }
 
         #[cfg(debug_assertions)]
         log!("{:#?}", scene);
 
-        let rays_per_pixel = args
-            .rays_per_pixel
-            .unwrap_or(public_consts::DEFAULT_RAYS_PER_PIXEL);
-        let sqrt_rays_per_pixel = (rays_per_pixel as f64).sqrt() as u16;
+        let width = scene.settings.image_width();
+        let height = scene.settings.image_height();
 
-        // error if rays_per_pixel is not a perfect square
-        if sqrt_rays_per_pixel * sqrt_rays_per_pixel != rays_per_pixel {
-            return Err(JsValue::from_str("rays_per_pixel must be a perfect square"));
-        }
-
-        let antialias_method = match args.antialias_method {
-            Some(ref s) => raytracer_lib::AntialiasMethod::from_str(s).unwrap(),
-            _ => raytracer_lib::AntialiasMethod::Normal,
-        };
-
-        canvas.set_width(args.width);
-        canvas.set_height(args.height);
+        canvas.set_width(width);
+        canvas.set_height(height);
 
         #[cfg(debug_assertions)]
         test_webgl2()?;
@@ -149,8 +120,9 @@ impl RayTracer {
             in vec2 texCoord;
             out vec4 fragColor;
             void main() {
-                V3 color = texture(tex, texCoord).rgb;
-                fragColor = vec4(color, 1.0);
+                vec4 color = texture(tex, texCoord);
+                // the canvas expects premultiplied alpha
+                fragColor = vec4(color.rgb * color.a, color.a);
             }
             "#,
         )?;
@@ -188,72 +160,56 @@ impl RayTracer {
 
         Ok(RayTracer {
             context,
-            fb: Framebuffer::new(args.width, args.height),
+            accumulation: AccumulationBuffer::new(width, height),
             scene,
-            sqrt_rays_per_pixel,
-            antialias_method,
-            next_pixel: (0, 0),
+            next_sample: (0, 0, 0),
             complete: false,
         })
     }
 
     #[wasm_bindgen]
     pub fn raytrace_blocking(&mut self) {
-        render_mut(
-            &mut self.fb,
-            &self.scene,
-            self.sqrt_rays_per_pixel,
-            self.antialias_method,
-            None,
-            None,
-        );
+        for _ in 0..self.passes() {
+            render_pass(&mut self.accumulation, &self.scene, None);
+        }
 
         self.complete = true;
     }
 
+    /// Adds the next `num_pixels` samples, a pass over the whole image at a time. Resolves to
+    /// the progress in pixels, reaching `width * height` once every pixel has all its rays.
     #[wasm_bindgen]
     pub fn raytrace_next_pixels(&mut self, num_pixels: u32) -> Promise {
+        let width = self.scene.settings.image_width();
+        let height = self.scene.settings.image_height();
+        let passes = self.passes();
         let mut count = 0;
-        let (mut i, mut j) = self.next_pixel;
-
-        while i < self.scene.image_width && count < num_pixels {
-            while j < self.scene.image_height && count < num_pixels {
-                render_pixel(
-                    &mut self.fb,
-                    &self.scene,
-                    self.sqrt_rays_per_pixel,
-                    self.antialias_method,
-                    i,
-                    j,
-                    None,
-                    None,
-                );
-
-                count += 1;
-                j += 1;
-            }
+        let (mut pass, mut i, mut j) = self.next_sample;
 
-            if j >= self.scene.image_height {
+        while pass < passes && count < num_pixels {
+            render_sample(&mut self.accumulation, &self.scene, i, j);
+
+            count += 1;
+            j += 1;
+            if j >= height {
                 j = 0;
                 i += 1;
             }
+            if i >= width {
+                i = 0;
+                pass += 1;
+            }
         }
 
-        self.next_pixel = (i, j);
+        self.next_sample = (pass, i, j);
 
-        // Check if we've completed the entire image
-        if i >= self.scene.image_width {
+        // Check if we've completed the last pass
+        if pass >= passes {
             self.complete = true;
         }
 
-        // Calculate total pixels processed
-        let total_pixels = if self.complete {
-            (self.scene.image_width * self.scene.image_height) as f64
-        } else {
-            (i * self.scene.image_height + j) as f64
-        };
-
-        Promise::resolve(&JsValue::from_f64(total_pixels))
+        let samples = (pass * width * height + i * height + j) as f64;
+        Promise::resolve(&JsValue::from_f64(samples / passes as f64))
     }
 
     #[wasm_bindgen]
@@ -278,28 +234,33 @@ impl RayTracer {
             WebGl2RenderingContext::NEAREST as i32,
         );
 
-        unsafe {
-            // Upload pixels to texture
-            let pixels: &[f32] = std::slice::from_raw_parts(
-                self.fb.pixels.as_ptr() as *const f32,
-                self.fb.pixels.len() * 3,
-            );
-
-            let pixels_array = Float32Array::view(pixels);
-            self.context
-                .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
-                    WebGl2RenderingContext::TEXTURE_2D,
-                    0,
-                    WebGl2RenderingContext::RGB32F as i32,
-                    self.scene.image_width as i32,
-                    self.scene.image_height as i32,
-                    0,
-                    WebGl2RenderingContext::RGB,
-                    WebGl2RenderingContext::FLOAT,
-                    Some(&pixels_array),
-                )?;
+        // Tone map and encode the pixels the same way 8 bit image files are, with straight alpha
+        let fb = self.accumulation.resolve();
+        let display = self.scene.settings.display_transform();
+        let transparent = self.scene.settings.transparent_background();
+        let mut pixels = Vec::with_capacity(fb.pixels.len() * 4);
+        for j in 0..fb.height {
+            for i in 0..fb.width {
+                let color = fb.get_pixel(i, j);
+                let alpha = if transparent { fb.get_alpha(i, j) } else { 1.0 };
+                let rgba = display.quantize_rgba(color, alpha, i, j, u8::MAX as u32);
+                pixels.extend(rgba.map(|c| c as u8));
+            }
         }
 
+        self.context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                WebGl2RenderingContext::RGBA8 as i32,
+                fb.width as i32,
+                fb.height as i32,
+                0,
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(&pixels),
+            )?;
+
         // Draw full-screen quad
         self.context.clear_color(0.0, 0.0, 0.0, 1.0);
         self.context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
@@ -308,6 +269,11 @@ impl RayTracer {
 
         Ok(())
     }
+
+    /// Every pixel gets all of its rays once this many passes are done
+    fn passes(&self) -> u32 {
+        self.scene.settings.sqrt_rays_per_pixel().pow(2) as u32
+    }
 }
 
 fn compile_shader(